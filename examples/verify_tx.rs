@@ -62,7 +62,7 @@ fn main() {
         // Don't bother checking signatures
         match elem.expect("no evaluation error") {
             miniscript::interpreter::SatisfiedConstraint::PublicKey { key, sig } => {
-                println!("Signed with {}: {}", key, sig);
+                println!("Signed with {}: {} ({:?})", key, sig.0, sig.1);
             }
             _ => {}
         }
@@ -102,7 +102,7 @@ fn main() {
     for elem in interpreter.iter(vfyfn) {
         match elem.expect("no evaluation error") {
             miniscript::interpreter::SatisfiedConstraint::PublicKey { key, sig } => {
-                println!("Signed with {}: {}", key, sig);
+                println!("Signed with {}: {} ({:?})", key, sig.0, sig.1);
             }
             _ => {}
         }
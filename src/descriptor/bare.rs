@@ -58,6 +58,24 @@ impl<Pk: MiniscriptKey> Bare<Pk> {
         self.ms
     }
 
+    /// Returns a satisfying witness and scriptSig taking the spending
+    /// path chosen by `path`. See [Miniscript::satisfy_path] for the
+    /// format of `path`.
+    pub fn get_satisfaction_path<S>(
+        &self,
+        satisfier: S,
+        path: &[usize],
+    ) -> Result<(Vec<Vec<u8>>, Script), Error>
+    where
+        Pk: ToPublicKey,
+        S: Satisfier<Pk>,
+    {
+        let ms = self.ms.satisfy_path(satisfier, path)?;
+        let script_sig = witness_to_scriptsig(&ms);
+        let witness = vec![];
+        Ok((witness, script_sig))
+    }
+
     /// get the inner
     pub fn as_inner(&self) -> &Miniscript<Pk, BareCtx> {
         &self.ms
@@ -20,6 +20,7 @@
 
 use std::{fmt, str::FromStr};
 
+use elements::hashes::sha256;
 use elements::secp256k1_zkp;
 use elements::{self, Script};
 
@@ -29,7 +30,7 @@ use {Error, MiniscriptKey, Satisfier, ToPublicKey};
 
 use super::{
     checksum::{desc_checksum, strip_checksum, verify_checksum},
-    Descriptor, DescriptorTrait, ElementsTrait, TranslatePk,
+    ConversionError, Descriptor, DescriptorPublicKey, DescriptorTrait, ElementsTrait, TranslatePk,
 };
 
 /// Create a Bare Descriptor. That is descriptor that is
@@ -64,6 +65,32 @@ impl<Pk: MiniscriptKey> Blinded<Pk> {
     pub fn into_unblinded(self) -> Descriptor<Pk> {
         self.desc
     }
+
+    /// Computes the dust value of a confidential output paying to this
+    /// descriptor, accounting for the asset and value commitments, the
+    /// surjection proof and the rangeproof. See [Descriptor::dust_value].
+    pub fn dust_value(&self, fee_rate: u64) -> Result<u64, Error>
+    where
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+    {
+        self.desc.dust_value_inner(fee_rate, true)
+    }
+}
+
+impl Blinded<DescriptorPublicKey> {
+    /// Computes the Electrum style hash of the scriptPubKey derived at
+    /// `index`, which does not depend on the blinding key. See
+    /// [Descriptor::script_hash].
+    pub fn script_hash<C: secp256k1_zkp::Verification>(
+        &self,
+        index: u32,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<sha256::Hash, ConversionError> {
+        self.desc.script_hash(index, secp)
+    }
 }
 
 impl<Pk: MiniscriptKey> fmt::Debug for Blinded<Pk> {
@@ -0,0 +1,396 @@
+// Miniscript
+// Written in 2021 by rust-miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Confidential Descriptors
+//!
+//! Implementation of the `ct(<blinding key>,<descriptor>)` descriptors of
+//! confidential Elements outputs. The blinding key is either a descriptor
+//! public key, a descriptor secret key, or a SLIP-77 master blinding key
+//! written `slip77(<hex>)` from which every script gets its own key.
+//!
+
+use std::{cmp, fmt, str::FromStr};
+
+use elements::hashes::hex::{FromHex, ToHex};
+use elements::{secp256k1_zkp, Script};
+
+use expression::{self, FromTree};
+use util::varint_len;
+use Error;
+
+use super::{
+    checksum::{desc_checksum, strip_checksum, verify_checksum},
+    Descriptor, DescriptorPublicKey, DescriptorSecretKey, DescriptorTrait, ElementsTrait,
+    Slip77MasterKey,
+};
+
+/// The blinding key of a [ConfidentialDescriptor]
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum BlindingKey {
+    /// Public blinding key, enough to produce addresses
+    Public(DescriptorPublicKey),
+    /// Secret blinding key, also allowing to unblind the outputs
+    Secret(DescriptorSecretKey),
+    /// SLIP-77 master blinding key, blinding each script with its own key
+    Slip77(Slip77MasterKey),
+}
+
+impl fmt::Display for BlindingKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlindingKey::Public(ref pk) => pk.fmt(f),
+            BlindingKey::Secret(ref sk) => sk.fmt(f),
+            BlindingKey::Slip77(ref master) => write!(f, "slip77({})", master.as_bytes().to_hex()),
+        }
+    }
+}
+
+impl FromStr for BlindingKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The expression parser keeps `slip77(..)` as a single terminal
+        if s.starts_with("slip77(") && s.ends_with(')') {
+            let hex = &s["slip77(".len()..s.len() - 1];
+            let bytes = Vec::<u8>::from_hex(hex)
+                .map_err(|_| Error::BadDescriptor(format!("Invalid slip77 key {}", hex)))?;
+            if bytes.len() != 32 {
+                return Err(Error::BadDescriptor(format!(
+                    "slip77 master blinding key must be 32 bytes, got {}",
+                    bytes.len()
+                )));
+            }
+            let mut key = [0; 32];
+            key.copy_from_slice(&bytes);
+            return Ok(BlindingKey::Slip77(Slip77MasterKey::new(key)));
+        }
+        DescriptorPublicKey::from_str(s)
+            .map(BlindingKey::Public)
+            .or_else(|_| DescriptorSecretKey::from_str(s).map(BlindingKey::Secret))
+            .map_err(|_| Error::BadDescriptor(format!("Invalid blinding key {}", s)))
+    }
+}
+
+/// A confidential descriptor, that is a descriptor along with the key
+/// blinding the outputs paying to it
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct ConfidentialDescriptor {
+    /// The blinding key
+    pub key: BlindingKey,
+    /// The unblinded descriptor
+    pub descriptor: Descriptor<DescriptorPublicKey>,
+}
+
+impl ConfidentialDescriptor {
+    /// Create a new confidential descriptor from a blinding key and a
+    /// descriptor
+    pub fn new(key: BlindingKey, descriptor: Descriptor<DescriptorPublicKey>) -> Self {
+        ConfidentialDescriptor { key, descriptor }
+    }
+
+    /// Computes the blinding public key of the output at `index`. Wildcards
+    /// of the blinding key are replaced by `index`, and SLIP-77 keys are
+    /// derived from the scriptPubKey at `index`.
+    ///
+    /// Errors if a key cannot be derived because of hardened derivation
+    /// steps. Panics if given an index ≥ 2^31
+    pub fn blinding_public_key<C>(
+        &self,
+        index: u32,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<secp256k1_zkp::PublicKey, Error>
+    where
+        C: secp256k1_zkp::Signing + secp256k1_zkp::Verification,
+    {
+        let public = match self.key {
+            BlindingKey::Public(ref pk) => pk.clone(),
+            BlindingKey::Secret(ref sk) => sk
+                .clone()
+                .derive(index)
+                .as_public(secp)
+                .map_err(|e| Error::BadDescriptor(e.to_string()))?,
+            BlindingKey::Slip77(ref master) => {
+                let spk = self.derived_descriptor(index, secp)?.script_pubkey();
+                return Ok(master.blinding_public_key(&spk, secp));
+            }
+        };
+        let key = public
+            .derive(index)
+            .derive_public_key(secp)
+            .map_err(|e| Error::BadDescriptor(e.to_string()))?;
+        Ok(key.key)
+    }
+
+    /// Computes the confidential address of the output at `index`
+    ///
+    /// Errors if a key cannot be derived because of hardened derivation
+    /// steps, and for descriptors without address. Panics if given an
+    /// index ≥ 2^31
+    pub fn address<C>(
+        &self,
+        index: u32,
+        params: &'static elements::AddressParams,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<elements::Address, Error>
+    where
+        C: secp256k1_zkp::Signing + secp256k1_zkp::Verification,
+    {
+        let blinder = self.blinding_public_key(index, secp)?;
+        self.derived_descriptor(index, secp)?
+            .blind_addr(Some(blinder), params)
+    }
+
+    /// Computes the scriptPubKey and blinding public key of the outputs at
+    /// `indices`, typically our change outputs, as needed to blind them
+    /// when constructing a confidential transaction
+    ///
+    /// Errors if a key cannot be derived because of hardened derivation
+    /// steps. Panics if given an index ≥ 2^31
+    pub fn output_blinding_pairs<C, I>(
+        &self,
+        indices: I,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<Vec<(Script, secp256k1_zkp::PublicKey)>, Error>
+    where
+        C: secp256k1_zkp::Signing + secp256k1_zkp::Verification,
+        I: IntoIterator<Item = u32>,
+    {
+        indices
+            .into_iter()
+            .map(|index| {
+                let spk = self.derived_descriptor(index, secp)?.script_pubkey();
+                Ok((spk, self.blinding_public_key(index, secp)?))
+            })
+            .collect()
+    }
+
+    /// The descriptor at `index`, with public keys
+    fn derived_descriptor<C: secp256k1_zkp::Verification>(
+        &self,
+        index: u32,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<Descriptor<bitcoin::PublicKey>, Error> {
+        self.descriptor
+            .derived_public_descriptor(index, secp)
+            .map_err(|e| Error::BadDescriptor(e.to_string()))
+    }
+}
+
+impl fmt::Display for ConfidentialDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let desc = format!("{}", self.descriptor);
+        let desc = format!("ct({},{})", self.key, strip_checksum(&desc));
+        let checksum = desc_checksum(&desc).map_err(|_| fmt::Error)?;
+        write!(f, "{}#{}", &desc, &checksum)
+    }
+}
+
+impl FromTree for ConfidentialDescriptor {
+    fn from_tree(top: &expression::Tree) -> Result<Self, Error> {
+        if top.name == "ct" && top.args.len() == 2 {
+            let key = expression::terminal(&top.args[0], BlindingKey::from_str)?;
+            if top.args[1].name == "ct" {
+                return Err(Error::BadDescriptor(
+                    "Blinding only permitted at root level".to_string(),
+                ));
+            }
+            let descriptor = Descriptor::from_tree(&top.args[1])?;
+            Ok(ConfidentialDescriptor { key, descriptor })
+        } else {
+            Err(Error::Unexpected(format!(
+                "{}({} args) while parsing ct descriptor",
+                top.name,
+                top.args.len(),
+            )))
+        }
+    }
+}
+
+impl FromStr for ConfidentialDescriptor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let desc_str = verify_checksum(s)?;
+        let top = expression::Tree::from_str(desc_str)?;
+        Self::from_tree(&top)
+    }
+}
+
+/// Computes the size of the surjection proofs and rangeproofs of
+/// `num_outputs` blinded outputs of a transaction with `num_inputs` inputs,
+/// the values being proven over `rangeproof_bits` bits (52 by default in
+/// Elements). The length prefixes of the proofs are included. The proofs
+/// are part of the output witnesses, so this is also their weight.
+///
+/// Like Elements, surjection proofs use at most 3 of the input assets, and
+/// rangeproofs have a minimum value and no exponent.
+///
+/// Panics if `rangeproof_bits` is 0 or more than 64
+pub fn confidential_proof_size(
+    num_inputs: usize,
+    num_outputs: usize,
+    rangeproof_bits: usize,
+) -> usize {
+    assert!(
+        rangeproof_bits > 0 && rangeproof_bits <= 64,
+        "rangeproofs are over 1 to 64 bits"
+    );
+    // Input count and bitmap of the used inputs, followed by the asset
+    // generator and a signature for each used input
+    let surjection_proof = 2 + (num_inputs + 7) / 8 + 32 * (1 + cmp::min(num_inputs, 3));
+    // The value is split in base 4 digits, each proven by a ring of 4 keys,
+    // except for a last ring of 2 keys for odd numbers of bits
+    let rings = (rangeproof_bits + 1) / 2;
+    let ring_keys = 4 * (rangeproof_bits / 2) + 2 * (rangeproof_bits % 2);
+    // Header, mantissa and minimum value, the commitments of all rings but
+    // the last one with their sign bits, and the Borromean signature
+    let rangeproof = 1 + 1 + 8 + (rings - 1 + 7) / 8 + 32 * (rings - 1) + 32 + 32 * ring_keys;
+    num_outputs
+        * (varint_len(surjection_proof) + surjection_proof + varint_len(rangeproof) + rangeproof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XPUB: &str = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+
+    #[test]
+    fn parse_display() {
+        let pk = "02dce16018bbbb8e36de7b394df5b5166e9adb7498be7d881a85a09aeecf76b623";
+        let wif = "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn";
+        let slip77 = "b2396b3ee20509cdb64fe24180a14a72dbd671728eaa49bac69d2bdecb5f5a04";
+        for key in &[pk.to_owned(), wif.to_owned(), format!("slip77({})", slip77)] {
+            let s = format!("ct({},elwpkh({}/1/*))", key, XPUB);
+            let desc = ConfidentialDescriptor::from_str(&s).unwrap();
+            let printed = desc.to_string();
+            assert!(printed.starts_with(&s));
+            assert_eq!(ConfidentialDescriptor::from_str(&printed).unwrap(), desc);
+        }
+        let desc =
+            ConfidentialDescriptor::from_str(&format!("ct({},elwpkh({}/1/*))", wif, XPUB)).unwrap();
+        match desc.key {
+            BlindingKey::Secret(_) => {}
+            ref key => panic!("unexpected blinding key {}", key),
+        }
+
+        for bad in &[
+            format!("ct(slip77(00),elwpkh({}))", XPUB),
+            format!("ct(slip77({0},{0}),elwpkh({1}))", slip77, XPUB),
+            format!("ct(nokey,elwpkh({}))", XPUB),
+            format!("ct({0},ct({0},elwpkh({1})))", pk, XPUB),
+            format!("ct({})", pk),
+        ] {
+            assert!(ConfidentialDescriptor::from_str(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn confidential_address() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let params = &elements::AddressParams::ELEMENTS;
+
+        // A public blinder is derived at the index of the output
+        let ct = ConfidentialDescriptor::from_str(&format!("ct({0}/7/*,elwpkh({0}/1/*))", XPUB))
+            .unwrap();
+        let address = ct.address(3, params, &secp).unwrap();
+        let blinder = DescriptorPublicKey::from_str(&format!("{}/7/3", XPUB)).unwrap();
+        assert_eq!(
+            address.blinding_pubkey,
+            Some(blinder.derive_public_key(&secp).unwrap().key)
+        );
+        assert_eq!(
+            address.script_pubkey(),
+            ct.descriptor
+                .derived_public_descriptor(3, &secp)
+                .unwrap()
+                .script_pubkey()
+        );
+
+        // A secret blinder blinds with its public key
+        let wif = "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn";
+        let ct =
+            ConfidentialDescriptor::from_str(&format!("ct({},elwpkh({}/1/*))", wif, XPUB)).unwrap();
+        let sk = bitcoin::PrivateKey::from_wif(wif).unwrap();
+        let pk = secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk.key);
+        assert_eq!(ct.blinding_public_key(0, &secp).unwrap(), pk);
+        assert_eq!(ct.blinding_public_key(5, &secp).unwrap(), pk);
+
+        // SLIP-77 keys depend on the script
+        let master = Slip77MasterKey::from_seed(&[0x42; 64]);
+        let ct = ConfidentialDescriptor::new(BlindingKey::Slip77(master.clone()), ct.descriptor);
+        let address = ct.address(2, params, &secp).unwrap();
+        let expected = master.blinding_public_key(&address.script_pubkey(), &secp);
+        assert_eq!(address.blinding_pubkey, Some(expected));
+        assert_ne!(
+            ct.blinding_public_key(2, &secp).unwrap(),
+            ct.blinding_public_key(3, &secp).unwrap()
+        );
+    }
+
+    #[test]
+    fn output_blinding_pairs() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let params = &elements::AddressParams::ELEMENTS;
+        let slip77 = "b2396b3ee20509cdb64fe24180a14a72dbd671728eaa49bac69d2bdecb5f5a04";
+        for key in &[format!("{}/7/*", XPUB), format!("slip77({})", slip77)] {
+            let s = format!("ct({},elwpkh({}/1/*))", key, XPUB);
+            let ct = ConfidentialDescriptor::from_str(&s).unwrap();
+            let pairs = ct.output_blinding_pairs(vec![0, 3], &secp).unwrap();
+            assert_eq!(pairs.len(), 2);
+            for (&index, &(ref spk, blinding_pubkey)) in [0, 3].iter().zip(pairs.iter()) {
+                let address = ct.address(index, params, &secp).unwrap();
+                assert_eq!(*spk, address.script_pubkey());
+                assert_eq!(Some(blinding_pubkey), address.blinding_pubkey);
+            }
+            assert_ne!(pairs[0], pairs[1]);
+        }
+
+        // A single blinding key is used for every output
+        let pk = "020000000000000000000000000000000000000000000000000000000000000002";
+        let ct =
+            ConfidentialDescriptor::from_str(&format!("ct({},elwpkh({}/1/*))", pk, XPUB)).unwrap();
+        let pairs = ct.output_blinding_pairs(0..2, &secp).unwrap();
+        assert_eq!(pairs[0].1, pairs[1].1);
+        assert_ne!(pairs[0].0, pairs[1].0);
+    }
+
+    #[test]
+    fn confidential_proof_size() {
+        // The Elements defaults, a 67 byte surjection proof for a single
+        // input and a 4174 byte rangeproof over 52 bits
+        assert_eq!(super::confidential_proof_size(1, 1, 52), 1 + 67 + 3 + 4174);
+        assert_eq!(
+            super::confidential_proof_size(1, 3, 52),
+            3 * (1 + 67 + 3 + 4174)
+        );
+        assert_eq!(super::confidential_proof_size(1, 0, 52), 0);
+
+        // At most 3 inputs are used, but all of them are in the bitmap
+        let surjection_proof = |inputs| super::confidential_proof_size(inputs, 1, 52) - 3 - 4174;
+        assert_eq!(surjection_proof(2), 1 + 2 + 1 + 32 * 3);
+        assert_eq!(surjection_proof(5), 1 + 2 + 1 + 32 * 4);
+        assert_eq!(surjection_proof(300), 1 + 2 + 38 + 32 * 4);
+
+        // 32 rings of 4 keys over 64 bits, and a last ring of 2 keys over
+        // an odd number of bits
+        let rangeproof = |bits| super::confidential_proof_size(1, 1, bits) - 1 - 67 - 3;
+        assert_eq!(rangeproof(64), 10 + 4 + 32 * 31 + 32 + 32 * 128);
+        assert_eq!(rangeproof(51), 10 + 4 + 32 * 25 + 32 + 32 * 102);
+        // A single ring, with a single byte length prefix
+        assert_eq!(
+            super::confidential_proof_size(1, 1, 1),
+            1 + 67 + 1 + (10 + 32 + 32 * 2)
+        );
+    }
+}
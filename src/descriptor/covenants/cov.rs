@@ -151,51 +151,73 @@ impl<Pk: MiniscriptKey, Ext: Extension<Pk>> CovenantDescriptor<Pk, Ext> {
     where
         Pk: ToPublicKey,
     {
-        let mut wit = {
-            use descriptor::CovError::MissingSighashItem;
-            let n_version = s.lookup_nversion().ok_or(MissingSighashItem(1))?;
-            let hash_prevouts = s.lookup_hashprevouts().ok_or(MissingSighashItem(1))?;
-            let hash_sequence = s.lookup_hashsequence().ok_or(MissingSighashItem(3))?;
-            // note the 3 again, for elements
-            let hash_issuances = s.lookup_hashissuances().ok_or(MissingSighashItem(3))?;
-            let outpoint = s.lookup_outpoint().ok_or(MissingSighashItem(4))?;
-            let script_code = s.lookup_scriptcode().ok_or(MissingSighashItem(5))?;
-            let value = s.lookup_value().ok_or(MissingSighashItem(6))?;
-            let n_sequence = s.lookup_nsequence().ok_or(MissingSighashItem(7))?;
-            let outputs = s.lookup_outputs().ok_or(MissingSighashItem(8))?;
-            let hash_outputs = hash256_arr(outputs);
-            let n_locktime = s.lookup_nlocktime().ok_or(MissingSighashItem(9))?;
-            let sighash_ty = s.lookup_sighashu32().ok_or(MissingSighashItem(10))?;
-
-            let (sig, hash_ty) = s
-                .lookup_sig(&self.pk)
-                .ok_or(CovError::MissingCovSignature)?;
-            // Hashtype must be the same
-            if sighash_ty != hash_ty.as_u32() {
-                return Err(CovError::CovenantSighashTypeMismatch)?;
-            }
-
-            vec![
-                Vec::from(sig.serialize_der().as_ref()), // The covenant sig
-                serialize(&sighash_ty),                  // item 10(11)
-                serialize(&n_locktime),                  // item 9(10)
-                serialize(&hash_outputs),                // item 8(9)
-                serialize(&n_sequence),                  // item 7(8)
-                serialize(&value),                       // item 6(7)
-                serialize(script_code),                  // item 5(6)
-                serialize(&outpoint),                    // item 4(5)
-                serialize(&hash_issuances),              // ELEMENTS EXTRA: item 3b(4)
-                serialize(&hash_sequence),               // item 3
-                serialize(&hash_prevouts),               // item 2
-                serialize(&n_version),                   // item 1
-            ]
-        };
-
+        let mut wit = self.cov_witness(&s)?;
         let ms_wit = self.ms.satisfy(s)?;
         wit.extend(ms_wit);
         Ok(wit)
     }
 
+    /// Create a satisfaction for the Covenant Descriptor taking the
+    /// spending path chosen by `path`. See [Miniscript::satisfy_path]
+    /// for the format of `path`.
+    pub fn satisfy_path<S: Satisfier<Pk>>(
+        &self,
+        s: S,
+        path: &[usize],
+    ) -> Result<Vec<Vec<u8>>, Error>
+    where
+        Pk: ToPublicKey,
+    {
+        let mut wit = self.cov_witness(&s)?;
+        let ms_wit = self.ms.satisfy_path(s, path)?;
+        wit.extend(ms_wit);
+        Ok(wit)
+    }
+
+    // The sighash items and covenant signature, in witness order
+    fn cov_witness<S: Satisfier<Pk>>(&self, s: &S) -> Result<Vec<Vec<u8>>, Error>
+    where
+        Pk: ToPublicKey,
+    {
+        use descriptor::CovError::MissingSighashItem;
+        let n_version = s.lookup_nversion().ok_or(MissingSighashItem(1))?;
+        let hash_prevouts = s.lookup_hashprevouts().ok_or(MissingSighashItem(1))?;
+        let hash_sequence = s.lookup_hashsequence().ok_or(MissingSighashItem(3))?;
+        // note the 3 again, for elements
+        let hash_issuances = s.lookup_hashissuances().ok_or(MissingSighashItem(3))?;
+        let outpoint = s.lookup_outpoint().ok_or(MissingSighashItem(4))?;
+        let script_code = s.lookup_scriptcode().ok_or(MissingSighashItem(5))?;
+        let value = s.lookup_value().ok_or(MissingSighashItem(6))?;
+        let n_sequence = s.lookup_nsequence().ok_or(MissingSighashItem(7))?;
+        let outputs = s.lookup_outputs().ok_or(MissingSighashItem(8))?;
+        let hash_outputs = hash256_arr(outputs);
+        let n_locktime = s.lookup_nlocktime().ok_or(MissingSighashItem(9))?;
+        let sighash_ty = s.lookup_sighashu32().ok_or(MissingSighashItem(10))?;
+
+        let (sig, hash_ty) = s
+            .lookup_sig(&self.pk)
+            .ok_or(CovError::MissingCovSignature)?;
+        // Hashtype must be the same
+        if sighash_ty != hash_ty.as_u32() {
+            return Err(CovError::CovenantSighashTypeMismatch)?;
+        }
+
+        Ok(vec![
+            Vec::from(sig.serialize_der().as_ref()), // The covenant sig
+            serialize(&sighash_ty),                  // item 10(11)
+            serialize(&n_locktime),                  // item 9(10)
+            serialize(&hash_outputs),                // item 8(9)
+            serialize(&n_sequence),                  // item 7(8)
+            serialize(&value),                       // item 6(7)
+            serialize(script_code),                  // item 5(6)
+            serialize(&outpoint),                    // item 4(5)
+            serialize(&hash_issuances),              // ELEMENTS EXTRA: item 3b(4)
+            serialize(&hash_sequence),               // item 3
+            serialize(&hash_prevouts),               // item 2
+            serialize(&n_version),                   // item 1
+        ])
+    }
+
     /// Script code for signing with covenant publickey.
     /// Use this script_code for sighash method when signing
     /// with the covenant pk. Use the [DescriptorTrait] script_code
@@ -70,7 +70,7 @@ mod tests {
         AssetId, AssetIssuance, OutPoint, Script, SigHashType, Transaction, TxIn, TxInWitness,
         TxOut, Txid,
     };
-    use interpreter::SatisfiedConstraint;
+    use interpreter::{self, SatisfiedConstraint};
     use std::str::FromStr;
     use util::{count_non_push_opcodes, witness_size};
     use Interpreter;
@@ -278,9 +278,20 @@ mod tests {
             constraints.last().unwrap(),
             &SatisfiedConstraint::PublicKey {
                 key: &desc.pk,
-                sig: sig,
+                sig: el_sig,
             }
         );
+
+        // Tampering with the sighash message (here nLockTime) must make the
+        // CHECKSIGFROMSTACK check fail, even with all other signatures accepted
+        let mut bad_wit = wit.clone();
+        bad_wit[2][0] ^= 0x01;
+        let mut interpreter =
+            Interpreter::from_txdata(&desc.script_pubkey(), &ss, &bad_wit, 0, 0).unwrap();
+        match interpreter.iter(|_, _| true).collect::<Result<Vec<_>, _>>() {
+            Err(interpreter::Error::CheckSigFromStackFailed(pk)) => assert_eq!(pk, desc.pk),
+            res => panic!("Expected CHECKSIGFROMSTACK failure, got {:?}", res),
+        }
         Ok(())
     }
 
@@ -338,6 +349,19 @@ mod tests {
         ))
         .unwrap();
         _satisfy_and_interpret(desc, sks[0]).unwrap_err();
+
+        // 3. Outputs start with the asset of the first output, so a prefix
+        // made of the asset alone enforces that output 0 pays that asset
+        for &(asset, ok) in &[(BTC_ASSET, true), ([0x42; 32], false)] {
+            let asset = confidential::Asset::Explicit(AssetId::from_slice(&asset).unwrap());
+            let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+                "elcovwsh({},outputs_pref({}))",
+                pks[0],
+                serialize(&asset).to_hex(),
+            ))
+            .unwrap();
+            assert_eq!(_satisfy_and_interpret(desc, sks[0]).is_ok(), ok);
+        }
     }
 
     // Fund output and spend tx are tests handy with code for
@@ -0,0 +1,496 @@
+// Miniscript
+// Written in 2021 by rust-miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Binary Encoding
+//!
+//! Compact binary encoding of descriptors, implemented through the
+//! `elements::encode` traits so that descriptors can be shipped along
+//! with other consensus-encoded data.
+//!
+//! A descriptor is encoded as its string template, in which every key is
+//! replaced by its index in a key table and the checksum is dropped,
+//! followed by the table itself. Keys are stored in binary form: single
+//! keys as their serialization and extended keys as their 78 byte BIP32
+//! serialization, together with their origin, derivation path and wildcard.
+//!
+//! For transfer through QR codes, the binary encoding is prefixed with the
+//! first bytes of its SHA256 hash and wrapped in uppercase bech32m, whose
+//! characters all belong to the alphanumeric mode of QR codes. The bech32m
+//! checksum only guarantees error detection on short strings, the hash
+//! protects the whole payload.
+//!
+
+use std::io;
+use std::str::FromStr;
+
+use bitcoin;
+use bitcoin::bech32::{self, FromBase32, ToBase32, Variant};
+use bitcoin::consensus::encode::VarInt;
+use bitcoin::util::bip32;
+use elements::encode::{self, Decodable, Encodable};
+use elements::hashes::{sha256, Hash};
+
+use super::{
+    Descriptor, DescriptorMultiXKey, DescriptorPublicKey, DescriptorSinglePub, DescriptorXKey,
+    Wildcard,
+};
+use {Error, ForEachKey, TranslatePk2};
+
+/// Human readable part of the QR encoding of descriptors
+const QR_HRP: &str = "eldesc";
+
+/// Number of bytes of the SHA256 hash of the payload in the QR encoding
+const QR_HASH_LEN: usize = 4;
+
+/// Maximum length of the QR encoding, the length of the BCH code
+/// underlying bech32m. The checksum does not detect any error beyond it.
+const QR_MAX_LEN: usize = 1023;
+
+/// Encode a derivation path as its length followed by each child number
+fn encode_path<W: io::Write>(
+    path: &bip32::DerivationPath,
+    mut e: W,
+) -> Result<usize, encode::Error> {
+    let mut len = VarInt(path.as_ref().len() as u64).consensus_encode(&mut e)?;
+    for child in path.as_ref() {
+        len += u32::from(*child).consensus_encode(&mut e)?;
+    }
+    Ok(len)
+}
+
+fn decode_path<D: io::BufRead>(mut d: D) -> Result<bip32::DerivationPath, encode::Error> {
+    let n = VarInt::consensus_decode(&mut d)?.0;
+    // Avoid allocating a huge vector on malformed input
+    let mut path = Vec::with_capacity(n.min(256) as usize);
+    for _ in 0..n {
+        path.push(bip32::ChildNumber::from(u32::consensus_decode(&mut d)?));
+    }
+    Ok(bip32::DerivationPath::from(path))
+}
+
+fn encode_wildcard<W: io::Write>(wildcard: Wildcard, e: W) -> Result<usize, encode::Error> {
+    let wildcard: u8 = match wildcard {
+        Wildcard::None => 0,
+        Wildcard::Unhardened => 1,
+        Wildcard::Hardened => 2,
+    };
+    wildcard.consensus_encode(e)
+}
+
+fn decode_wildcard<D: io::BufRead>(d: D) -> Result<Wildcard, encode::Error> {
+    match u8::consensus_decode(d)? {
+        0 => Ok(Wildcard::None),
+        1 => Ok(Wildcard::Unhardened),
+        2 => Ok(Wildcard::Hardened),
+        _ => Err(encode::Error::ParseFailed("Invalid wildcard")),
+    }
+}
+
+fn decode_xpub<D: io::BufRead>(mut d: D) -> Result<bip32::ExtendedPubKey, encode::Error> {
+    let mut xkey = [0u8; 78];
+    d.read_exact(&mut xkey)?;
+    bip32::ExtendedPubKey::decode(&xkey)
+        .map_err(|_| encode::Error::ParseFailed("Invalid extended public key"))
+}
+
+/// Encode an optional origin as a presence flag followed by the
+/// fingerprint and path
+fn encode_origin<W: io::Write>(
+    origin: &Option<(bip32::Fingerprint, bip32::DerivationPath)>,
+    mut e: W,
+) -> Result<usize, encode::Error> {
+    match *origin {
+        None => 0u8.consensus_encode(e),
+        Some((ref fingerprint, ref path)) => {
+            let mut len = 1u8.consensus_encode(&mut e)?;
+            len += fingerprint.into_bytes().consensus_encode(&mut e)?;
+            len += encode_path(path, &mut e)?;
+            Ok(len)
+        }
+    }
+}
+
+fn decode_origin<D: io::BufRead>(
+    mut d: D,
+) -> Result<Option<(bip32::Fingerprint, bip32::DerivationPath)>, encode::Error> {
+    match u8::consensus_decode(&mut d)? {
+        0 => Ok(None),
+        1 => {
+            let fingerprint = bip32::Fingerprint::from(&<[u8; 4]>::consensus_decode(&mut d)?[..]);
+            Ok(Some((fingerprint, decode_path(&mut d)?)))
+        }
+        _ => Err(encode::Error::ParseFailed("Invalid key origin flag")),
+    }
+}
+
+impl Encodable for DescriptorPublicKey {
+    fn consensus_encode<W: io::Write>(&self, mut e: W) -> Result<usize, encode::Error> {
+        match *self {
+            DescriptorPublicKey::SinglePub(ref single) => {
+                let mut len = 0u8.consensus_encode(&mut e)?;
+                len += encode_origin(&single.origin, &mut e)?;
+                len += single.key.to_bytes().consensus_encode(&mut e)?;
+                Ok(len)
+            }
+            DescriptorPublicKey::XPub(ref xpub) => {
+                let mut len = 1u8.consensus_encode(&mut e)?;
+                len += encode_origin(&xpub.origin, &mut e)?;
+                let xkey = xpub.xkey.encode();
+                e.write_all(&xkey)?;
+                len += xkey.len();
+                len += encode_path(&xpub.derivation_path, &mut e)?;
+                len += encode_wildcard(xpub.wildcard, &mut e)?;
+                Ok(len)
+            }
+            DescriptorPublicKey::MultiXPub(ref xpub) => {
+                let mut len = 2u8.consensus_encode(&mut e)?;
+                len += encode_origin(&xpub.origin, &mut e)?;
+                let xkey = xpub.xkey.encode();
+                e.write_all(&xkey)?;
+                len += xkey.len();
+                len += VarInt(xpub.derivation_paths.len() as u64).consensus_encode(&mut e)?;
+                for path in &xpub.derivation_paths {
+                    len += encode_path(path, &mut e)?;
+                }
+                len += encode_wildcard(xpub.wildcard, &mut e)?;
+                Ok(len)
+            }
+        }
+    }
+}
+
+impl Decodable for DescriptorPublicKey {
+    fn consensus_decode<D: io::BufRead>(mut d: D) -> Result<Self, encode::Error> {
+        match u8::consensus_decode(&mut d)? {
+            0 => {
+                let origin = decode_origin(&mut d)?;
+                let key = bitcoin::PublicKey::from_slice(&Vec::<u8>::consensus_decode(&mut d)?)
+                    .map_err(|_| encode::Error::ParseFailed("Invalid public key"))?;
+                Ok(DescriptorPublicKey::SinglePub(DescriptorSinglePub {
+                    origin,
+                    key,
+                }))
+            }
+            1 => {
+                let origin = decode_origin(&mut d)?;
+                let xkey = decode_xpub(&mut d)?;
+                let derivation_path = decode_path(&mut d)?;
+                let wildcard = decode_wildcard(&mut d)?;
+                Ok(DescriptorPublicKey::XPub(DescriptorXKey {
+                    origin,
+                    xkey,
+                    derivation_path,
+                    wildcard,
+                }))
+            }
+            2 => {
+                let origin = decode_origin(&mut d)?;
+                let xkey = decode_xpub(&mut d)?;
+                let n = VarInt::consensus_decode(&mut d)?.0;
+                let mut derivation_paths = Vec::with_capacity(n.min(256) as usize);
+                for _ in 0..n {
+                    derivation_paths.push(decode_path(&mut d)?);
+                }
+                let wildcard = decode_wildcard(&mut d)?;
+                // Same checks as the string parser, so that the key can be
+                // displayed and parsed back
+                let key = DescriptorMultiXKey::new(xkey, origin, derivation_paths, wildcard)
+                    .map_err(|_| encode::Error::ParseFailed("Invalid multipath key"))?;
+                Ok(DescriptorPublicKey::MultiXPub(key))
+            }
+            _ => Err(encode::Error::ParseFailed("Invalid descriptor key type")),
+        }
+    }
+}
+
+impl Encodable for Descriptor<DescriptorPublicKey> {
+    fn consensus_encode<W: io::Write>(&self, mut e: W) -> Result<usize, encode::Error> {
+        // Replace every key by its index in the key table
+        let mut keys: Vec<DescriptorPublicKey> = vec![];
+        self.for_each_key(|key| {
+            if !keys.contains(key.as_key()) {
+                keys.push(key.as_key().clone());
+            }
+            true
+        });
+        let template = self.translate_pk2_infallible(|pk| {
+            let index = keys.iter().position(|k| k == pk);
+            index.expect("all keys are in the table").to_string()
+        });
+        // The checksum can be recomputed when decoding
+        let template = template.to_string();
+        let template = template
+            .split('#')
+            .next()
+            .expect("split has a first element");
+
+        let mut len = template.as_bytes().to_vec().consensus_encode(&mut e)?;
+        len += VarInt(keys.len() as u64).consensus_encode(&mut e)?;
+        for key in &keys {
+            len += key.consensus_encode(&mut e)?;
+        }
+        Ok(len)
+    }
+}
+
+impl Decodable for Descriptor<DescriptorPublicKey> {
+    fn consensus_decode<D: io::BufRead>(mut d: D) -> Result<Self, encode::Error> {
+        let template = String::from_utf8(Vec::<u8>::consensus_decode(&mut d)?)
+            .map_err(|_| encode::Error::ParseFailed("Descriptor template is not UTF-8"))?;
+        let template = Descriptor::<String>::from_str(&template)
+            .map_err(|_| encode::Error::ParseFailed("Invalid descriptor template"))?;
+        let n_keys = VarInt::consensus_decode(&mut d)?.0;
+        let mut keys = Vec::with_capacity(n_keys.min(256) as usize);
+        for _ in 0..n_keys {
+            keys.push(DescriptorPublicKey::consensus_decode(&mut d)?);
+        }
+        template.translate_pk2(|index| {
+            index
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| keys.get(index))
+                .cloned()
+                .ok_or(encode::Error::ParseFailed(
+                    "Invalid key index in descriptor",
+                ))
+        })
+    }
+}
+
+impl Descriptor<DescriptorPublicKey> {
+    /// Encodes the descriptor for transfer through QR codes, as its binary
+    /// encoding prefixed with the first bytes of its SHA256 hash and
+    /// wrapped in uppercase bech32m. The QR alphanumeric mode stores such
+    /// strings in 5.5 bits per character, against 8 bits for the
+    /// descriptor string.
+    ///
+    /// Errors if the encoding is longer than 1023 characters, beyond which
+    /// bech32m does not detect errors.
+    pub fn to_qr_string(&self) -> Result<String, Error> {
+        let bytes = encode::serialize(self);
+        let mut payload = sha256::Hash::hash(&bytes)[..QR_HASH_LEN].to_vec();
+        payload.extend(bytes);
+        let qr = bech32::encode(QR_HRP, payload.to_base32(), Variant::Bech32m)
+            .expect("the human readable part is valid")
+            .to_uppercase();
+        if qr.len() > QR_MAX_LEN {
+            return Err(Error::BadDescriptor(
+                "Descriptor too large for a QR encoding".to_string(),
+            ));
+        }
+        Ok(qr)
+    }
+
+    /// Decodes a descriptor encoded by [Descriptor::to_qr_string], in
+    /// either case
+    pub fn from_qr_str(s: &str) -> Result<Self, Error> {
+        if s.len() > QR_MAX_LEN {
+            return Err(Error::BadDescriptor(
+                "QR encoding of a descriptor too long".to_string(),
+            ));
+        }
+        let (hrp, data, variant) =
+            bech32::decode(s).map_err(|e| Error::BadDescriptor(e.to_string()))?;
+        if hrp != QR_HRP || variant != Variant::Bech32m {
+            return Err(Error::BadDescriptor(
+                "Not a bech32m encoded descriptor".to_string(),
+            ));
+        }
+        let payload =
+            Vec::<u8>::from_base32(&data).map_err(|e| Error::BadDescriptor(e.to_string()))?;
+        if payload.len() < QR_HASH_LEN
+            || payload[..QR_HASH_LEN] != sha256::Hash::hash(&payload[QR_HASH_LEN..])[..QR_HASH_LEN]
+        {
+            return Err(Error::BadDescriptor(
+                "Hash mismatch in the QR encoding of a descriptor".to_string(),
+            ));
+        }
+        encode::deserialize(&payload[QR_HASH_LEN..])
+            .map_err(|e| Error::BadDescriptor(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bech32::{self, ToBase32, Variant};
+    use bitcoin::util::bip32;
+    use elements::encode::{deserialize, serialize};
+    use elements::hashes::{sha256, Hash};
+    use std::str::FromStr;
+    use {Descriptor, DescriptorPublicKey};
+
+    #[test]
+    fn binary_roundtrip() {
+        let descs = [
+            "elwpkh([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*)",
+            "elsh(wsh(or_d(pk(020000000000000000000000000000000000000000000000000000000000000002),and_v(v:pkh(tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/0h/*h),older(144)))))",
+            "elsh(multi(2,020000000000000000000000000000000000000000000000000000000000000002,020000000000000000000000000000000000000000000000000000000000000002))",
+            "elwpkh([d34db33f/84'/1'/0']tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/<0;1;7>/*)",
+            "elpk(04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235)",
+        ];
+        for s in descs.iter() {
+            let desc = Descriptor::<DescriptorPublicKey>::from_str(s).unwrap();
+            let bytes = serialize(&desc);
+            assert!(bytes.len() < desc.to_string().len());
+            let decoded: Descriptor<DescriptorPublicKey> = deserialize(&bytes).unwrap();
+            assert_eq!(decoded, desc);
+            assert_eq!(decoded.to_string(), desc.to_string());
+        }
+    }
+
+    #[test]
+    fn binary_decode_errors() {
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(
+            "elwpkh(020000000000000000000000000000000000000000000000000000000000000002)",
+        )
+        .unwrap();
+        let mut bytes = serialize(&desc);
+        // Truncated key table
+        assert!(deserialize::<Descriptor<DescriptorPublicKey>>(&bytes[..bytes.len() - 1]).is_err());
+        // Invalid key type
+        let key_start = bytes.len() - 36;
+        bytes[key_start] = 7;
+        assert!(deserialize::<Descriptor<DescriptorPublicKey>>(&bytes).is_err());
+    }
+
+    #[test]
+    fn binary_multipath_checks() {
+        let xpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed";
+        let key = DescriptorPublicKey::from_str(&format!("{}/<0;1>/2/*", xpub)).unwrap();
+        let multi = match key {
+            DescriptorPublicKey::MultiXPub(ref multi) => multi.clone(),
+            _ => unreachable!(),
+        };
+        let with_paths = |paths: &[&str]| {
+            let mut multi = multi.clone();
+            multi.derivation_paths = paths
+                .iter()
+                .map(|p| bip32::DerivationPath::from_str(p).unwrap())
+                .collect();
+            serialize(&DescriptorPublicKey::MultiXPub(multi))
+        };
+
+        // Valid keys survive the binary and the string encodings
+        for paths in [&["m/0/2", "m/1/2"][..], &["m/0/5", "m/0/3", "m/0/4"]].iter() {
+            let decoded: DescriptorPublicKey = deserialize(&with_paths(paths)).unwrap();
+            assert_eq!(serialize(&decoded), with_paths(paths));
+            assert_eq!(
+                DescriptorPublicKey::from_str(&decoded.to_string()).unwrap(),
+                decoded
+            );
+        }
+        // Keys which cannot be written with a single multipath step
+        for paths in [
+            &["m/0/2"][..],
+            &["m/0/2", "m/0/2"],
+            &["m/0/2", "m/1/3"],
+            &["m/0/2", "m/1/2", "m/0/2"],
+            &["m/0/2", "m/1"],
+        ]
+        .iter()
+        {
+            let bytes = with_paths(paths);
+            assert!(deserialize::<DescriptorPublicKey>(&bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn qr_roundtrip() {
+        let descs = [
+            "elwpkh([d34db33f/84'/1'/0']tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/0/*)",
+            "elsh(wsh(or_d(pk(020000000000000000000000000000000000000000000000000000000000000002),and_v(v:pkh(tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/0h/*h),older(144)))))",
+            "elwpkh([d34db33f/84'/1'/0']tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/<0;1>/*)",
+        ];
+        for s in descs.iter() {
+            let desc = Descriptor::<DescriptorPublicKey>::from_str(s).unwrap();
+            let qr = desc.to_qr_string().unwrap();
+            assert!(qr.starts_with("ELDESC1"));
+            // Only characters of the QR alphanumeric mode
+            assert!(qr
+                .chars()
+                .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase()));
+            assert!(qr.len() * 11 / 2 < desc.to_string().len() * 8);
+
+            let decoded = Descriptor::<DescriptorPublicKey>::from_qr_str(&qr).unwrap();
+            assert_eq!(decoded, desc);
+            assert_eq!(decoded.to_string(), desc.to_string());
+            let lower = Descriptor::<DescriptorPublicKey>::from_qr_str(&qr.to_lowercase());
+            assert_eq!(lower.unwrap(), desc);
+        }
+    }
+
+    #[test]
+    fn qr_decode_errors() {
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(
+            "elwpkh(020000000000000000000000000000000000000000000000000000000000000002)",
+        )
+        .unwrap();
+        let qr = desc.to_qr_string().unwrap();
+
+        // A single wrong character breaks the checksum
+        let mut bad = qr.into_bytes();
+        bad[20] = if bad[20] == b'Q' { b'P' } else { b'Q' };
+        let bad = String::from_utf8(bad).unwrap();
+        assert!(Descriptor::<DescriptorPublicKey>::from_qr_str(&bad).is_err());
+
+        // Other human readable parts and bech32 are rejected
+        let payload = |bytes: &[u8]| {
+            let mut payload = sha256::Hash::hash(bytes)[..4].to_vec();
+            payload.extend(bytes);
+            payload.to_base32()
+        };
+        let data = payload(&serialize(&desc));
+        for &(hrp, variant) in &[("other", Variant::Bech32m), ("eldesc", Variant::Bech32)] {
+            let s = bech32::encode(hrp, data.clone(), variant).unwrap();
+            assert!(Descriptor::<DescriptorPublicKey>::from_qr_str(&s).is_err());
+        }
+        // As are trailing bytes
+        let mut bytes = serialize(&desc);
+        bytes.push(0);
+        let s = bech32::encode("eldesc", payload(&bytes), Variant::Bech32m).unwrap();
+        assert!(Descriptor::<DescriptorPublicKey>::from_qr_str(&s).is_err());
+        // And payloads whose hash does not match, even with a valid bech32m
+        // checksum
+        let mut data = payload(&serialize(&desc));
+        let last = data.len() - 1;
+        data[last] = bech32::u5::try_from_u8(data[last].to_u8() ^ 1).unwrap();
+        let s = bech32::encode("eldesc", data, Variant::Bech32m).unwrap();
+        assert!(Descriptor::<DescriptorPublicKey>::from_qr_str(&s).is_err());
+    }
+
+    #[test]
+    fn qr_large_multisig() {
+        let xpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed";
+        let multisig = |n: usize| {
+            let keys: Vec<String> = (0..n).map(|i| format!("{}/{}/*", xpub, i)).collect();
+            let s = format!("elwsh(sortedmulti(2,{}))", keys.join(","));
+            Descriptor::<DescriptorPublicKey>::from_str(&s).unwrap()
+        };
+
+        // Past 90 characters, the hash catches what bech32m may not
+        let desc = multisig(3);
+        let qr = desc.to_qr_string().unwrap();
+        assert!(qr.len() > 90);
+        assert_eq!(
+            Descriptor::<DescriptorPublicKey>::from_qr_str(&qr).unwrap(),
+            desc
+        );
+
+        // Past 1023 characters, the descriptor is not encoded at all
+        let desc = multisig(15);
+        assert!(serialize(&desc).len() * 8 / 5 > 1023);
+        assert!(desc.to_qr_string().is_err());
+        let s = bech32::encode("eldesc", serialize(&desc).to_base32(), Variant::Bech32m).unwrap();
+        assert!(Descriptor::<DescriptorPublicKey>::from_qr_str(&s).is_err());
+    }
+}
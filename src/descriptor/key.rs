@@ -1,6 +1,10 @@
-use std::{error, fmt, str::FromStr};
+use std::{cmp, error, fmt, hash, ops::Range, str::FromStr};
 
-use bitcoin::{self, util::bip32, XpubIdentifier};
+use bitcoin::{
+    self,
+    util::{base58, bip32},
+    XpubIdentifier,
+};
 
 use elements::{
     hashes::hex::FromHex,
@@ -9,6 +13,8 @@ use elements::{
 };
 use MiniscriptKey;
 
+use super::DescriptorType;
+
 /// The MiniscriptKey corresponding to Descriptors. This can
 /// either be Single public key or a Xpub
 #[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
@@ -17,8 +23,15 @@ pub enum DescriptorPublicKey {
     SinglePub(DescriptorSinglePub),
     /// Xpub
     XPub(DescriptorXKey<bip32::ExtendedPubKey>),
+    /// Xpub with several parallel derivation paths, e.g. `xpub/<0;1>/*`
+    MultiXPub(DescriptorMultiXKey<bip32::ExtendedPubKey>),
 }
 
+/// A descriptor public key without wildcard nor multipath step, which
+/// stands for a single public key
+#[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
+pub struct DefiniteDescriptorKey(DescriptorPublicKey);
+
 /// A Single Descriptor Key with optional origin information
 #[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
 pub struct DescriptorSinglePub {
@@ -29,7 +42,7 @@ pub struct DescriptorSinglePub {
 }
 
 /// A Single Descriptor Secret Key with optional origin information
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DescriptorSinglePriv {
     /// Origin information
     pub origin: Option<bip32::KeySource>,
@@ -38,7 +51,10 @@ pub struct DescriptorSinglePriv {
 }
 
 /// A Secret Key that can be either a single key or an Xprv
-#[derive(Debug)]
+///
+/// Secret keys are compared, ordered and hashed through their string
+/// representation, since the underlying key types are not ordered.
+#[derive(Debug, Clone)]
 pub enum DescriptorSecretKey {
     /// Single Secret Key
     SinglePriv(DescriptorSinglePriv),
@@ -46,24 +62,45 @@ pub enum DescriptorSecretKey {
     XPrv(DescriptorXKey<bip32::ExtendedPrivKey>),
 }
 
+impl PartialEq for DescriptorSecretKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl Eq for DescriptorSecretKey {}
+
+impl PartialOrd for DescriptorSecretKey {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DescriptorSecretKey {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.to_string().cmp(&other.to_string())
+    }
+}
+
+impl hash::Hash for DescriptorSecretKey {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.to_string().hash(state)
+    }
+}
+
 impl fmt::Display for DescriptorSecretKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             &DescriptorSecretKey::SinglePriv(ref sk) => {
-                maybe_fmt_master_id(f, &sk.origin)?;
+                maybe_fmt_master_id(f, &sk.origin, HardenedMarker::Apostrophe)?;
                 sk.key.fmt(f)?;
                 Ok(())
             }
             &DescriptorSecretKey::XPrv(ref xprv) => {
-                maybe_fmt_master_id(f, &xprv.origin)?;
+                maybe_fmt_master_id(f, &xprv.origin, HardenedMarker::Apostrophe)?;
                 xprv.xkey.fmt(f)?;
-                fmt_derivation_path(f, &xprv.derivation_path)?;
-                match xprv.wildcard {
-                    Wildcard::None => {}
-                    Wildcard::Unhardened => write!(f, "/*")?,
-                    Wildcard::Hardened => write!(f, "/*h")?,
-                }
-                Ok(())
+                fmt_derivation_path(f, &xprv.derivation_path, HardenedMarker::Apostrophe)?;
+                fmt_wildcard(f, xprv.wildcard, HardenedMarker::H)
             }
         }
     }
@@ -71,7 +108,7 @@ impl fmt::Display for DescriptorSecretKey {
 
 /// Trait for "extended key" types like `xpub` and `xprv`. Used internally to generalize parsing and
 /// handling of `bip32::ExtendedPubKey` and `bip32::ExtendedPrivKey`.
-pub trait InnerXKey: fmt::Display + FromStr {
+pub trait InnerXKey: fmt::Display + FromStr<Err = bip32::Error> {
     /// Returns the fingerprint of the key
     fn xkey_fingerprint<C: Signing>(&self, secp: &Secp256k1<C>) -> bip32::Fingerprint;
 
@@ -101,6 +138,26 @@ impl InnerXKey for bip32::ExtendedPrivKey {
     }
 }
 
+/// The marker of hardened derivation steps in the string representation
+/// of keys. Both are accepted when parsing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum HardenedMarker {
+    /// Apostrophe, e.g. `0'`, used by default
+    Apostrophe,
+    /// Letter h, e.g. `0h`, which needs no escaping in shells and JSON
+    H,
+}
+
+impl HardenedMarker {
+    /// The suffix of hardened steps
+    fn as_str(self) -> &'static str {
+        match self {
+            HardenedMarker::Apostrophe => "'",
+            HardenedMarker::H => "h",
+        }
+    }
+}
+
 /// Whether a descriptor has a wildcard in it
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Wildcard {
@@ -121,7 +178,24 @@ pub struct DescriptorXKey<K: InnerXKey> {
     pub xkey: K,
     /// The derivation path
     pub derivation_path: bip32::DerivationPath,
-    /// Whether the descriptor is wildcard
+    /// Whether the descriptor is wildcard. A wildcard is always the last
+    /// step of the derivation path: keys like `xpub/0/*/0` are rejected
+    pub wildcard: Wildcard,
+}
+
+/// Instance of an extended key with origin and several derivation paths,
+/// written with a single multipath step such as `xpub/<0;1>/*`
+#[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
+pub struct DescriptorMultiXKey<K: InnerXKey> {
+    /// Origin information
+    pub origin: Option<(bip32::Fingerprint, bip32::DerivationPath)>,
+    /// The extended key
+    pub xkey: K,
+    /// The derivation paths, one per branch. They all have the same length
+    /// and only differ at the multipath step
+    pub derivation_paths: Vec<bip32::DerivationPath>,
+    /// Whether the descriptor is wildcard, the wildcard being shared by
+    /// all the branches
     pub wildcard: Wildcard,
 }
 
@@ -164,7 +238,7 @@ impl DescriptorXKey<bip32::ExtendedPrivKey> {
         let derived_xprv = self
             .xkey
             .derive_priv(&secp, &deriv_on_hardened)
-            .map_err(|_| DescriptorKeyParseError("Unable to derive the hardened steps"))?;
+            .map_err(DescriptorKeyParseError::HardenedDerivation)?;
         let xpub = bip32::ExtendedPubKey::from_private(&secp, &derived_xprv);
 
         let origin = match &self.origin {
@@ -192,38 +266,165 @@ impl DescriptorXKey<bip32::ExtendedPrivKey> {
 }
 
 /// Descriptor Key parsing errors
-// FIXME: replace with error enums
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub struct DescriptorKeyParseError(&'static str);
+#[derive(Debug, PartialEq, Clone)]
+pub enum DescriptorKeyParseError {
+    /// The key is shorter than any valid key
+    TooShort,
+    /// The key contains an unprintable character
+    UnprintableCharacter,
+    /// The key is empty
+    EmptyKey,
+    /// A key origin was opened with '[' but never closed
+    UnclosedOrigin,
+    /// There is no master fingerprint after '['
+    MissingFingerprint,
+    /// The master fingerprint is not 8 characters long
+    FingerprintLength,
+    /// The master fingerprint is not valid hex
+    BadFingerprint,
+    /// A wildcard appears in the key origin
+    WildcardInOrigin,
+    /// The derivation path of the key origin is invalid
+    BadOriginPath(bip32::Error),
+    /// There is no key after the key origin
+    MissingKey,
+    /// There is no extended key before the derivation path
+    MissingXKey,
+    /// There are several ']' in the key
+    MultipleClose,
+    /// The extended key is invalid
+    InvalidXKey(bip32::Error),
+    /// The derivation path after the extended key is invalid
+    BadDerivationPath(bip32::Error),
+    /// There are several wildcards in the derivation path
+    MultipleWildcards,
+    /// The wildcard is not the last step of the derivation path
+    WildcardNotLast,
+    /// A step starts with '*' but is not a valid wildcard
+    MalformedWildcard,
+    /// A multipath key has no multipath step
+    MissingMultipathStep,
+    /// There are several multipath steps in the derivation path
+    MultipleMultipathSteps,
+    /// The multipath step is not of the form `<a;b;...>`
+    MalformedMultipathStep,
+    /// A multipath step is nested in another
+    NestedMultipathSteps,
+    /// The multipath step has less than two branches
+    SingleMultipathBranch,
+    /// The multipath step has the same branch several times
+    DuplicateMultipathBranch,
+    /// The derivation paths of a multipath key have different lengths
+    MultipathLengthMismatch,
+    /// The single public key does not start with 02, 03 or 04
+    BadPubkeyPrefix,
+    /// The single public key is not valid hex or has the wrong length
+    BadPubkey,
+    /// The single public key is not a valid curve point
+    InvalidCurvePoint,
+    /// The single private key is not a valid WIF key
+    BadWif,
+    /// The hardened steps of an extended private key could not be derived
+    HardenedDerivation(bip32::Error),
+}
 
 impl fmt::Display for DescriptorKeyParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(self.0)
+        f.write_str(match *self {
+            DescriptorKeyParseError::TooShort => {
+                "Key too short (<66 char), doesn't match any format"
+            }
+            DescriptorKeyParseError::UnprintableCharacter => "Encountered an unprintable character",
+            DescriptorKeyParseError::EmptyKey => "Empty key",
+            DescriptorKeyParseError::UnclosedOrigin => "Unclosed '['",
+            DescriptorKeyParseError::MissingFingerprint => "No master fingerprint found after '['",
+            DescriptorKeyParseError::FingerprintLength => {
+                "Master fingerprint should be 8 characters long"
+            }
+            DescriptorKeyParseError::BadFingerprint => {
+                "Malformed master fingerprint, expected 8 hex chars"
+            }
+            DescriptorKeyParseError::WildcardInOrigin => {
+                "Wildcard '*' may not appear in a key origin."
+            }
+            DescriptorKeyParseError::BadOriginPath(_) => {
+                "Error while parsing master derivation path"
+            }
+            DescriptorKeyParseError::MissingKey => "No key after origin.",
+            DescriptorKeyParseError::MissingXKey => "No key found after origin description",
+            DescriptorKeyParseError::MultipleClose => "Multiple ']' in Descriptor Public Key",
+            DescriptorKeyParseError::InvalidXKey(_) => "Error while parsing xkey.",
+            DescriptorKeyParseError::BadDerivationPath(_) => {
+                "Error while parsing key derivation path"
+            }
+            DescriptorKeyParseError::MultipleWildcards => {
+                "Only a single wildcard '*' may appear in a derivation path."
+            }
+            DescriptorKeyParseError::WildcardNotLast => {
+                "'*' may only appear as last element in a derivation path."
+            }
+            DescriptorKeyParseError::MalformedWildcard => {
+                "Malformed wildcard, expected '*', '*h' or \"*'\"."
+            }
+            DescriptorKeyParseError::MissingMultipathStep => "No multipath step in derivation path",
+            DescriptorKeyParseError::MultipleMultipathSteps => {
+                "Only a single multipath step may appear in a derivation path"
+            }
+            DescriptorKeyParseError::MalformedMultipathStep => "Malformed multipath step",
+            DescriptorKeyParseError::NestedMultipathSteps => {
+                "Nested multipath steps are not allowed"
+            }
+            DescriptorKeyParseError::SingleMultipathBranch => {
+                "A multipath step needs at least two branches"
+            }
+            DescriptorKeyParseError::DuplicateMultipathBranch => {
+                "Duplicate branch in multipath step"
+            }
+            DescriptorKeyParseError::MultipathLengthMismatch => {
+                "The derivation paths of a multipath key have different lengths"
+            }
+            DescriptorKeyParseError::BadPubkeyPrefix => {
+                "Only publickeys with prefixes 02/03/04 are allowed"
+            }
+            DescriptorKeyParseError::BadPubkey => "Error while parsing simple public key",
+            DescriptorKeyParseError::InvalidCurvePoint => "Public key is not a valid curve point",
+            DescriptorKeyParseError::BadWif => "Error while parsing a WIF private key",
+            DescriptorKeyParseError::HardenedDerivation(_) => "Unable to derive the hardened steps",
+        })
     }
 }
 
-impl error::Error for DescriptorKeyParseError {}
+impl error::Error for DescriptorKeyParseError {
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            DescriptorKeyParseError::BadOriginPath(ref e)
+            | DescriptorKeyParseError::InvalidXKey(ref e)
+            | DescriptorKeyParseError::BadDerivationPath(ref e)
+            | DescriptorKeyParseError::HardenedDerivation(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<bip32::Error> for DescriptorKeyParseError {
+    fn from(e: bip32::Error) -> Self {
+        DescriptorKeyParseError::InvalidXKey(e)
+    }
+}
 
 impl fmt::Display for DescriptorPublicKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            DescriptorPublicKey::SinglePub(ref pk) => {
-                maybe_fmt_master_id(f, &pk.origin)?;
-                pk.key.fmt(f)?;
-                Ok(())
-            }
-            DescriptorPublicKey::XPub(ref xpub) => {
-                maybe_fmt_master_id(f, &xpub.origin)?;
-                xpub.xkey.fmt(f)?;
-                fmt_derivation_path(f, &xpub.derivation_path)?;
-                match xpub.wildcard {
-                    Wildcard::None => {}
-                    Wildcard::Unhardened => write!(f, "/*")?,
-                    Wildcard::Hardened => write!(f, "/*h")?,
-                }
-                Ok(())
-            }
-        }
+        self.fmt_with_markers(f, HardenedMarker::Apostrophe, HardenedMarker::H)
+    }
+}
+
+/// Displays a key with all its hardened steps, including the wildcard,
+/// written with the same marker
+struct WithHardenedMarker<'a>(&'a DescriptorPublicKey, HardenedMarker);
+
+impl<'a> fmt::Display for WithHardenedMarker<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_with_markers(f, self.1, self.1)
     }
 }
 
@@ -248,47 +449,248 @@ impl DescriptorSecretKey {
             }
         })
     }
+
+    /// If this secret key has a wildcard, replace it by the given index. A
+    /// hardened wildcard becomes a hardened step, which [`as_public`] then
+    /// applies on the private key.
+    ///
+    /// Panics if given an index ≥ 2^31
+    ///
+    /// [`as_public`]: DescriptorSecretKey::as_public
+    pub fn derive(mut self, index: u32) -> DescriptorSecretKey {
+        if let DescriptorSecretKey::XPrv(mut xprv) = self {
+            match xprv.wildcard {
+                Wildcard::None => {}
+                Wildcard::Unhardened => {
+                    xprv.derivation_path = xprv
+                        .derivation_path
+                        .into_child(bip32::ChildNumber::from_normal_idx(index).unwrap())
+                }
+                Wildcard::Hardened => {
+                    xprv.derivation_path = xprv
+                        .derivation_path
+                        .into_child(bip32::ChildNumber::from_hardened_idx(index).unwrap())
+                }
+            }
+            xprv.wildcard = Wildcard::None;
+            self = DescriptorSecretKey::XPrv(xprv);
+        }
+        self
+    }
+
+    /// Builds the extended private key at `path` from a seed, such as a
+    /// BIP39 seed. The origin of the key is set to the fingerprint of the
+    /// master key derived from the seed and to `path`, and the key has no
+    /// further derivation steps.
+    pub fn from_seed<C: Signing>(
+        seed: &[u8],
+        path: bip32::DerivationPath,
+        network: bitcoin::Network,
+        secp: &Secp256k1<C>,
+    ) -> Result<DescriptorSecretKey, DerivationError> {
+        let master = bip32::ExtendedPrivKey::new_master(network, seed)
+            .map_err(DerivationError::MasterDerivation)?;
+        let xkey = master
+            .derive_priv(secp, &path)
+            .map_err(DerivationError::PrivateDerivation)?;
+        Ok(DescriptorSecretKey::XPrv(DescriptorXKey {
+            origin: Some((master.fingerprint(secp), path)),
+            xkey,
+            derivation_path: bip32::DerivationPath::from(vec![]),
+            wildcard: Wildcard::None,
+        }))
+    }
+
+    /// Derives the single private key at every index of `range`, replacing
+    /// the wildcard of an xprv by the index. Each key carries its full
+    /// origin, from the master fingerprint down to the derived child, so
+    /// that it can be matched against PSET derivation paths.
+    ///
+    /// There is one key per index, so that the result lines up with
+    /// `range`: keys without a wildcard derive to the same key at every
+    /// index, which is repeated. Errors if the range contains an index
+    /// ≥ 2^31.
+    pub fn derive_range<C: Signing>(
+        &self,
+        range: Range<u32>,
+        secp: &Secp256k1<C>,
+    ) -> Result<Vec<DescriptorSinglePriv>, DerivationError> {
+        let xprv = match *self {
+            DescriptorSecretKey::SinglePriv(ref sk) => {
+                let key = DescriptorSinglePriv {
+                    origin: sk.origin.clone(),
+                    key: sk.key,
+                };
+                return Ok(range.map(|_| key.clone()).collect());
+            }
+            DescriptorSecretKey::XPrv(ref xprv) => xprv,
+        };
+        let derive = |index: u32| {
+            let path = match xprv.wildcard {
+                Wildcard::None => xprv.derivation_path.clone(),
+                Wildcard::Unhardened => xprv.derivation_path.child(
+                    bip32::ChildNumber::from_normal_idx(index)
+                        .map_err(|_| DerivationError::IndexOutOfRange)?,
+                ),
+                Wildcard::Hardened => xprv.derivation_path.child(
+                    bip32::ChildNumber::from_hardened_idx(index)
+                        .map_err(|_| DerivationError::IndexOutOfRange)?,
+                ),
+            };
+            let derived = xprv
+                .xkey
+                .derive_priv(secp, &path)
+                .map_err(DerivationError::PrivateDerivation)?;
+            let origin = match xprv.origin {
+                Some((fingerprint, ref origin_path)) => (fingerprint, origin_path.extend(&path)),
+                None => (xprv.xkey.fingerprint(secp), path),
+            };
+            Ok(DescriptorSinglePriv {
+                origin: Some(origin),
+                key: derived.private_key,
+            })
+        };
+        match xprv.wildcard {
+            Wildcard::None => {
+                let key = derive(0)?;
+                Ok(range.map(|_| key.clone()).collect())
+            }
+            Wildcard::Unhardened | Wildcard::Hardened => range.map(derive).collect(),
+        }
+    }
+
+    /// Computes the public key at `index`, replacing the wildcard of an xprv
+    /// by the index. Hardened steps, including a hardened wildcard, are
+    /// applied on the private key like [DescriptorSecretKey::as_public]
+    /// does.
+    ///
+    /// Errors with [DerivationError::IndexOutOfRange] if given an index
+    /// ≥ 2^31.
+    pub fn to_public_key_at<C: Signing>(
+        &self,
+        index: u32,
+        secp: &Secp256k1<C>,
+    ) -> Result<bitcoin::PublicKey, DerivationError> {
+        self.derive_range(index..index.saturating_add(1), secp)?
+            .pop()
+            .map(|sk| sk.key.public_key(secp))
+            .ok_or(DerivationError::IndexOutOfRange)
+    }
 }
 
 /// Writes the fingerprint of the origin, if there is one.
 fn maybe_fmt_master_id(
     f: &mut fmt::Formatter,
     origin: &Option<(bip32::Fingerprint, bip32::DerivationPath)>,
+    marker: HardenedMarker,
 ) -> fmt::Result {
     if let Some((ref master_id, ref master_deriv)) = *origin {
         fmt::Formatter::write_str(f, "[")?;
         for byte in master_id.into_bytes().iter() {
             write!(f, "{:02x}", byte)?;
         }
-        fmt_derivation_path(f, master_deriv)?;
+        fmt_derivation_path(f, master_deriv, marker)?;
         fmt::Formatter::write_str(f, "]")?;
     }
 
     Ok(())
 }
 
+/// Parses a derivation step, hardened steps being marked with `'`, `h`
+/// or `H`
+fn parse_child_number(s: &str) -> Result<bip32::ChildNumber, bip32::Error> {
+    let mut chars = s.chars();
+    if chars.next_back() == Some('H') {
+        bip32::ChildNumber::from_str(&format!("{}h", chars.as_str()))
+    } else {
+        bip32::ChildNumber::from_str(s)
+    }
+}
+
+/// Writes a derivation step, hardened steps being marked with `marker`
+fn fmt_child_number(
+    f: &mut fmt::Formatter,
+    child: bip32::ChildNumber,
+    marker: HardenedMarker,
+) -> fmt::Result {
+    match child {
+        bip32::ChildNumber::Normal { index } => write!(f, "{}", index),
+        bip32::ChildNumber::Hardened { index } => write!(f, "{}{}", index, marker.as_str()),
+    }
+}
+
 /// Writes a derivation path to the formatter, no leading 'm'
-fn fmt_derivation_path(f: &mut fmt::Formatter, path: &bip32::DerivationPath) -> fmt::Result {
+fn fmt_derivation_path(
+    f: &mut fmt::Formatter,
+    path: &bip32::DerivationPath,
+    marker: HardenedMarker,
+) -> fmt::Result {
     for child in path {
-        write!(f, "/{}", child)?;
+        f.write_str("/")?;
+        fmt_child_number(f, *child, marker)?;
+    }
+    Ok(())
+}
+
+/// Writes parallel derivation paths to the formatter, no leading 'm'. The
+/// steps shared by all paths are written once, the others as `<a;b;...>`
+fn fmt_derivation_paths(
+    f: &mut fmt::Formatter,
+    paths: &[bip32::DerivationPath],
+    marker: HardenedMarker,
+) -> fmt::Result {
+    let len = paths.first().map_or(0, |path| path.as_ref().len());
+    for i in 0..len {
+        let child = paths[0][i];
+        if paths.iter().all(|path| path[i] == child) {
+            f.write_str("/")?;
+            fmt_child_number(f, child, marker)?;
+        } else {
+            f.write_str("/<")?;
+            for (n, path) in paths.iter().enumerate() {
+                if n > 0 {
+                    f.write_str(";")?;
+                }
+                fmt_child_number(f, path[i], marker)?;
+            }
+            f.write_str(">")?;
+        }
     }
     Ok(())
 }
 
+/// Writes the wildcard of a key, a hardened wildcard being marked with
+/// `marker`
+fn fmt_wildcard(f: &mut fmt::Formatter, wildcard: Wildcard, marker: HardenedMarker) -> fmt::Result {
+    match wildcard {
+        Wildcard::None => Ok(()),
+        Wildcard::Unhardened => f.write_str("/*"),
+        Wildcard::Hardened => write!(f, "/*{}", marker.as_str()),
+    }
+}
+
 impl FromStr for DescriptorPublicKey {
     type Err = DescriptorKeyParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         // A "raw" public key without any origin is the least we accept.
         if s.len() < 66 {
-            return Err(DescriptorKeyParseError(
-                "Key too short (<66 char), doesn't match any format",
-            ));
+            return Err(DescriptorKeyParseError::TooShort);
         }
 
         let (key_part, origin) = DescriptorXKey::<bip32::ExtendedPubKey>::parse_xkey_origin(s)?;
 
-        if key_part.contains("pub") {
+        if key_part.contains("pub") && key_part.contains('<') {
+            let (xpub, derivation_paths, wildcard) =
+                DescriptorXKey::<bip32::ExtendedPubKey>::parse_xkey_multi_deriv(key_part)?;
+
+            Ok(DescriptorPublicKey::MultiXPub(DescriptorMultiXKey::new(
+                xpub,
+                origin,
+                derivation_paths,
+                wildcard,
+            )?))
+        } else if key_part.contains("pub") {
             let (xpub, derivation_path, wildcard) =
                 DescriptorXKey::<bip32::ExtendedPubKey>::parse_xkey_deriv(key_part)?;
 
@@ -302,12 +704,16 @@ impl FromStr for DescriptorPublicKey {
             if key_part.len() >= 2
                 && !(&key_part[0..2] == "02" || &key_part[0..2] == "03" || &key_part[0..2] == "04")
             {
-                return Err(DescriptorKeyParseError(
-                    "Only publickeys with prefixes 02/03/04 are allowed",
-                ));
+                return Err(DescriptorKeyParseError::BadPubkeyPrefix);
             }
-            let key = bitcoin::PublicKey::from_str(key_part)
-                .map_err(|_| DescriptorKeyParseError("Error while parsing simple public key"))?;
+            let key_bytes =
+                Vec::<u8>::from_hex(key_part).map_err(|_| DescriptorKeyParseError::BadPubkey)?;
+            if key_bytes.len() != 33 && key_bytes.len() != 65 {
+                return Err(DescriptorKeyParseError::BadPubkey);
+            }
+            // Parsing through secp fully validates that the point is on the curve
+            let key = bitcoin::PublicKey::from_slice(&key_bytes)
+                .map_err(|_| DescriptorKeyParseError::InvalidCurvePoint)?;
             Ok(DescriptorPublicKey::SinglePub(DescriptorSinglePub {
                 key,
                 origin,
@@ -325,6 +731,8 @@ pub enum ConversionError {
     HardenedChild,
     /// Attempted to convert a key with a hardened wildcard to a bitcoin public key
     HardenedWildcard,
+    /// Attempted to convert a key with several derivation paths to a bitcoin public key
+    MultiKey,
 }
 
 impl fmt::Display for ConversionError {
@@ -335,14 +743,50 @@ impl fmt::Display for ConversionError {
             ConversionError::HardenedWildcard => {
                 "hardened and uninstantiated wildcard in bip32 path"
             }
+            ConversionError::MultiKey => "multiple derivation paths in bip32 key",
         })
     }
 }
 
 impl error::Error for ConversionError {}
 
+/// Error deriving the private keys of a descriptor secret key
+#[derive(Debug, PartialEq, Clone)]
+pub enum DerivationError {
+    /// The master key could not be derived from the seed
+    MasterDerivation(bip32::Error),
+    /// The private key could not be derived
+    PrivateDerivation(bip32::Error),
+    /// The derivation index is hardened or otherwise out of range
+    IndexOutOfRange,
+}
+
+impl fmt::Display for DerivationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            DerivationError::MasterDerivation(_) => "unable to derive the master key",
+            DerivationError::PrivateDerivation(_) => "unable to derive the private key",
+            DerivationError::IndexOutOfRange => "derivation index out of range",
+        })
+    }
+}
+
+impl error::Error for DerivationError {
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            DerivationError::MasterDerivation(ref e)
+            | DerivationError::PrivateDerivation(ref e) => Some(e),
+            DerivationError::IndexOutOfRange => None,
+        }
+    }
+}
+
 impl DescriptorPublicKey {
     /// The fingerprint of the master key associated with this key
+    ///
+    /// This is the origin fingerprint when there is one. Otherwise extended
+    /// keys are their own master, and single keys are identified by the
+    /// first four bytes of their hash160, like Bitcoin Core does.
     pub fn master_fingerprint(&self) -> bip32::Fingerprint {
         match *self {
             DescriptorPublicKey::XPub(ref xpub) => {
@@ -352,6 +796,13 @@ impl DescriptorPublicKey {
                     xpub.xkey.fingerprint()
                 }
             }
+            DescriptorPublicKey::MultiXPub(ref xpub) => {
+                if let Some((fingerprint, _)) = xpub.origin {
+                    fingerprint
+                } else {
+                    xpub.xkey.fingerprint()
+                }
+            }
             DescriptorPublicKey::SinglePub(ref single) => {
                 if let Some((fingerprint, _)) = single.origin {
                     fingerprint
@@ -361,26 +812,39 @@ impl DescriptorPublicKey {
                         .key
                         .write_into(&mut engine)
                         .expect("engines don't error");
-                    bip32::Fingerprint::from(&XpubIdentifier::from_engine(engine)[..])
+                    bip32::Fingerprint::from(&XpubIdentifier::from_engine(engine)[..4])
                 }
             }
         }
     }
 
+    /// A short identifier of the key, the hex of its [master fingerprint],
+    /// suitable as a label for cosigners. Keys without origin are
+    /// identified by their own fingerprint.
+    ///
+    /// [master fingerprint]: DescriptorPublicKey::master_fingerprint
+    pub fn short_id(&self) -> String {
+        self.master_fingerprint().to_string()
+    }
+
     /// Full path, from the master key
     ///
     /// For wildcard keys this will return the path up to the wildcard, so you
     /// can get full paths by appending one additional derivation step, according
     /// to the wildcard type (hardened or normal)
+    ///
+    /// For multipath keys this is the path of the first branch, see
+    /// [`DescriptorPublicKey::into_single_keys`] to get every branch
     pub fn full_derivation_path(&self) -> bip32::DerivationPath {
         match *self {
-            DescriptorPublicKey::XPub(ref xpub) => {
+            DescriptorPublicKey::XPub(ref xpub) => xpub.full_derivation_path(),
+            DescriptorPublicKey::MultiXPub(ref xpub) => {
                 let origin_path = if let Some((_, ref path)) = xpub.origin {
                     path.clone()
                 } else {
                     bip32::DerivationPath::from(vec![])
                 };
-                origin_path.extend(&xpub.derivation_path)
+                origin_path.extend(&xpub.derivation_paths[0])
             }
             DescriptorPublicKey::SinglePub(ref single) => {
                 if let Some((_, ref path)) = single.origin {
@@ -392,18 +856,105 @@ impl DescriptorPublicKey {
         }
     }
 
+    /// The string representation of the key, with hardened steps,
+    /// including a hardened wildcard, written with `marker`
+    pub fn to_string_with_hardened_marker(&self, marker: HardenedMarker) -> String {
+        WithHardenedMarker(self, marker).to_string()
+    }
+
+    /// Writes the key with hardened derivation steps marked with `marker`
+    /// and a hardened wildcard marked with `wildcard_marker`
+    fn fmt_with_markers(
+        &self,
+        f: &mut fmt::Formatter,
+        marker: HardenedMarker,
+        wildcard_marker: HardenedMarker,
+    ) -> fmt::Result {
+        match *self {
+            DescriptorPublicKey::SinglePub(ref pk) => {
+                maybe_fmt_master_id(f, &pk.origin, marker)?;
+                fmt::Display::fmt(&pk.key, f)
+            }
+            DescriptorPublicKey::XPub(ref xpub) => {
+                maybe_fmt_master_id(f, &xpub.origin, marker)?;
+                fmt::Display::fmt(&xpub.xkey, f)?;
+                fmt_derivation_path(f, &xpub.derivation_path, marker)?;
+                fmt_wildcard(f, xpub.wildcard, wildcard_marker)
+            }
+            DescriptorPublicKey::MultiXPub(ref xpub) => {
+                maybe_fmt_master_id(f, &xpub.origin, marker)?;
+                fmt::Display::fmt(&xpub.xkey, f)?;
+                fmt_derivation_paths(f, &xpub.derivation_paths, marker)?;
+                fmt_wildcard(f, xpub.wildcard, wildcard_marker)
+            }
+        }
+    }
+
     /// Whether or not the key has a wildcards
     pub fn is_deriveable(&self) -> bool {
+        self.has_wildcard()
+    }
+
+    /// Whether the key has a wildcard, hardened or not, which must be
+    /// replaced by an index with [`DescriptorPublicKey::derive`] before
+    /// computing the public key
+    pub fn has_wildcard(&self) -> bool {
         match *self {
             DescriptorPublicKey::SinglePub(..) => false,
             DescriptorPublicKey::XPub(ref xpub) => xpub.wildcard != Wildcard::None,
+            DescriptorPublicKey::MultiXPub(ref xpub) => xpub.wildcard != Wildcard::None,
+        }
+    }
+
+    /// Whether the key has several derivation paths, and must be split
+    /// with [`DescriptorPublicKey::into_single_keys`] before computing the
+    /// public key
+    pub fn is_multipath(&self) -> bool {
+        match *self {
+            DescriptorPublicKey::MultiXPub(..) => true,
+            DescriptorPublicKey::SinglePub(..) | DescriptorPublicKey::XPub(..) => false,
         }
     }
 
-    /// If this public key has a wildcard, replace it by the given index
+    /// Splits a multipath key into one key per derivation path, in the
+    /// order they were written. Other keys are returned as is.
+    pub fn into_single_keys(&self) -> Vec<DescriptorPublicKey> {
+        match *self {
+            DescriptorPublicKey::MultiXPub(ref xpub) => xpub
+                .derivation_paths
+                .iter()
+                .map(|path| {
+                    DescriptorPublicKey::XPub(DescriptorXKey {
+                        origin: xpub.origin.clone(),
+                        xkey: xpub.xkey,
+                        derivation_path: path.clone(),
+                        wildcard: xpub.wildcard,
+                    })
+                })
+                .collect(),
+            _ => vec![self.clone()],
+        }
+    }
+
+    /// If this public key has a wildcard, replace it by the given index. The
+    /// index is applied to every derivation path of multipath keys.
     ///
     /// Panics if given an index ≥ 2^31
     pub fn derive(mut self, index: u32) -> DescriptorPublicKey {
+        if let DescriptorPublicKey::MultiXPub(mut xpub) = self {
+            let child = match xpub.wildcard {
+                Wildcard::None => None,
+                Wildcard::Unhardened => Some(bip32::ChildNumber::from_normal_idx(index).unwrap()),
+                Wildcard::Hardened => Some(bip32::ChildNumber::from_hardened_idx(index).unwrap()),
+            };
+            if let Some(child) = child {
+                for path in xpub.derivation_paths.iter_mut() {
+                    *path = path.child(child);
+                }
+            }
+            xpub.wildcard = Wildcard::None;
+            return DescriptorPublicKey::MultiXPub(xpub);
+        }
         if let DescriptorPublicKey::XPub(mut xpub) = self {
             match xpub.wildcard {
                 Wildcard::None => {}
@@ -424,10 +975,31 @@ impl DescriptorPublicKey {
         self
     }
 
+    /// Replaces the wildcard of this key by the given index, returning a
+    /// key which is guaranteed to have no wildcard. Keys without wildcard
+    /// are returned unchanged.
+    ///
+    /// Errors with [ConversionError::HardenedChild] if the index is ≥ 2^31,
+    /// and with [ConversionError::MultiKey] for multipath keys, which must
+    /// be split with [`DescriptorPublicKey::into_single_keys`] first.
+    pub fn at_derivation_index(
+        &self,
+        index: u32,
+    ) -> Result<DefiniteDescriptorKey, ConversionError> {
+        if let DescriptorPublicKey::MultiXPub(..) = *self {
+            return Err(ConversionError::MultiKey);
+        }
+        if bip32::ChildNumber::from_normal_idx(index).is_err() {
+            return Err(ConversionError::HardenedChild);
+        }
+        Ok(DefiniteDescriptorKey(self.clone().derive(index)))
+    }
+
     /// Computes the public key corresponding to this descriptor key
     ///
     /// Will return an error if the descriptor key has any hardened
-    /// derivation steps in its path, or if the key has any wildcards.
+    /// derivation steps in its path, if the key has any wildcards or if
+    /// it has several derivation paths.
     ///
     /// To ensure there are no wildcards, call `.derive(0)` or similar;
     /// to avoid hardened derivation steps, start from a `DescriptorSecretKey`
@@ -450,8 +1022,85 @@ impl DescriptorPublicKey {
                     Err(e) => unreachable!("cryptographically unreachable: {}", e),
                 },
             },
+            DescriptorPublicKey::MultiXPub(..) => Err(ConversionError::MultiKey),
         }
     }
+
+    /// Computes the public key at every index of `range`, replacing the
+    /// wildcard by the index like [DescriptorPublicKey::derive] followed by
+    /// [DescriptorPublicKey::derive_public_key].
+    ///
+    /// The parent of the wildcard is derived once, each index then costing
+    /// a single derivation step, which makes scanning many addresses much
+    /// faster. There is one key per index, so that the result lines up
+    /// with `range`: keys without a wildcard derive to the same key at
+    /// every index, which is repeated. Errors on hardened derivation steps,
+    /// including indices ≥ 2^31, and for keys with several derivation paths.
+    pub fn derive_range<C: secp256k1_zkp::Verification>(
+        &self,
+        range: Range<u32>,
+        secp: &Secp256k1<C>,
+    ) -> Result<Vec<bitcoin::PublicKey>, ConversionError> {
+        let xpk = match *self {
+            DescriptorPublicKey::XPub(ref xpk) if xpk.wildcard == Wildcard::Unhardened => xpk,
+            _ => {
+                let key = self.derive_public_key(secp)?;
+                return Ok(range.map(|_| key).collect());
+            }
+        };
+        let parent = match xpk.xkey.derive_pub(secp, &xpk.derivation_path) {
+            Ok(parent) => parent,
+            Err(bip32::Error::CannotDeriveFromHardenedKey) => {
+                return Err(ConversionError::HardenedChild)
+            }
+            Err(e) => unreachable!("cryptographically unreachable: {}", e),
+        };
+        range
+            .map(|index| {
+                let child = bip32::ChildNumber::from_normal_idx(index)
+                    .map_err(|_| ConversionError::HardenedChild)?;
+                match parent.ckd_pub(secp, child) {
+                    Ok(xpub) => Ok(xpub.public_key),
+                    Err(e) => unreachable!("cryptographically unreachable: {}", e),
+                }
+            })
+            .collect()
+    }
+}
+
+impl DefiniteDescriptorKey {
+    /// Computes the public key corresponding to this descriptor key
+    ///
+    /// Will return an error if the key has any hardened derivation steps
+    /// in its path.
+    pub fn derive_public_key<C: secp256k1_zkp::Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+    ) -> Result<bitcoin::PublicKey, ConversionError> {
+        self.0.derive_public_key(secp)
+    }
+
+    /// The descriptor key
+    pub fn as_descriptor_public_key(&self) -> &DescriptorPublicKey {
+        &self.0
+    }
+
+    /// Converts into the descriptor key
+    pub fn into_descriptor_public_key(self) -> DescriptorPublicKey {
+        self.0
+    }
+}
+
+impl fmt::Display for DefiniteDescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<DefiniteDescriptorKey> for DescriptorPublicKey {
+    fn from(key: DefiniteDescriptorKey) -> Self {
+        key.0
+    }
 }
 
 impl FromStr for DescriptorSecretKey {
@@ -462,10 +1111,10 @@ impl FromStr for DescriptorSecretKey {
 
         if key_part.len() <= 52 {
             let sk = bitcoin::PrivateKey::from_str(key_part)
-                .map_err(|_| DescriptorKeyParseError("Error while parsing a WIF private key"))?;
+                .map_err(|_| DescriptorKeyParseError::BadWif)?;
             Ok(DescriptorSecretKey::SinglePriv(DescriptorSinglePriv {
                 key: sk,
-                origin: None,
+                origin,
             }))
         } else {
             let (xprv, derivation_path, wildcard) =
@@ -480,57 +1129,240 @@ impl FromStr for DescriptorSecretKey {
     }
 }
 
-impl<K: InnerXKey> DescriptorXKey<K> {
-    fn parse_xkey_origin(
-        s: &str,
-    ) -> Result<(&str, Option<(bip32::Fingerprint, bip32::DerivationPath)>), DescriptorKeyParseError>
+serde_string_impl!(DescriptorPublicKey, "a descriptor public key");
+serde_string_impl!(DescriptorSecretKey, "a descriptor secret key");
+
+// Extended keys are (de)serialized as the descriptor key they make up
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for DescriptorXKey<bip32::ExtendedPubKey> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
     {
-        for ch in s.as_bytes() {
-            if *ch < 20 || *ch > 127 {
-                return Err(DescriptorKeyParseError(
-                    "Encountered an unprintable character",
-                ));
-            }
-        }
+        DescriptorPublicKey::XPub(self.clone()).serialize(serializer)
+    }
+}
 
-        if s.is_empty() {
-            return Err(DescriptorKeyParseError("Empty key"));
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for DescriptorXKey<bip32::ExtendedPubKey> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        match DescriptorPublicKey::deserialize(deserializer)? {
+            DescriptorPublicKey::XPub(xpub) => Ok(xpub),
+            _ => Err(::serde::de::Error::custom(
+                "expected an extended public key",
+            )),
         }
-        let mut parts = s[1..].split(']');
+    }
+}
 
-        if let Some('[') = s.chars().next() {
-            let mut raw_origin = parts
-                .next()
-                .ok_or(DescriptorKeyParseError("Unclosed '['"))?
-                .split('/');
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for DescriptorXKey<bip32::ExtendedPrivKey> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
+    {
+        DescriptorSecretKey::XPrv(self.clone()).serialize(serializer)
+    }
+}
 
-            let origin_id_hex = raw_origin.next().ok_or(DescriptorKeyParseError(
-                "No master fingerprint found after '['",
-            ))?;
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for DescriptorXKey<bip32::ExtendedPrivKey> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        match DescriptorSecretKey::deserialize(deserializer)? {
+            DescriptorSecretKey::XPrv(xprv) => Ok(xprv),
+            _ => Err(::serde::de::Error::custom(
+                "expected an extended private key",
+            )),
+        }
+    }
+}
+
+/// SLIP-132 extended key version bytes, along with the standard BIP32
+/// version bytes they are normalized to and the descriptor type they imply
+const SLIP132_VERSIONS: [([u8; 4], [u8; 4], DescriptorType); 20] = [
+    // Mainnet public keys: xpub, ypub, zpub, Ypub, Zpub
+    (
+        [0x04, 0x88, 0xb2, 0x1e],
+        [0x04, 0x88, 0xb2, 0x1e],
+        DescriptorType::Pkh,
+    ),
+    (
+        [0x04, 0x9d, 0x7c, 0xb2],
+        [0x04, 0x88, 0xb2, 0x1e],
+        DescriptorType::ShWpkh,
+    ),
+    (
+        [0x04, 0xb2, 0x47, 0x46],
+        [0x04, 0x88, 0xb2, 0x1e],
+        DescriptorType::Wpkh,
+    ),
+    (
+        [0x02, 0x95, 0xb4, 0x3f],
+        [0x04, 0x88, 0xb2, 0x1e],
+        DescriptorType::ShWsh,
+    ),
+    (
+        [0x02, 0xaa, 0x7e, 0xd3],
+        [0x04, 0x88, 0xb2, 0x1e],
+        DescriptorType::Wsh,
+    ),
+    // Mainnet private keys: xprv, yprv, zprv, Yprv, Zprv
+    (
+        [0x04, 0x88, 0xad, 0xe4],
+        [0x04, 0x88, 0xad, 0xe4],
+        DescriptorType::Pkh,
+    ),
+    (
+        [0x04, 0x9d, 0x78, 0x78],
+        [0x04, 0x88, 0xad, 0xe4],
+        DescriptorType::ShWpkh,
+    ),
+    (
+        [0x04, 0xb2, 0x43, 0x0c],
+        [0x04, 0x88, 0xad, 0xe4],
+        DescriptorType::Wpkh,
+    ),
+    (
+        [0x02, 0x95, 0xb0, 0x05],
+        [0x04, 0x88, 0xad, 0xe4],
+        DescriptorType::ShWsh,
+    ),
+    (
+        [0x02, 0xaa, 0x7a, 0x99],
+        [0x04, 0x88, 0xad, 0xe4],
+        DescriptorType::Wsh,
+    ),
+    // Testnet public keys: tpub, upub, vpub, Upub, Vpub
+    (
+        [0x04, 0x35, 0x87, 0xcf],
+        [0x04, 0x35, 0x87, 0xcf],
+        DescriptorType::Pkh,
+    ),
+    (
+        [0x04, 0x4a, 0x52, 0x62],
+        [0x04, 0x35, 0x87, 0xcf],
+        DescriptorType::ShWpkh,
+    ),
+    (
+        [0x04, 0x5f, 0x1c, 0xf6],
+        [0x04, 0x35, 0x87, 0xcf],
+        DescriptorType::Wpkh,
+    ),
+    (
+        [0x02, 0x42, 0x89, 0xef],
+        [0x04, 0x35, 0x87, 0xcf],
+        DescriptorType::ShWsh,
+    ),
+    (
+        [0x02, 0x57, 0x54, 0x83],
+        [0x04, 0x35, 0x87, 0xcf],
+        DescriptorType::Wsh,
+    ),
+    // Testnet private keys: tprv, uprv, vprv, Uprv, Vprv
+    (
+        [0x04, 0x35, 0x83, 0x94],
+        [0x04, 0x35, 0x83, 0x94],
+        DescriptorType::Pkh,
+    ),
+    (
+        [0x04, 0x4a, 0x4e, 0x28],
+        [0x04, 0x35, 0x83, 0x94],
+        DescriptorType::ShWpkh,
+    ),
+    (
+        [0x04, 0x5f, 0x18, 0xbc],
+        [0x04, 0x35, 0x83, 0x94],
+        DescriptorType::Wpkh,
+    ),
+    (
+        [0x02, 0x42, 0x85, 0xb5],
+        [0x04, 0x35, 0x83, 0x94],
+        DescriptorType::ShWsh,
+    ),
+    (
+        [0x02, 0x57, 0x50, 0x48],
+        [0x04, 0x35, 0x83, 0x94],
+        DescriptorType::Wsh,
+    ),
+];
+
+/// Normalize a SLIP-132 encoded extended key to the standard BIP32 encoding,
+/// returning the descriptor type implied by its version bytes.
+/// Returns `None` if `s` is not an extended key with a known version.
+fn normalize_slip132(s: &str) -> Option<(String, DescriptorType)> {
+    let mut data = base58::from_check(s).ok()?;
+    if data.len() != 78 {
+        return None;
+    }
+    let &(_, standard, desc_type) = SLIP132_VERSIONS
+        .iter()
+        .find(|&&(version, _, _)| data[0..4] == version)?;
+    data[0..4].copy_from_slice(&standard);
+    Some((base58::check_encode_slice(&data), desc_type))
+}
+
+/// Normalize the SLIP-132 extended key of the descriptor key `s`, which
+/// may have an origin and a derivation path, returning the descriptor key
+/// with the standard encoding and the descriptor type implied by the
+/// version bytes.
+pub(super) fn normalize_slip132_key(s: &str) -> Option<(String, DescriptorType)> {
+    let (key_part, _) = DescriptorXKey::<bip32::ExtendedPubKey>::parse_xkey_origin(s).ok()?;
+    let xkey_str = key_part.split('/').next()?;
+    let (xkey, desc_type) = normalize_slip132(xkey_str)?;
+    let origin = &s[..s.len() - key_part.len()];
+    let deriv = &key_part[xkey_str.len()..];
+    Some((format!("{}{}{}", origin, xkey, deriv), desc_type))
+}
+
+impl<K: InnerXKey> DescriptorXKey<K> {
+    fn parse_xkey_origin(
+        s: &str,
+    ) -> Result<(&str, Option<(bip32::Fingerprint, bip32::DerivationPath)>), DescriptorKeyParseError>
+    {
+        for ch in s.as_bytes() {
+            if *ch < 20 || *ch > 127 {
+                return Err(DescriptorKeyParseError::UnprintableCharacter);
+            }
+        }
+
+        if s.is_empty() {
+            return Err(DescriptorKeyParseError::EmptyKey);
+        }
+        let mut parts = s[1..].split(']');
+
+        if let Some('[') = s.chars().next() {
+            let mut raw_origin = parts
+                .next()
+                .ok_or(DescriptorKeyParseError::UnclosedOrigin)?
+                .split('/');
+
+            let origin_id_hex = raw_origin
+                .next()
+                .ok_or(DescriptorKeyParseError::MissingFingerprint)?;
 
             if origin_id_hex.len() != 8 {
-                return Err(DescriptorKeyParseError(
-                    "Master fingerprint should be 8 characters long",
-                ));
+                return Err(DescriptorKeyParseError::FingerprintLength);
+            }
+            let parent_fingerprint = bip32::Fingerprint::from_hex(origin_id_hex)
+                .map_err(|_| DescriptorKeyParseError::BadFingerprint)?;
+            if raw_origin.clone().any(|p| p.starts_with('*')) {
+                return Err(DescriptorKeyParseError::WildcardInOrigin);
             }
-            let parent_fingerprint = bip32::Fingerprint::from_hex(origin_id_hex).map_err(|_| {
-                DescriptorKeyParseError("Malformed master fingerprint, expected 8 hex chars")
-            })?;
             let origin_path = raw_origin
-                .map(|p| bip32::ChildNumber::from_str(p))
+                .map(parse_child_number)
                 .collect::<Result<bip32::DerivationPath, bip32::Error>>()
-                .map_err(|_| {
-                    DescriptorKeyParseError("Error while parsing master derivation path")
-                })?;
+                .map_err(DescriptorKeyParseError::BadOriginPath)?;
 
-            let key = parts
-                .next()
-                .ok_or(DescriptorKeyParseError("No key after origin."))?;
+            let key = parts.next().ok_or(DescriptorKeyParseError::MissingKey)?;
 
             if parts.next().is_some() {
-                Err(DescriptorKeyParseError(
-                    "Multiple ']' in Descriptor Public Key",
-                ))
+                Err(DescriptorKeyParseError::MultipleClose)
             } else {
                 Ok((key, Some((parent_fingerprint, origin_path))))
             }
@@ -544,11 +1376,10 @@ impl<K: InnerXKey> DescriptorXKey<K> {
         key_deriv: &str,
     ) -> Result<(K, bip32::DerivationPath, Wildcard), DescriptorKeyParseError> {
         let mut key_deriv = key_deriv.split('/');
-        let xkey_str = key_deriv.next().ok_or(DescriptorKeyParseError(
-            "No key found after origin description",
-        ))?;
-        let xkey = K::from_str(xkey_str)
-            .map_err(|_| DescriptorKeyParseError("Error while parsing xkey."))?;
+        let xkey_str = key_deriv
+            .next()
+            .ok_or(DescriptorKeyParseError::MissingXKey)?;
+        let xkey = K::from_str(xkey_str)?;
 
         let mut wildcard = Wildcard::None;
         let derivation_path = key_deriv
@@ -556,17 +1387,17 @@ impl<K: InnerXKey> DescriptorXKey<K> {
                 if wildcard == Wildcard::None && p == "*" {
                     wildcard = Wildcard::Unhardened;
                     None
-                } else if wildcard == Wildcard::None && (p == "*'" || p == "*h") {
+                } else if wildcard == Wildcard::None && (p == "*'" || p == "*h" || p == "*H") {
                     wildcard = Wildcard::Hardened;
                     None
+                } else if wildcard != Wildcard::None && p.starts_with('*') {
+                    Some(Err(DescriptorKeyParseError::MultipleWildcards))
                 } else if wildcard != Wildcard::None {
-                    Some(Err(DescriptorKeyParseError(
-                        "'*' may only appear as last element in a derivation path.",
-                    )))
+                    Some(Err(DescriptorKeyParseError::WildcardNotLast))
+                } else if p.starts_with('*') {
+                    Some(Err(DescriptorKeyParseError::MalformedWildcard))
                 } else {
-                    Some(bip32::ChildNumber::from_str(p).map_err(|_| {
-                        DescriptorKeyParseError("Error while parsing key derivation path")
-                    }))
+                    Some(parse_child_number(p).map_err(DescriptorKeyParseError::BadDerivationPath))
                 }
             })
             .collect::<Result<bip32::DerivationPath, _>>()?;
@@ -574,6 +1405,108 @@ impl<K: InnerXKey> DescriptorXKey<K> {
         Ok((xkey, derivation_path, wildcard))
     }
 
+    /// Parse an extended key concatenated to a derivation path containing
+    /// a single multipath step `<a;b;...>`, returning one path per branch.
+    fn parse_xkey_multi_deriv(
+        key_deriv: &str,
+    ) -> Result<(K, Vec<bip32::DerivationPath>, Wildcard), DescriptorKeyParseError> {
+        let steps: Vec<&str> = key_deriv.split('/').collect();
+        let mut multi_steps = steps
+            .iter()
+            .enumerate()
+            .filter(|&(_, step)| step.contains('<') || step.contains('>'));
+        let (position, multi_step) = multi_steps
+            .next()
+            .ok_or(DescriptorKeyParseError::MissingMultipathStep)?;
+        if multi_steps.next().is_some() {
+            return Err(DescriptorKeyParseError::MultipleMultipathSteps);
+        }
+        if position == 0 || !multi_step.starts_with('<') || !multi_step.ends_with('>') {
+            return Err(DescriptorKeyParseError::MalformedMultipathStep);
+        }
+        let inner = &multi_step[1..multi_step.len() - 1];
+        if inner.contains('<') || inner.contains('>') {
+            return Err(DescriptorKeyParseError::NestedMultipathSteps);
+        }
+
+        let branches: Vec<&str> = inner.split(';').collect();
+        if branches.len() < 2 {
+            return Err(DescriptorKeyParseError::SingleMultipathBranch);
+        }
+        for branch in &branches {
+            parse_child_number(branch).map_err(DescriptorKeyParseError::BadDerivationPath)?;
+        }
+
+        // Each branch is parsed as a regular key, which validates the
+        // rest of the path and the wildcard. The branches themselves are
+        // checked by [DescriptorMultiXKey::new]
+        let mut xkey = None;
+        let mut wildcard = Wildcard::None;
+        let mut derivation_paths = Vec::with_capacity(branches.len());
+        for branch in branches {
+            let mut branch_steps = steps.clone();
+            branch_steps[position] = branch;
+            let (branch_xkey, path, branch_wildcard) =
+                Self::parse_xkey_deriv(&branch_steps.join("/"))?;
+            xkey = Some(branch_xkey);
+            wildcard = branch_wildcard;
+            derivation_paths.push(path);
+        }
+        Ok((
+            xkey.expect("at least two branches"),
+            derivation_paths,
+            wildcard,
+        ))
+    }
+
+    /// Full path from the master key: the origin path followed by the
+    /// key's derivation path, without the wildcard step
+    fn full_derivation_path(&self) -> bip32::DerivationPath {
+        match self.origin {
+            Some((_, ref path)) => path.extend(&self.derivation_path),
+            None => self.derivation_path.clone(),
+        }
+    }
+
+    /// Creates a key from its parts, without parsing. The `derivation_path`
+    /// is the path after the extended key, the wildcard not included.
+    pub fn new(
+        xkey: K,
+        origin: Option<(bip32::Fingerprint, bip32::DerivationPath)>,
+        derivation_path: bip32::DerivationPath,
+        wildcard: Wildcard,
+    ) -> Self {
+        DescriptorXKey {
+            origin,
+            xkey,
+            derivation_path,
+            wildcard,
+        }
+    }
+
+    /// Replaces the origin of the key by the master `fingerprint` and the
+    /// `path` from the master key to the extended key
+    pub fn with_origin(
+        mut self,
+        fingerprint: bip32::Fingerprint,
+        path: bip32::DerivationPath,
+    ) -> Self {
+        self.origin = Some((fingerprint, path));
+        self
+    }
+
+    /// The origin of the key: the master fingerprint and the path from the
+    /// master key to the extended key
+    pub fn origin(&self) -> Option<&(bip32::Fingerprint, bip32::DerivationPath)> {
+        self.origin.as_ref()
+    }
+
+    /// The path from the master key to the extended key, if the origin is
+    /// known
+    pub fn origin_derivation_path(&self) -> Option<&bip32::DerivationPath> {
+        self.origin.as_ref().map(|(_, path)| path)
+    }
+
     /// Compares this key with a `keysource` and returns the matching derivation path, if any.
     ///
     /// For keys that have an origin, the `keysource`'s fingerprint will be compared
@@ -611,40 +1544,116 @@ impl<K: InnerXKey> DescriptorXKey<K> {
         keysource: &bip32::KeySource,
         secp: &Secp256k1<C>,
     ) -> Option<bip32::DerivationPath> {
-        let (fingerprint, path) = keysource;
+        let compare_fingerprint = match self.origin {
+            Some((fingerprint, _)) => fingerprint,
+            None => self.xkey.xkey_fingerprint(secp),
+        };
+        match_keysource(
+            compare_fingerprint,
+            &self.full_derivation_path(),
+            self.wildcard,
+            keysource,
+        )
+    }
+}
 
-        let (compare_fingerprint, compare_path) = match &self.origin {
-            &Some((fingerprint, ref path)) => (
-                fingerprint,
-                path.into_iter()
-                    .chain(self.derivation_path.into_iter())
-                    .collect(),
-            ),
-            &None => (
+impl<K: InnerXKey> DescriptorMultiXKey<K> {
+    /// Creates a multipath key from its parts, checking that the
+    /// `derivation_paths` could be written with a single multipath step:
+    /// there are at least two of them, of the same length, differing at
+    /// exactly one step where all the branches are distinct.
+    pub fn new(
+        xkey: K,
+        origin: Option<(bip32::Fingerprint, bip32::DerivationPath)>,
+        derivation_paths: Vec<bip32::DerivationPath>,
+        wildcard: Wildcard,
+    ) -> Result<Self, DescriptorKeyParseError> {
+        if derivation_paths.len() < 2 {
+            return Err(DescriptorKeyParseError::SingleMultipathBranch);
+        }
+        let len = derivation_paths[0].as_ref().len();
+        if derivation_paths.iter().any(|p| p.as_ref().len() != len) {
+            return Err(DescriptorKeyParseError::MultipathLengthMismatch);
+        }
+        let mut multi_steps = (0..len).filter(|&i| {
+            let child = derivation_paths[0][i];
+            derivation_paths.iter().any(|p| p[i] != child)
+        });
+        // Identical paths are all duplicates of the first branch
+        let position = multi_steps
+            .next()
+            .ok_or(DescriptorKeyParseError::DuplicateMultipathBranch)?;
+        if multi_steps.next().is_some() {
+            return Err(DescriptorKeyParseError::MultipleMultipathSteps);
+        }
+        for (i, path) in derivation_paths.iter().enumerate() {
+            if derivation_paths[..i]
+                .iter()
+                .any(|other| other[position] == path[position])
+            {
+                return Err(DescriptorKeyParseError::DuplicateMultipathBranch);
+            }
+        }
+        Ok(DescriptorMultiXKey {
+            origin,
+            xkey,
+            derivation_paths,
+            wildcard,
+        })
+    }
+
+    /// Compares this key with a `keysource` like [DescriptorXKey::matches],
+    /// trying each derivation path in turn. Returns the matching derivation
+    /// path of the first branch lining up with the `keysource`, if any.
+    pub fn matches<C: Signing>(
+        &self,
+        keysource: &bip32::KeySource,
+        secp: &Secp256k1<C>,
+    ) -> Option<bip32::DerivationPath> {
+        let (compare_fingerprint, origin_path) = match self.origin {
+            Some((fingerprint, ref path)) => (fingerprint, path.clone()),
+            None => (
                 self.xkey.xkey_fingerprint(secp),
-                self.derivation_path.into_iter().collect::<Vec<_>>(),
+                bip32::DerivationPath::from(vec![]),
             ),
         };
+        self.derivation_paths
+            .iter()
+            .filter_map(|path| {
+                match_keysource(
+                    compare_fingerprint,
+                    &origin_path.extend(path),
+                    self.wildcard,
+                    keysource,
+                )
+            })
+            .next()
+    }
+}
 
-        let path_excluding_wildcard = if self.wildcard != Wildcard::None && path.as_ref().len() > 0
-        {
-            path.into_iter()
-                .take(path.as_ref().len() - 1)
-                .cloned()
-                .collect()
-        } else {
-            path.clone()
-        };
-
-        if &compare_fingerprint == fingerprint
-            && compare_path
-                .into_iter()
-                .eq(path_excluding_wildcard.into_iter())
-        {
-            Some(path_excluding_wildcard)
-        } else {
-            None
-        }
+/// Compares the fingerprint and full derivation path of a key with a
+/// `keysource`, ignoring the last step of the `keysource` for wildcard keys
+fn match_keysource(
+    compare_fingerprint: bip32::Fingerprint,
+    compare_path: &bip32::DerivationPath,
+    wildcard: Wildcard,
+    keysource: &bip32::KeySource,
+) -> Option<bip32::DerivationPath> {
+    let (fingerprint, path) = keysource;
+
+    let path_excluding_wildcard = if wildcard != Wildcard::None && !path.as_ref().is_empty() {
+        path.into_iter()
+            .take(path.as_ref().len() - 1)
+            .cloned()
+            .collect()
+    } else {
+        path.clone()
+    };
+
+    if &compare_fingerprint == fingerprint && compare_path == &path_excluding_wildcard {
+        Some(path_excluding_wildcard)
+    } else {
+        None
     }
 }
 
@@ -666,10 +1675,32 @@ impl MiniscriptKey for DescriptorPublicKey {
     }
 }
 
+impl MiniscriptKey for DescriptorSecretKey {
+    type Hash = Self;
+
+    fn is_uncompressed(&self) -> bool {
+        match *self {
+            DescriptorSecretKey::SinglePriv(ref sk) => !sk.key.compressed,
+            DescriptorSecretKey::XPrv(..) => false,
+        }
+    }
+
+    fn to_pubkeyhash(&self) -> Self {
+        self.clone()
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{DescriptorKeyParseError, DescriptorPublicKey, DescriptorSecretKey};
-
+    use super::{
+        ConversionError, DerivationError, DescriptorKeyParseError, DescriptorMultiXKey,
+        DescriptorPublicKey, DescriptorSecretKey, DescriptorType, DescriptorXKey, HardenedMarker,
+        Wildcard,
+    };
+
+    use bitcoin::{self, util::base58, util::bip32};
+    use elements::hashes::hex::FromHex;
+    use elements::hashes::{hash160, Hash};
     use elements::secp256k1_zkp;
 
     use std::str::FromStr;
@@ -680,61 +1711,92 @@ mod test {
         let desc = "[78412e3a/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*/44";
         assert_eq!(
             DescriptorPublicKey::from_str(desc),
-            Err(DescriptorKeyParseError(
-                "\'*\' may only appear as last element in a derivation path."
-            ))
+            Err(DescriptorKeyParseError::WildcardNotLast)
+        );
+        let desc = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*/0";
+        assert_eq!(
+            DescriptorPublicKey::from_str(desc),
+            Err(DescriptorKeyParseError::WildcardNotLast)
+        );
+        let desc = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/*/*";
+        assert_eq!(
+            DescriptorPublicKey::from_str(desc),
+            Err(DescriptorKeyParseError::MultipleWildcards)
+        );
+        let desc = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*x";
+        assert_eq!(
+            DescriptorPublicKey::from_str(desc),
+            Err(DescriptorKeyParseError::MalformedWildcard)
+        );
+        let desc = "[78412e3a/44'/*]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0";
+        assert_eq!(
+            DescriptorPublicKey::from_str(desc),
+            Err(DescriptorKeyParseError::WildcardInOrigin)
         );
 
         // And ones with invalid fingerprints
         let desc = "[NonHexor]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*";
         assert_eq!(
             DescriptorPublicKey::from_str(desc),
-            Err(DescriptorKeyParseError(
-                "Malformed master fingerprint, expected 8 hex chars"
-            ))
+            Err(DescriptorKeyParseError::BadFingerprint)
         );
 
         // And ones with invalid xpubs..
         let desc = "[78412e3a]xpub1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaLcgJvLJuZZvRcEL/1/*";
         assert_eq!(
             DescriptorPublicKey::from_str(desc),
-            Err(DescriptorKeyParseError("Error while parsing xkey."))
+            Err(DescriptorKeyParseError::InvalidXKey(bip32::Error::Base58(
+                base58::Error::BadChecksum(2728094875, 18910954)
+            )))
         );
 
         // ..or invalid raw keys
         let desc = "[78412e3a]0208a117f3897c3a13c9384b8695eed98dc31bc2500feb19a1af424cd47a5d83/1/*";
         assert_eq!(
             DescriptorPublicKey::from_str(desc),
-            Err(DescriptorKeyParseError(
-                "Error while parsing simple public key"
-            ))
+            Err(DescriptorKeyParseError::BadPubkey)
+        );
+
+        // ..or raw keys which are not on the curve
+        let desc = "[78412e3a]020000000000000000000000000000000000000000000000000000000000000000";
+        assert_eq!(
+            DescriptorPublicKey::from_str(desc),
+            Err(DescriptorKeyParseError::InvalidCurvePoint)
+        );
+        let desc = "04ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff";
+        assert_eq!(
+            DescriptorPublicKey::from_str(desc),
+            Err(DescriptorKeyParseError::InvalidCurvePoint)
         );
 
         // ..or invalid separators
         let desc = "[78412e3a]]03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8";
         assert_eq!(
             DescriptorPublicKey::from_str(desc),
-            Err(DescriptorKeyParseError(
-                "Multiple \']\' in Descriptor Public Key"
-            ))
+            Err(DescriptorKeyParseError::MultipleClose)
         );
 
         // fuzzer errors
         let desc = "[11111f11]033333333333333333333333333333323333333333333333333333333433333333]]333]]3]]101333333333333433333]]]10]333333mmmm";
         assert_eq!(
             DescriptorPublicKey::from_str(desc),
-            Err(DescriptorKeyParseError(
-                "Multiple \']\' in Descriptor Public Key"
-            ))
+            Err(DescriptorKeyParseError::MultipleClose)
         );
 
         // fuzz failure, hybrid keys
         let desc = "0777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777777";
         assert_eq!(
             DescriptorPublicKey::from_str(desc),
-            Err(DescriptorKeyParseError(
-                "Only publickeys with prefixes 02/03/04 are allowed"
-            ))
+            Err(DescriptorKeyParseError::BadPubkeyPrefix)
+        );
+
+        // The messages are unchanged and the underlying bip32 errors are kept
+        let desc = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/1x";
+        let err = DescriptorPublicKey::from_str(desc).unwrap_err();
+        assert_eq!(err.to_string(), "Error while parsing key derivation path");
+        assert_eq!(
+            err,
+            DescriptorKeyParseError::BadDerivationPath(bip32::Error::InvalidChildNumberFormat)
         );
     }
 
@@ -744,26 +1806,51 @@ mod test {
         let secret_key = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
         assert_eq!(
             DescriptorSecretKey::from_str(secret_key),
-            Err(DescriptorKeyParseError("Error while parsing xkey."))
+            Err(DescriptorKeyParseError::InvalidXKey(
+                bip32::Error::UnknownVersion([4, 136, 178, 30])
+            ))
         );
 
         // And ones with invalid fingerprints
         let desc = "[NonHexor]tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc/1/*";
         assert_eq!(
             DescriptorSecretKey::from_str(desc),
-            Err(DescriptorKeyParseError(
-                "Malformed master fingerprint, expected 8 hex chars"
-            ))
+            Err(DescriptorKeyParseError::BadFingerprint)
         );
 
         // ..or invalid raw keys
         let desc = "[78412e3a]L32jTfVLei6BYTPUpwpJSkrHx8iL9GZzeErVS8y4Y/1/*";
         assert_eq!(
             DescriptorSecretKey::from_str(desc),
-            Err(DescriptorKeyParseError(
-                "Error while parsing a WIF private key"
+            Err(DescriptorKeyParseError::BadWif)
+        );
+    }
+
+    #[test]
+    fn parse_slip132_keys() {
+        // BIP84 test vector account key
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let xpub = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        assert_eq!(
+            super::normalize_slip132_key(&format!("[d34db33f/84'/0'/0']{}/0/*", zpub)),
+            Some((
+                format!("[d34db33f/84'/0'/0']{}/0/*", xpub),
+                DescriptorType::Wpkh
             ))
         );
+        assert_eq!(
+            super::normalize_slip132_key(xpub),
+            Some((xpub.to_owned(), DescriptorType::Pkh))
+        );
+        assert_eq!(
+            super::normalize_slip132_key(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+            ),
+            None
+        );
+        // Descriptor keys only accept the standard encoding
+        assert!(DescriptorPublicKey::from_str(&format!("{}/0/*", zpub)).is_err());
+        assert!(DescriptorSecretKey::from_str(zpub).is_err());
     }
 
     #[test]
@@ -782,6 +1869,24 @@ mod test {
         assert_eq!(public_key.master_fingerprint().to_string(), "abcdef00");
         assert_eq!(public_key.full_derivation_path().to_string(), "m/0'/1'");
         assert_eq!(public_key.is_deriveable(), true);
+        assert!(public_key.has_wildcard());
+        assert!(!public_key.is_multipath());
+        assert!(!public_key.derive(1).has_wildcard());
+
+        let public_key = DescriptorPublicKey::from_str("[abcdef00/0'/1']tpubDBrgjcxBxnXyL575sHdkpKohWu5qHKoQ7TJXKNrYznh5fVEGBv89hA8ENW7A8MFVpFUSvgLqc4Nj1WZcpePX6rrxviVtPowvMuGF5rdT2Vi/<0;1>/*").unwrap();
+        assert!(public_key.has_wildcard());
+        assert!(public_key.is_multipath());
+        assert!(!public_key.clone().derive(1).has_wildcard());
+        assert!(public_key.derive(1).is_multipath());
+
+        // Single keys only have their origin path
+        let pk = "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8";
+        let public_key = DescriptorPublicKey::from_str(&format!("[abcdef00/0'/1]{}", pk)).unwrap();
+        assert_eq!(public_key.full_derivation_path().to_string(), "m/0'/1");
+        let public_key = DescriptorPublicKey::from_str(pk).unwrap();
+        assert_eq!(public_key.full_derivation_path().to_string(), "m");
+        assert!(!public_key.has_wildcard());
+        assert!(!public_key.is_multipath());
     }
 
     #[test]
@@ -822,4 +1927,682 @@ mod test {
             "m/90'/0'/1'/2"
         );
     }
+
+    #[test]
+    fn origin_must_be_concrete() {
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let tprv = "tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc";
+
+        // Origins cannot contain wildcards or multipath steps
+        for origin in ["[deadbeef/0/*]", "[deadbeef/0h/*h]"].iter() {
+            assert_eq!(
+                DescriptorPublicKey::from_str(&format!("{}{}/0", origin, xpub)),
+                Err(DescriptorKeyParseError::WildcardInOrigin)
+            );
+            assert_eq!(
+                DescriptorSecretKey::from_str(&format!("{}{}/0", origin, tprv)),
+                Err(DescriptorKeyParseError::WildcardInOrigin)
+            );
+        }
+        for origin in ["[deadbeef/<0;1>]", "[deadbeef/0/<0;1>]", "[deadbeef//0]"].iter() {
+            assert_eq!(
+                DescriptorPublicKey::from_str(&format!("{}{}/0", origin, xpub)),
+                Err(DescriptorKeyParseError::BadOriginPath(
+                    bip32::Error::InvalidChildNumberFormat
+                ))
+            );
+            assert_eq!(
+                DescriptorSecretKey::from_str(&format!("{}{}/0", origin, tprv)),
+                Err(DescriptorKeyParseError::BadOriginPath(
+                    bip32::Error::InvalidChildNumberFormat
+                ))
+            );
+        }
+
+        // Plain paths are accepted
+        let key = DescriptorPublicKey::from_str(&format!("[deadbeef/0/1h]{}/0", xpub)).unwrap();
+        assert_eq!(key.full_derivation_path().to_string(), "m/0/1'/0");
+        assert!(DescriptorSecretKey::from_str(&format!("[deadbeef/0/1h]{}/0", tprv)).is_ok());
+    }
+
+    #[test]
+    fn xkey_constructor() {
+        let xpub = "tpubDBrgjcxBxnXyL575sHdkpKohWu5qHKoQ7TJXKNrYznh5fVEGBv89hA8ENW7A8MFVpFUSvgLqc4Nj1WZcpePX6rrxviVtPowvMuGF5rdT2Vi";
+        let fingerprint = bip32::Fingerprint::from_str("aabbccdd").unwrap();
+        let origin_path = bip32::DerivationPath::from_str("m/90'/0'/1'").unwrap();
+
+        let xkey = DescriptorXKey::new(
+            bip32::ExtendedPubKey::from_str(xpub).unwrap(),
+            None,
+            bip32::DerivationPath::from_str("m/2").unwrap(),
+            Wildcard::Unhardened,
+        );
+        assert_eq!(xkey.origin(), None);
+        assert_eq!(xkey.origin_derivation_path(), None);
+        assert_eq!(
+            DescriptorPublicKey::XPub(xkey.clone()),
+            DescriptorPublicKey::from_str(&format!("{}/2/*", xpub)).unwrap()
+        );
+
+        let xkey = xkey.with_origin(fingerprint, origin_path.clone());
+        assert_eq!(xkey.origin(), Some(&(fingerprint, origin_path.clone())));
+        assert_eq!(xkey.origin_derivation_path(), Some(&origin_path));
+        assert_eq!(
+            DescriptorPublicKey::XPub(xkey),
+            DescriptorPublicKey::from_str(&format!("[aabbccdd/90'/0'/1']{}/2/*", xpub)).unwrap()
+        );
+    }
+
+    #[test]
+    fn master_fingerprint_matches() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let xpub = "tpubDBrgjcxBxnXyL575sHdkpKohWu5qHKoQ7TJXKNrYznh5fVEGBv89hA8ENW7A8MFVpFUSvgLqc4Nj1WZcpePX6rrxviVtPowvMuGF5rdT2Vi";
+
+        // The fingerprint is the one `matches` compares keysources against
+        for s in [
+            format!("[abcdef00/0'/1']{}/2", xpub),
+            format!("[abcdef00]{}/*", xpub),
+            format!("{}/0/*", xpub),
+            xpub.to_owned(),
+        ]
+        .iter()
+        {
+            let key = DescriptorPublicKey::from_str(s).unwrap();
+            let xkey = match key {
+                DescriptorPublicKey::XPub(ref xkey) => xkey,
+                _ => unreachable!(),
+            };
+            let mut path = key.full_derivation_path();
+            if key.is_deriveable() {
+                path = path.child(bip32::ChildNumber::from_normal_idx(5).unwrap());
+            }
+            let keysource = (key.master_fingerprint(), path);
+            assert!(xkey.matches(&keysource, &secp).is_some(), "{}", s);
+            let other = (bip32::Fingerprint::from(&[0xff; 4][..]), keysource.1);
+            assert!(xkey.matches(&other, &secp).is_none(), "{}", s);
+        }
+
+        // Single keys without origin use their hash160
+        let pk = "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8";
+        let key = DescriptorPublicKey::from_str(pk).unwrap();
+        let hash = hash160::Hash::hash(&Vec::<u8>::from_hex(pk).unwrap());
+        assert_eq!(
+            key.master_fingerprint(),
+            bip32::Fingerprint::from(&hash[..4])
+        );
+        let key = DescriptorPublicKey::from_str(&format!("[78412e3a/1]{}", pk)).unwrap();
+        assert_eq!(key.master_fingerprint().to_string(), "78412e3a");
+    }
+
+    #[test]
+    fn short_id() {
+        let xpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed";
+        let with_origin =
+            DescriptorPublicKey::from_str(&format!("[abcdef00/0'/1']{}/0/*", xpub)).unwrap();
+        assert_eq!(with_origin.short_id(), "abcdef00");
+
+        // Without origin, the key is identified by its own fingerprint
+        let without_origin = DescriptorPublicKey::from_str(&format!("{}/0/*", xpub)).unwrap();
+        let id = without_origin.short_id();
+        assert_eq!(id.len(), 8);
+        assert_eq!(id, without_origin.master_fingerprint().to_string());
+        assert_eq!(
+            DescriptorPublicKey::from_str(&format!("{}/1/*", xpub))
+                .unwrap()
+                .short_id(),
+            id
+        );
+        let single = DescriptorPublicKey::from_str(
+            "020000000000000000000000000000000000000000000000000000000000000002",
+        )
+        .unwrap();
+        assert_eq!(single.short_id().len(), 8);
+        assert_ne!(single.short_id(), id);
+    }
+
+    #[test]
+    fn multipath_keys() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed";
+        let s = format!("[abcdef00/84'/1'/0']{}/<0;1>/*", xpub);
+        let key = DescriptorPublicKey::from_str(&s).unwrap();
+        assert_eq!(key.to_string(), s);
+        assert!(key.is_deriveable());
+        assert_eq!(key.master_fingerprint().to_string(), "abcdef00");
+        assert_eq!(key.derive_public_key(&secp), Err(ConversionError::MultiKey));
+
+        let singles = key.into_single_keys();
+        assert_eq!(singles.len(), 2);
+        assert_eq!(singles[0].to_string(), s.replace("<0;1>", "0"));
+        assert_eq!(singles[1].to_string(), s.replace("<0;1>", "1"));
+        let derived = key.derive(3);
+        assert_eq!(
+            derived.to_string(),
+            format!("[abcdef00/84'/1'/0']{}/<0;1>/3", xpub)
+        );
+        assert_eq!(derived.into_single_keys()[1], singles[1].clone().derive(3));
+
+        // Any step of the path may be the multipath one, hardened or not
+        for path in ["/<0;1;2>", "/<0';1h>/0", "/5/<1;0>/*h"].iter() {
+            let s = format!("{}{}", xpub, path);
+            let key = DescriptorPublicKey::from_str(&s).unwrap();
+            assert_eq!(
+                DescriptorPublicKey::from_str(&key.to_string()).unwrap(),
+                key
+            );
+        }
+        let key = DescriptorPublicKey::from_str(&format!("{}/<0;1;2>", xpub)).unwrap();
+        assert_eq!(key.into_single_keys().len(), 3);
+        assert!(!key.is_deriveable());
+
+        // Keys without multipath step are their own single key
+        let key = DescriptorPublicKey::from_str(&format!("{}/0/*", xpub)).unwrap();
+        assert_eq!(key.into_single_keys(), vec![key]);
+
+        for (path, err) in [
+            ("/<0;<1;2>>/*", "Nested multipath steps are not allowed"),
+            (
+                "/<0;1>/<2;3>",
+                "Only a single multipath step may appear in a derivation path",
+            ),
+            ("/<1;1>/*", "Duplicate branch in multipath step"),
+            ("/<1>/*", "A multipath step needs at least two branches"),
+            ("/<0;1/*", "Malformed multipath step"),
+            ("/<0;*>", "Error while parsing key derivation path"),
+            (
+                "/<0;1>/*/0",
+                "'*' may only appear as last element in a derivation path.",
+            ),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                DescriptorPublicKey::from_str(&format!("{}{}", xpub, path))
+                    .map_err(|e| e.to_string()),
+                Err(err.to_string()),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn multipath_new() {
+        let xpub = bip32::ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed").unwrap();
+        let new = |paths: &[&str]| {
+            let paths = paths
+                .iter()
+                .map(|p| bip32::DerivationPath::from_str(p).unwrap())
+                .collect();
+            DescriptorMultiXKey::new(xpub, None, paths, Wildcard::Unhardened)
+        };
+        let key = new(&["m/0/1", "m/0/2"]).unwrap();
+        assert_eq!(
+            DescriptorPublicKey::MultiXPub(key).to_string(),
+            format!("{}/0/<1;2>/*", xpub)
+        );
+        assert_eq!(
+            new(&["m/0"]),
+            Err(DescriptorKeyParseError::SingleMultipathBranch)
+        );
+        assert_eq!(
+            new(&["m/0", "m/1/2"]),
+            Err(DescriptorKeyParseError::MultipathLengthMismatch)
+        );
+        assert_eq!(
+            new(&["m/0/1", "m/0/1"]),
+            Err(DescriptorKeyParseError::DuplicateMultipathBranch)
+        );
+        assert_eq!(
+            new(&["m/0", "m/1", "m/0"]),
+            Err(DescriptorKeyParseError::DuplicateMultipathBranch)
+        );
+        assert_eq!(
+            new(&["m/0/1", "m/1/2"]),
+            Err(DescriptorKeyParseError::MultipleMultipathSteps)
+        );
+    }
+
+    #[test]
+    fn multipath_matches() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let xpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed";
+        let keysource = |fingerprint: &str, path: &str| {
+            (
+                bip32::Fingerprint::from_str(fingerprint).unwrap(),
+                bip32::DerivationPath::from_str(path).unwrap(),
+            )
+        };
+        let path = |path: &str| Some(bip32::DerivationPath::from_str(path).unwrap());
+
+        let key =
+            match DescriptorPublicKey::from_str(&format!("[abcdef00/84'/1'/0']{}/<0;1>/*", xpub))
+                .unwrap()
+            {
+                DescriptorPublicKey::MultiXPub(xpub) => xpub,
+                _ => unreachable!(),
+            };
+        assert_eq!(
+            key.matches(&keysource("abcdef00", "m/84'/1'/0'/0/7"), &secp),
+            path("m/84'/1'/0'/0")
+        );
+        assert_eq!(
+            key.matches(&keysource("abcdef00", "m/84'/1'/0'/1/7"), &secp),
+            path("m/84'/1'/0'/1")
+        );
+        assert_eq!(
+            key.matches(&keysource("abcdef00", "m/84'/1'/0'/2/7"), &secp),
+            None
+        );
+        assert_eq!(
+            key.matches(&keysource("ffffffff", "m/84'/1'/0'/1/7"), &secp),
+            None
+        );
+
+        // Without origin nor wildcard
+        let key = match DescriptorPublicKey::from_str(&format!("{}/<0;1>/2", xpub)).unwrap() {
+            DescriptorPublicKey::MultiXPub(xpub) => xpub,
+            _ => unreachable!(),
+        };
+        let fingerprint = key.xkey.fingerprint().to_string();
+        assert_eq!(
+            key.matches(&keysource(&fingerprint, "m/1/2"), &secp),
+            path("m/1/2")
+        );
+        assert_eq!(
+            key.matches(&keysource(&fingerprint, "m/1/2/3"), &secp),
+            None
+        );
+    }
+
+    #[test]
+    fn from_seed() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        // BIP32 test vector 1, chain m/0H/1
+        let seed = Vec::<u8>::from_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        let path = bip32::DerivationPath::from_str("m/0'/1").unwrap();
+        let key =
+            DescriptorSecretKey::from_seed(&seed, path, bitcoin::Network::Bitcoin, &secp).unwrap();
+        assert_eq!(key.to_string(), "[3442193e/0'/1]xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs");
+        assert_eq!(
+            key.as_public(&secp).unwrap().to_string(),
+            "[3442193e/0'/1]xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ"
+        );
+
+        let key = DescriptorSecretKey::from_seed(
+            &seed,
+            bip32::DerivationPath::from(vec![]),
+            bitcoin::Network::Testnet,
+            &secp,
+        )
+        .unwrap();
+        assert!(key.to_string().starts_with("[3442193e]tprv"));
+    }
+
+    #[test]
+    fn single_secret_key_origin() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let s = "[deadbeef/0'/1]cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy";
+        let secret_key = DescriptorSecretKey::from_str(s).unwrap();
+        assert_eq!(secret_key.to_string(), s);
+        let public_key = secret_key.as_public(&secp).unwrap();
+        assert_eq!(public_key.master_fingerprint().to_string(), "deadbeef");
+        assert_eq!(public_key.full_derivation_path().to_string(), "m/0'/1");
+
+        let s = "cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy";
+        assert_eq!(DescriptorSecretKey::from_str(s).unwrap().to_string(), s);
+    }
+
+    #[test]
+    fn derive_hardened_wildcard_xprv() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let tprv = "tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc";
+        let s = format!("[aabbccdd/90']{}/0/*'", tprv);
+        let secret_key = DescriptorSecretKey::from_str(&s).unwrap();
+        assert_eq!(secret_key.to_string(), s.replace("*'", "*h"));
+        assert_eq!(
+            DescriptorSecretKey::from_str(&secret_key.to_string())
+                .unwrap()
+                .to_string(),
+            secret_key.to_string()
+        );
+
+        // The hardened step is applied on the private key
+        let derived = secret_key.derive(7);
+        assert_eq!(derived.to_string(), format!("[aabbccdd/90']{}/0/7'", tprv));
+        let public_key = derived.as_public(&secp).unwrap();
+        assert_eq!(public_key.full_derivation_path().to_string(), "m/90'/0/7'");
+        let expected = DescriptorSecretKey::from_str(&s)
+            .unwrap()
+            .derive_range(7..8, &secp)
+            .unwrap();
+        assert_eq!(
+            public_key.derive_public_key(&secp).unwrap(),
+            expected[0].key.public_key(&secp)
+        );
+
+        // Keys without wildcard are unchanged
+        let s = format!("{}/0/1", tprv);
+        let secret_key = DescriptorSecretKey::from_str(&s).unwrap();
+        assert_eq!(secret_key.derive(3).to_string(), s);
+    }
+
+    #[test]
+    fn at_derivation_index() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed";
+        let key =
+            DescriptorPublicKey::from_str(&format!("[abcdef00/84'/1'/0']{}/0/*", xpub)).unwrap();
+
+        let definite = key.at_derivation_index(0).unwrap();
+        assert_eq!(
+            definite.to_string(),
+            format!("[abcdef00/84'/1'/0']{}/0/0", xpub)
+        );
+        assert!(!definite.as_descriptor_public_key().is_deriveable());
+        assert_eq!(
+            definite.derive_public_key(&secp).unwrap(),
+            key.clone().derive(0).derive_public_key(&secp).unwrap()
+        );
+        let max = key.at_derivation_index((1 << 31) - 1).unwrap();
+        assert_eq!(
+            DescriptorPublicKey::from(max)
+                .full_derivation_path()
+                .to_string(),
+            "m/84'/1'/0'/0/2147483647"
+        );
+        assert_eq!(
+            key.at_derivation_index(1 << 31),
+            Err(ConversionError::HardenedChild)
+        );
+
+        // Hardened wildcards derive hardened steps, which cannot be turned
+        // into a public key
+        let key = DescriptorPublicKey::from_str(&format!("{}/0/*h", xpub)).unwrap();
+        let definite = key.at_derivation_index(1).unwrap();
+        assert_eq!(definite.to_string(), format!("{}/0/1'", xpub));
+        assert_eq!(
+            definite.derive_public_key(&secp),
+            Err(ConversionError::HardenedChild)
+        );
+
+        // Keys without wildcard are unchanged, multipath keys are rejected
+        let key = DescriptorPublicKey::from_str(&format!("{}/0/1", xpub)).unwrap();
+        assert_eq!(
+            key.at_derivation_index(5)
+                .unwrap()
+                .into_descriptor_public_key(),
+            key
+        );
+        let key = DescriptorPublicKey::from_str(&format!("{}/<0;1>/*", xpub)).unwrap();
+        assert_eq!(key.at_derivation_index(0), Err(ConversionError::MultiKey));
+    }
+
+    #[test]
+    fn hardened_markers() {
+        let xpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed";
+        let apostrophe = format!("[abcdef00/84'/1'/0']{}/0'/1/*'", xpub);
+        let key = DescriptorPublicKey::from_str(&apostrophe).unwrap();
+        for s in [
+            format!("[abcdef00/84h/1h/0h]{}/0h/1/*h", xpub),
+            format!("[abcdef00/84H/1H/0H]{}/0H/1/*H", xpub),
+            format!("[abcdef00/84h/1'/0H]{}/0'/1/*H", xpub),
+        ]
+        .iter()
+        {
+            assert_eq!(DescriptorPublicKey::from_str(s).unwrap(), key);
+        }
+        let key = DescriptorPublicKey::from_str(&format!("{}/<0H;1h>", xpub)).unwrap();
+        assert_eq!(key.to_string(), format!("{}/<0';1'>", xpub));
+
+        let key = DescriptorPublicKey::from_str(&apostrophe).unwrap();
+        assert_eq!(
+            key.to_string_with_hardened_marker(HardenedMarker::Apostrophe),
+            format!("[abcdef00/84'/1'/0']{}/0'/1/*'", xpub)
+        );
+        let h = key.to_string_with_hardened_marker(HardenedMarker::H);
+        assert_eq!(h, format!("[abcdef00/84h/1h/0h]{}/0h/1/*h", xpub));
+        assert_eq!(DescriptorPublicKey::from_str(&h).unwrap(), key);
+    }
+
+    #[test]
+    fn derive_range_xprv() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let tprv = "tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc";
+
+        // The private keys match the keys derived from the public side
+        let secret_key =
+            DescriptorSecretKey::from_str(&format!("[aabbccdd/90']{}/0'/1/*", tprv)).unwrap();
+        let public_key = secret_key.as_public(&secp).unwrap();
+        let keys = secret_key.derive_range(5..8, &secp).unwrap();
+        assert_eq!(keys.len(), 3);
+        for (key, index) in keys.iter().zip(5..8) {
+            assert_eq!(
+                key.key.public_key(&secp),
+                public_key
+                    .clone()
+                    .derive(index)
+                    .derive_public_key(&secp)
+                    .unwrap()
+            );
+            let (fingerprint, ref path) = *key.origin.as_ref().unwrap();
+            assert_eq!(fingerprint.to_string(), "aabbccdd");
+            assert_eq!(path.to_string(), format!("m/90'/0'/1/{}", index));
+        }
+
+        // Without origin, the key is its own master
+        let secret_key = DescriptorSecretKey::from_str(&format!("{}/0/*h", tprv)).unwrap();
+        let keys = secret_key.derive_range(0..2, &secp).unwrap();
+        let (fingerprint, ref path) = *keys[1].origin.as_ref().unwrap();
+        assert_eq!(fingerprint.to_string(), "2cbe2a6d");
+        assert_eq!(path.to_string(), "m/0/1'");
+
+        // Keys without wildcard are repeated for every index
+        let secret_key = DescriptorSecretKey::from_str(&format!("{}/0/1", tprv)).unwrap();
+        let keys = secret_key.derive_range(0..10, &secp).unwrap();
+        assert_eq!(keys.len(), 10);
+        for key in &keys {
+            assert_eq!(key.origin.as_ref().unwrap().1.to_string(), "m/0/1");
+            assert_eq!(key.key, keys[0].key);
+        }
+        assert!(secret_key.derive_range(5..5, &secp).unwrap().is_empty());
+        let secret_key = DescriptorSecretKey::from_str(
+            "[aabbccdd/1]cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy",
+        )
+        .unwrap();
+        let keys = secret_key.derive_range(3..5, &secp).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[1].origin.as_ref().unwrap().1.to_string(), "m/1");
+        assert!(secret_key.derive_range(5..5, &secp).unwrap().is_empty());
+
+        // Indices must be unhardened
+        let secret_key = DescriptorSecretKey::from_str(&format!("{}/0/*", tprv)).unwrap();
+        assert!(secret_key.derive_range(0..10, &secp).is_ok());
+        assert_eq!(
+            secret_key
+                .derive_range((1 << 31) - 1..(1 << 31) + 1, &secp)
+                .unwrap_err(),
+            DerivationError::IndexOutOfRange
+        );
+    }
+
+    #[test]
+    fn to_public_key_at() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let tprv = "tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc";
+
+        // Hardened steps and wildcards are applied on the private key
+        for s in &[
+            format!("[aabbccdd/90']{}/0'/1/*", tprv),
+            format!("{}/0/*h", tprv),
+        ] {
+            let secret_key = DescriptorSecretKey::from_str(s).unwrap();
+            let expected = secret_key
+                .clone()
+                .derive(12)
+                .as_public(&secp)
+                .unwrap()
+                .derive_public_key(&secp)
+                .unwrap();
+            assert_eq!(secret_key.to_public_key_at(12, &secp).unwrap(), expected);
+        }
+
+        // Keys without wildcard ignore the index
+        let wif = "cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy";
+        let secret_key = DescriptorSecretKey::from_str(wif).unwrap();
+        let expected = bitcoin::PrivateKey::from_wif(wif)
+            .unwrap()
+            .public_key(&secp);
+        assert_eq!(secret_key.to_public_key_at(3, &secp).unwrap(), expected);
+
+        let secret_key = DescriptorSecretKey::from_str(&format!("{}/0/*", tprv)).unwrap();
+        assert_eq!(
+            secret_key.to_public_key_at(1 << 31, &secp),
+            Err(DerivationError::IndexOutOfRange)
+        );
+        assert_eq!(
+            secret_key.to_public_key_at(u32::max_value(), &secp),
+            Err(DerivationError::IndexOutOfRange)
+        );
+    }
+
+    #[test]
+    fn derive_range_xpub() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed";
+
+        // Same keys as deriving each index separately
+        let key =
+            DescriptorPublicKey::from_str(&format!("[abcdef00/84'/1'/0']{}/1/*", xpub)).unwrap();
+        let naive: Vec<_> = (1000..1100)
+            .map(|index| key.clone().derive(index).derive_public_key(&secp).unwrap())
+            .collect();
+        assert_eq!(key.derive_range(1000..1100, &secp).unwrap(), naive);
+        assert_eq!(key.derive_range(5..5, &secp).unwrap(), vec![]);
+
+        // Keys without wildcard are repeated for every index
+        let key = DescriptorPublicKey::from_str(&format!("{}/1/2", xpub)).unwrap();
+        assert_eq!(
+            key.derive_range(0..10, &secp).unwrap(),
+            vec![key.derive_public_key(&secp).unwrap(); 10]
+        );
+        assert_eq!(key.derive_range(5..5, &secp).unwrap(), vec![]);
+        let pk = "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd";
+        let key = DescriptorPublicKey::from_str(pk).unwrap();
+        assert_eq!(
+            key.derive_range(3..5, &secp).unwrap(),
+            vec![bitcoin::PublicKey::from_str(pk).unwrap(); 2]
+        );
+
+        // Hardened steps cannot be derived
+        let key = DescriptorPublicKey::from_str(&format!("{}/*", xpub)).unwrap();
+        assert_eq!(
+            key.derive_range((1 << 31) - 1..(1 << 31) + 1, &secp),
+            Err(ConversionError::HardenedChild)
+        );
+        let key = DescriptorPublicKey::from_str(&format!("{}/1'/*", xpub)).unwrap();
+        assert_eq!(
+            key.derive_range(0..10, &secp),
+            Err(ConversionError::HardenedChild)
+        );
+        let key = DescriptorPublicKey::from_str(&format!("{}/1/*h", xpub)).unwrap();
+        assert_eq!(
+            key.derive_range(0..10, &secp),
+            Err(ConversionError::HardenedWildcard)
+        );
+        let key = DescriptorPublicKey::from_str(&format!("{}/<0;1>/*", xpub)).unwrap();
+        assert_eq!(
+            key.derive_range(0..10, &secp),
+            Err(ConversionError::MultiKey)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_roundtrip() {
+        extern crate serde_json;
+        use super::DescriptorXKey;
+
+        fn roundtrip<T>(s: &str) -> T
+        where
+            T: ::serde::Serialize
+                + ::serde::de::DeserializeOwned
+                + FromStr
+                + PartialEq
+                + ::std::fmt::Debug,
+            <T as FromStr>::Err: ::std::fmt::Debug,
+        {
+            let key = T::from_str(s).unwrap();
+            let ser = serde_json::to_string(&key).unwrap();
+            assert_eq!(ser, format!("\"{}\"", s));
+            let de: T = serde_json::from_str(&ser).unwrap();
+            assert_eq!(de, key);
+            de
+        }
+
+        let xpub = "[78412e3a/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*";
+        let single = "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8";
+        let xprv = "[d34db33f/44'/0'/0']tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc/0/*h";
+        let wif = "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn";
+
+        for s in &[xpub, single] {
+            roundtrip::<DescriptorPublicKey>(s);
+        }
+        for s in &[xprv, wif] {
+            roundtrip::<DescriptorSecretKey>(s);
+        }
+
+        let xkey: DescriptorXKey<bip32::ExtendedPubKey> =
+            serde_json::from_str(&format!("\"{}\"", xpub)).unwrap();
+        assert_eq!(
+            serde_json::to_string(&xkey).unwrap(),
+            format!("\"{}\"", xpub)
+        );
+        let xkey: DescriptorXKey<bip32::ExtendedPrivKey> =
+            serde_json::from_str(&format!("\"{}\"", xprv)).unwrap();
+        assert_eq!(
+            serde_json::to_string(&xkey).unwrap(),
+            format!("\"{}\"", xprv)
+        );
+        assert!(
+            serde_json::from_str::<DescriptorXKey<bip32::ExtendedPubKey>>(&format!(
+                "\"{}\"",
+                single
+            ))
+            .is_err()
+        );
+        assert!(serde_json::from_str::<DescriptorPublicKey>("\"nokey\"").is_err());
+    }
+}
+
+#[cfg(all(test, feature = "unstable"))]
+mod benches {
+    use std::str::FromStr;
+    use test::{black_box, Bencher};
+
+    use super::DescriptorPublicKey;
+    use elements::secp256k1_zkp;
+
+    const KEY: &str = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/84/1/0/0/*";
+
+    #[bench]
+    pub fn derive_range(bh: &mut Bencher) {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let key = DescriptorPublicKey::from_str(KEY).unwrap();
+        bh.iter(|| {
+            black_box(key.derive_range(0..100, &secp).unwrap());
+        });
+    }
+
+    #[bench]
+    pub fn derive_each(bh: &mut Bencher) {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let key = DescriptorPublicKey::from_str(KEY).unwrap();
+        bh.iter(|| {
+            let keys: Vec<_> = (0..100)
+                .map(|index| key.clone().derive(index).derive_public_key(&secp).unwrap())
+                .collect();
+            black_box(keys);
+        });
+    }
 }
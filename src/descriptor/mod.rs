@@ -23,49 +23,81 @@
 //! these with BIP32 paths, pay-to-contract instructions, etc.
 //!
 
-use std::{collections::HashMap, sync::Arc};
 use std::{
-    fmt,
+    cmp, error, fmt,
+    ops::Range,
     str::{self, FromStr},
 };
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
 
 #[allow(unused_imports)]
 pub mod pegin;
 
 // use bitcoin;
+use bitcoin::util::bip32;
 use elements;
+use elements::hashes::{hash160, ripemd160, sha256, sha256d, Hash};
+use elements::pset::PartiallySignedTransaction as Pset;
+use elements::script::{self, Instruction};
 use elements::secp256k1_zkp;
+use elements::sighash::SigHashCache;
 use elements::Script;
 
-use CovenantExt;
+use {CovenantExt, ElementsSig};
 
 use self::checksum::verify_checksum;
 use expression;
 use miniscript;
+use miniscript::limits::{
+    HEIGHT_TIME_THRESHOLD, MAX_SCRIPTSIG_SIZE, MAX_STANDARD_P2WSH_SCRIPT_SIZE,
+    MAX_STANDARD_P2WSH_STACK_ITEMS, MAX_STANDARD_P2WSH_STACK_ITEM_SIZE, SEQUENCE_LOCKTIME_MASK,
+    SEQUENCE_LOCKTIME_TYPE_FLAG,
+};
+use miniscript::satisfy::{
+    check_path_consumed, elementssig_to_rawsig, After, LoggingSatisfier, Lookup, Older, Preimage32,
+};
 use miniscript::{Legacy, Miniscript, Segwitv0};
+use policy::{semantic, Liftable};
+use pset;
+use util::varint_len;
 use {
-    BareCtx, Error, ForEach, ForEachKey, MiniscriptKey, Satisfier, ToPublicKey, TranslatePk,
-    TranslatePk2,
+    BareCtx, Error, Extension, ForEach, ForEachKey, MiniscriptKey, Satisfier, ScriptContext,
+    Terminal, ToPublicKey, TranslatePk, TranslatePk2,
 };
 
 mod bare;
 mod blinded;
+mod confidential;
 mod covenants;
+mod encode;
 mod segwitv0;
+mod set;
 mod sh;
+mod slip77;
 mod sortedmulti;
+mod tracked;
+mod wallet;
 // Descriptor Exports
 pub use self::bare::{Bare, Pkh};
 pub use self::blinded::Blinded;
+pub use self::confidential::{confidential_proof_size, BlindingKey, ConfidentialDescriptor};
 pub use self::segwitv0::{Wpkh, Wsh, WshInner};
+pub use self::set::{DescriptorSet, OutputClass};
 pub use self::sh::{Sh, ShInner};
+pub use self::slip77::{blinding_key_for_script, Slip77MasterKey};
 pub use self::sortedmulti::SortedMultiVec;
+pub use self::tracked::TrackedDescriptor;
+pub use self::wallet::{Keychain, Wallet};
 mod checksum;
 mod key;
 pub use self::covenants::{CovError, CovOperations, CovSatisfier, CovenantDescriptor};
 pub use self::key::{
-    DescriptorKeyParseError, DescriptorPublicKey, DescriptorSecretKey, DescriptorSinglePriv,
-    DescriptorSinglePub, DescriptorXKey, InnerXKey, Wildcard,
+    ConversionError, DefiniteDescriptorKey, DerivationError, DescriptorKeyParseError,
+    DescriptorMultiXKey, DescriptorPublicKey, DescriptorSecretKey, DescriptorSinglePriv,
+    DescriptorSinglePub, DescriptorXKey, HardenedMarker, InnerXKey, Wildcard,
 };
 
 /// Alias type for a map of public key to secret key
@@ -264,6 +296,37 @@ impl FromStr for DescriptorType {
         }
     }
 }
+
+/// Type of the scriptPubKey produced by a descriptor
+///
+/// Unlike [DescriptorType], this only describes the outermost wrapper,
+/// which is all that is visible on-chain.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum ScriptPubKeyType {
+    /// Bare script (including pay-to-pubkey)
+    Bare,
+    /// Pay-to-PubKey-Hash
+    P2pkh,
+    /// Pay-to-ScriptHash (including nested wsh/wpkh)
+    P2sh,
+    /// Pay-to-Witness-PubKey-Hash
+    P2wpkh,
+    /// Pay-to-Witness-ScriptHash (including covenants)
+    P2wsh,
+}
+
+impl fmt::Display for ScriptPubKeyType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScriptPubKeyType::Bare => write!(f, "bare"),
+            ScriptPubKeyType::P2pkh => write!(f, "p2pkh"),
+            ScriptPubKeyType::P2sh => write!(f, "p2sh"),
+            ScriptPubKeyType::P2wpkh => write!(f, "p2wpkh"),
+            ScriptPubKeyType::P2wsh => write!(f, "p2wsh"),
+        }
+    }
+}
+
 /// Method for determining Type of descriptor when parsing from String
 pub enum DescriptorInfo {
     /// Bitcoin Descriptor
@@ -333,6 +396,206 @@ impl DescriptorInfo {
     }
 }
 
+/// Reason why spending or creating an output is not standard on Liquid,
+/// as returned by [Descriptor::is_standard_on_liquid]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NonStandardReason {
+    /// No spending path can be satisfied
+    Unsatisfiable,
+    /// A satisfaction has a scriptSig larger than `MAX_SCRIPTSIG_SIZE`
+    ScriptSigSize(usize),
+    /// Witness script larger than `MAX_STANDARD_P2WSH_SCRIPT_SIZE`
+    WitnessScriptSize(usize),
+    /// A satisfaction has more than `MAX_STANDARD_P2WSH_STACK_ITEMS`
+    /// witness elements, not counting the witness script
+    WitnessItems(usize),
+    /// A satisfaction has a witness element larger than
+    /// `MAX_STANDARD_P2WSH_STACK_ITEM_SIZE`, not counting the witness script
+    WitnessItemSize(usize),
+}
+
+impl fmt::Display for NonStandardReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NonStandardReason::Unsatisfiable => f.write_str("descriptor cannot be satisfied"),
+            NonStandardReason::ScriptSigSize(n) => write!(
+                f,
+                "scriptSig of {} bytes, standardness limit is {}",
+                n, MAX_SCRIPTSIG_SIZE
+            ),
+            NonStandardReason::WitnessScriptSize(n) => write!(
+                f,
+                "witness script of {} bytes, standardness limit is {}",
+                n, MAX_STANDARD_P2WSH_SCRIPT_SIZE
+            ),
+            NonStandardReason::WitnessItems(n) => write!(
+                f,
+                "{} witness elements, standardness limit is {}",
+                n, MAX_STANDARD_P2WSH_STACK_ITEMS
+            ),
+            NonStandardReason::WitnessItemSize(n) => write!(
+                f,
+                "witness element of {} bytes, standardness limit is {}",
+                n, MAX_STANDARD_P2WSH_STACK_ITEM_SIZE
+            ),
+        }
+    }
+}
+
+impl error::Error for NonStandardReason {}
+
+/// The Miniscript of a descriptor, in its script context, as returned by
+/// [Descriptor::as_miniscript]
+#[derive(Debug, PartialEq, Eq)]
+pub enum DescriptorMiniscript<'a, Pk: MiniscriptKey> {
+    /// Miniscript of a bare descriptor
+    Bare(&'a Miniscript<Pk, BareCtx>),
+    /// Miniscript of a sh descriptor
+    Sh(&'a Miniscript<Pk, Legacy>),
+    /// Miniscript of a wsh or sh wrapped wsh descriptor
+    Wsh(&'a Miniscript<Pk, Segwitv0>),
+    /// Miniscript of a covenant descriptor
+    Cov(&'a Miniscript<Pk, Segwitv0, CovenantExt>),
+}
+
+impl<'a, Pk: MiniscriptKey> Liftable<Pk> for DescriptorMiniscript<'a, Pk> {
+    fn lift(&self) -> Result<semantic::Policy<Pk>, Error> {
+        match *self {
+            DescriptorMiniscript::Bare(ms) => ms.lift(),
+            DescriptorMiniscript::Sh(ms) => ms.lift(),
+            DescriptorMiniscript::Wsh(ms) => ms.lift(),
+            DescriptorMiniscript::Cov(ms) => ms.lift(),
+        }
+    }
+}
+
+impl<'a, Pk: MiniscriptKey> ForEachKey<Pk> for DescriptorMiniscript<'a, Pk> {
+    fn for_each_key<'b, F: FnMut(ForEach<'b, Pk>) -> bool>(&'b self, pred: F) -> bool
+    where
+        Pk: 'b,
+        Pk::Hash: 'b,
+    {
+        match *self {
+            DescriptorMiniscript::Bare(ms) => ms.for_each_key(pred),
+            DescriptorMiniscript::Sh(ms) => ms.for_each_key(pred),
+            DescriptorMiniscript::Wsh(ms) => ms.for_each_key(pred),
+            DescriptorMiniscript::Cov(ms) => ms.for_each_key(pred),
+        }
+    }
+}
+
+/// Earliest time at which a descriptor can be spent, as computed by
+/// [Descriptor::earliest_spend]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SpendEstimate {
+    /// The spending path, as returned by [Miniscript::spending_paths]
+    pub path: Vec<usize>,
+    /// The locktime of the spending transaction, 0 when the path has no
+    /// absolute timelock
+    pub lock_time: u32,
+    /// The sequence number of the spending input, 0 when the path has no
+    /// relative timelock
+    pub sequence: u32,
+    /// The chain height from which the path can be spent
+    pub height: u32,
+    /// The median time past of the chain from which the path can be spent
+    pub time: u32,
+}
+
+/// Two keys of a descriptor deriving to the same public key, as returned
+/// by [Descriptor::check_key_collisions]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KeyCollision {
+    /// The derivation index at which the keys collide
+    pub index: u32,
+    /// The colliding keys, in the order they appear in the descriptor
+    pub keys: (DescriptorPublicKey, DescriptorPublicKey),
+    /// The public key both keys derive to
+    pub public_key: bitcoin::PublicKey,
+}
+
+/// BIP standard derivation scheme followed by a descriptor, as returned by
+/// [Descriptor::derivation_standard]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum DerivationStandard {
+    /// BIP-44: `elpkh` with keys derived at `m/44'/coin_type'/account'`
+    Bip44,
+    /// BIP-49: `elsh(wpkh)` with keys derived at `m/49'/coin_type'/account'`
+    Bip49,
+    /// BIP-84: `elwpkh` with keys derived at `m/84'/coin_type'/account'`
+    Bip84,
+}
+
+impl DerivationStandard {
+    /// The purpose, that is the first hardened step of the derivation path
+    pub fn purpose(&self) -> u32 {
+        match *self {
+            DerivationStandard::Bip44 => 44,
+            DerivationStandard::Bip49 => 49,
+            DerivationStandard::Bip84 => 84,
+        }
+    }
+}
+
+impl fmt::Display for DerivationStandard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DerivationStandard::Bip44 => write!(f, "Legacy (BIP-44)"),
+            DerivationStandard::Bip49 => write!(f, "Nested SegWit (BIP-49)"),
+            DerivationStandard::Bip84 => write!(f, "Native SegWit (BIP-84)"),
+        }
+    }
+}
+
+/// Iterator over the addresses of a descriptor at consecutive indices, as
+/// returned by [Descriptor::addresses]
+pub struct Addresses<'a, C: 'a + secp256k1_zkp::Verification> {
+    /// The descriptor with every wildcard key rebased on its parent
+    descriptor: Descriptor<DescriptorPublicKey>,
+    params: &'static elements::AddressParams,
+    secp: &'a secp256k1_zkp::Secp256k1<C>,
+    /// The address at `index`, computed when the iterator was created
+    first: Option<elements::Address>,
+    index: u32,
+    /// Whether the descriptor has a wildcard, otherwise there is a single
+    /// address
+    deriveable: bool,
+}
+
+impl<'a, C: secp256k1_zkp::Verification> Iterator for Addresses<'a, C> {
+    type Item = elements::Address;
+
+    fn next(&mut self) -> Option<elements::Address> {
+        if let Some(addr) = self.first.take() {
+            return Some(addr);
+        }
+        if !self.deriveable || self.index >= (1 << 31) - 1 {
+            return None;
+        }
+        self.index += 1;
+        // Every key derived at the first index, and the derivation of the
+        // rebased keys is a single unhardened step
+        let addr = self
+            .descriptor
+            .derived_public_descriptor(self.index, self.secp)
+            .expect("derivation checked at the first index")
+            .address(self.params)
+            .expect("address checked at the first index");
+        Some(addr)
+    }
+}
+
+/// Requirements of a spending path which a satisfier could not provide,
+/// as returned by [Descriptor::try_satisfy]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MissingItems<Pk: MiniscriptKey> {
+    /// The spending path, as returned by [Miniscript::spending_paths]
+    pub path: Vec<usize>,
+    /// The signatures, public keys, hash preimages, timelocks and
+    /// covenant transaction data missing to satisfy the path
+    pub missing: Vec<Lookup<Pk>>,
+}
+
 /// Script descriptor
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Descriptor<Pk: MiniscriptKey> {
@@ -435,6 +698,34 @@ impl<Pk: MiniscriptKey> Descriptor<Pk> {
         Ok(Descriptor::Wsh(Wsh::new_sortedmulti(k, pks)?))
     }
 
+    /// Create a new wsh descriptor for the common timelocked recovery
+    /// pattern `or_d(pk(primary),and_v(v:older(blocks),pk(recovery)))`:
+    /// `primary` can spend at any time while `recovery` can only spend once
+    /// the coins are `blocks` blocks old.
+    /// Errors when `blocks` is not a valid block-based relative locktime,
+    /// that is when it is zero or does not fit in 16 bits
+    pub fn timelocked_recovery(primary: Pk, recovery: Pk, blocks: u32) -> Result<Self, Error> {
+        if blocks == 0 || blocks > 0xffff {
+            return Err(Error::BadDescriptor(format!(
+                "Invalid recovery timelock of {} blocks",
+                blocks
+            )));
+        }
+        let pk = |pk| -> Result<Arc<Miniscript<Pk, Segwitv0>>, Error> {
+            let pk_k = Miniscript::from_ast(Terminal::PkK(pk))?;
+            Ok(Arc::new(Miniscript::from_ast(Terminal::Check(Arc::new(
+                pk_k,
+            )))?))
+        };
+        let older = Miniscript::from_ast(Terminal::Older(blocks))?;
+        let recovery = Miniscript::from_ast(Terminal::AndV(
+            Arc::new(Miniscript::from_ast(Terminal::Verify(Arc::new(older)))?),
+            pk(recovery)?,
+        ))?;
+        let ms = Miniscript::from_ast(Terminal::OrD(pk(primary)?, Arc::new(recovery)))?;
+        Descriptor::new_wsh(ms)
+    }
+
     /// Create a new covenant descriptor
     // All extensions are supported in wsh descriptor
     pub fn new_cov_wsh(pk: Pk, ms: Miniscript<Pk, Segwitv0, CovenantExt>) -> Result<Self, Error> {
@@ -442,6 +733,29 @@ impl<Pk: MiniscriptKey> Descriptor<Pk> {
         Ok(Descriptor::Cov(cov))
     }
 
+    /// Get the Miniscript of script-based descriptors, in its script
+    /// context. Returns `None` for key-based descriptors (`pkh`, `wpkh`,
+    /// `sh(wpkh)`) and sorted multisigs, which have no Miniscript.
+    pub fn as_miniscript(&self) -> Option<DescriptorMiniscript<Pk>> {
+        match *self {
+            Descriptor::Bare(ref bare) => Some(DescriptorMiniscript::Bare(bare.as_inner())),
+            Descriptor::Sh(ref sh) => match sh.as_inner() {
+                ShInner::Ms(ref ms) => Some(DescriptorMiniscript::Sh(ms)),
+                ShInner::Wsh(ref wsh) => match wsh.as_inner() {
+                    WshInner::Ms(ref ms) => Some(DescriptorMiniscript::Wsh(ms)),
+                    WshInner::SortedMulti(..) => None,
+                },
+                ShInner::Wpkh(..) | ShInner::SortedMulti(..) => None,
+            },
+            Descriptor::Wsh(ref wsh) => match wsh.as_inner() {
+                WshInner::Ms(ref ms) => Some(DescriptorMiniscript::Wsh(ms)),
+                WshInner::SortedMulti(..) => None,
+            },
+            Descriptor::Cov(ref cov) => Some(DescriptorMiniscript::Cov(cov.to_ms())),
+            Descriptor::Pkh(..) | Descriptor::Wpkh(..) => None,
+        }
+    }
+
     /// Get the [DescriptorType] of [Descriptor]
     pub fn desc_type(&self) -> DescriptorType {
         match *self {
@@ -465,6 +779,82 @@ impl<Pk: MiniscriptKey> Descriptor<Pk> {
         }
     }
 
+    /// Get the [ScriptPubKeyType] of [Descriptor]
+    /// This only depends on the outer wrapper of the descriptor
+    /// and does not require deriving any keys.
+    pub fn spk_type(&self) -> ScriptPubKeyType {
+        match *self {
+            Descriptor::Bare(ref _bare) => ScriptPubKeyType::Bare,
+            Descriptor::Pkh(ref _pkh) => ScriptPubKeyType::P2pkh,
+            Descriptor::Wpkh(ref _wpkh) => ScriptPubKeyType::P2wpkh,
+            Descriptor::Sh(ref _sh) => ScriptPubKeyType::P2sh,
+            Descriptor::Wsh(ref _wsh) => ScriptPubKeyType::P2wsh,
+            Descriptor::Cov(ref _cov) => ScriptPubKeyType::P2wsh,
+        }
+    }
+
+    /// Enumerate the minimal sets of keys whose signatures can satisfy
+    /// the descriptor, computed from its lifted semantic policy.
+    /// Timelocks and hash preimages are assumed to be available.
+    /// See [policy::semantic::Policy::satisfying_key_sets]
+    pub fn satisfying_key_sets(&self) -> Result<Vec<HashSet<Pk::Hash>>, Error> {
+        Ok(self.lift()?.satisfying_key_sets())
+    }
+
+    /// Converts a `multi` descriptor into the equivalent `sortedmulti` one
+    ///
+    /// The order of the keys in a `multi` is significant: `multi(2,A,B)`
+    /// and `multi(2,B,A)` produce different scripts. A `sortedmulti` always
+    /// sorts its keys according to BIP 67, so the conversion is only done
+    /// for sh, wsh and sh-wrapped wsh `multi` descriptors whose keys are
+    /// already in that order. Both forms then produce the same script,
+    /// which makes this useful to deduplicate descriptors.
+    /// Any other descriptor is returned unchanged.
+    pub fn to_sorted_form(&self) -> Descriptor<Pk>
+    where
+        Pk: ToPublicKey,
+    {
+        fn sorted_multi<Pk: ToPublicKey, Ctx: ScriptContext, Ext: Extension<Pk>>(
+            ms: &Miniscript<Pk, Ctx, Ext>,
+        ) -> Option<(usize, Vec<Pk>)> {
+            match ms.node {
+                Terminal::Multi(k, ref pks) if SortedMultiVec::<Pk, Ctx>::is_sorted(pks) => {
+                    Some((k, pks.clone()))
+                }
+                _ => None,
+            }
+        }
+        let sorted = match *self {
+            Descriptor::Sh(ref sh) => match sh.as_inner() {
+                ShInner::Ms(ref ms) => {
+                    sorted_multi(ms).map(|(k, pks)| Descriptor::new_sh_sortedmulti(k, pks))
+                }
+                ShInner::Wsh(ref wsh) => match wsh.as_inner() {
+                    WshInner::Ms(ref ms) => {
+                        sorted_multi(ms).map(|(k, pks)| Descriptor::new_sh_wsh_sortedmulti(k, pks))
+                    }
+                    WshInner::SortedMulti(..) => None,
+                },
+                ShInner::Wpkh(..) | ShInner::SortedMulti(..) => None,
+            },
+            Descriptor::Wsh(ref wsh) => match wsh.as_inner() {
+                WshInner::Ms(ref ms) => {
+                    sorted_multi(ms).map(|(k, pks)| Descriptor::new_wsh_sortedmulti(k, pks))
+                }
+                WshInner::SortedMulti(..) => None,
+            },
+            Descriptor::Bare(..)
+            | Descriptor::Pkh(..)
+            | Descriptor::Wpkh(..)
+            | Descriptor::Cov(..) => None,
+        };
+        match sorted {
+            // The multi already passed the same context checks
+            Some(desc) => desc.expect("sortedmulti with the keys of a valid multi"),
+            None => self.clone(),
+        }
+    }
+
     /// Tries to convert descriptor as a covenant descriptor
     pub fn as_cov(&self) -> Result<&CovenantDescriptor<Pk, CovenantExt>, Error> {
         if let Descriptor::Cov(cov) = self {
@@ -474,1202 +864,4559 @@ impl<Pk: MiniscriptKey> Descriptor<Pk> {
         }
     }
 
-    /// Return a string without the checksum
-    pub fn to_string_no_chksum(&self) -> String {
-        format!("{:?}", self)
-    }
-}
-
-impl<P: MiniscriptKey, Q: MiniscriptKey> TranslatePk<P, Q> for Descriptor<P> {
-    type Output = Descriptor<Q>;
-    /// Convert a descriptor using abstract keys to one using specific keys
-    /// This will panic if translatefpk returns an uncompressed key when
-    /// converting to a Segwit descriptor. To prevent this panic, ensure
-    /// translatefpk returns an error in this case instead.
-    fn translate_pk<Fpk, Fpkh, E>(
+    /// Returns satisfying witness and scriptSig to spend an output
+    /// controlled by the descriptor, taking the spending path chosen by
+    /// `path` instead of the cheapest one.
+    ///
+    /// Each `andor`, `or_b`, `or_c`, `or_d` and `or_i` fragment on the
+    /// chosen path consumes the next entry of `path` in script order,
+    /// `0` selecting the first branch and `1` the second one. Descriptors
+    /// without Miniscript only accept an empty `path`. See
+    /// [Miniscript::satisfy_path] for details.
+    pub fn satisfy_path<S>(
         &self,
-        mut translatefpk: Fpk,
-        mut translatefpkh: Fpkh,
-    ) -> Result<Descriptor<Q>, E>
+        satisfier: S,
+        path: &[usize],
+    ) -> Result<(Vec<Vec<u8>>, Script), Error>
     where
-        Fpk: FnMut(&P) -> Result<Q, E>,
-        Fpkh: FnMut(&P::Hash) -> Result<Q::Hash, E>,
-        Q: MiniscriptKey,
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+        S: Satisfier<Pk>,
     {
-        let desc = match *self {
-            Descriptor::Bare(ref bare) => {
-                Descriptor::Bare(bare.translate_pk(&mut translatefpk, &mut translatefpkh)?)
-            }
-            Descriptor::Pkh(ref pk) => {
-                Descriptor::Pkh(pk.translate_pk(&mut translatefpk, &mut translatefpkh)?)
-            }
-            Descriptor::Wpkh(ref pk) => {
-                Descriptor::Wpkh(pk.translate_pk(&mut translatefpk, &mut translatefpkh)?)
-            }
-            Descriptor::Sh(ref sh) => {
-                Descriptor::Sh(sh.translate_pk(&mut translatefpk, &mut translatefpkh)?)
+        match *self {
+            Descriptor::Bare(ref bare) => bare.get_satisfaction_path(satisfier, path),
+            Descriptor::Pkh(ref pkh) => {
+                check_path_consumed(path)?;
+                pkh.get_satisfaction(satisfier)
             }
-            Descriptor::Wsh(ref wsh) => {
-                Descriptor::Wsh(wsh.translate_pk(&mut translatefpk, &mut translatefpkh)?)
+            Descriptor::Wpkh(ref wpkh) => {
+                check_path_consumed(path)?;
+                wpkh.get_satisfaction(satisfier)
             }
+            Descriptor::Sh(ref sh) => sh.get_satisfaction_path(satisfier, path),
+            Descriptor::Wsh(ref wsh) => wsh.get_satisfaction_path(satisfier, path),
             Descriptor::Cov(ref cov) => {
-                Descriptor::Cov(cov.translate_pk(&mut translatefpk, &mut translatefpkh)?)
+                let mut witness = cov.satisfy_path(satisfier, path)?;
+                witness.push(cov.encode().into_bytes());
+                Ok((witness, Script::new()))
             }
-        };
-        Ok(desc)
+        }
     }
-}
 
-impl<Pk: MiniscriptKey> ElementsTrait<Pk> for Descriptor<Pk>
-where
-    Pk: FromStr,
-    Pk::Hash: FromStr,
-    <Pk as FromStr>::Err: ToString,
-    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
-{
-    fn blind_addr(
-        &self,
-        blinder: Option<secp256k1_zkp::PublicKey>,
-        params: &'static elements::AddressParams,
-    ) -> Result<elements::Address, Error>
+    /// Returns satisfying witness and scriptSig to spend an output
+    /// controlled by the descriptor or, when the satisfier lacks some of
+    /// the data, what is missing along the cheapest spending path: the one
+    /// with the fewest missing items, and the lightest among those.
+    ///
+    /// Thresholds report all their missing signatures and preimages, even
+    /// if only some of them are needed. An empty list of missing items
+    /// means that everything is available along the path, but it has no
+    /// non-malleable satisfaction. Covenant descriptors report the items
+    /// missing from their default satisfaction, with an empty path.
+    pub fn try_satisfy<S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), MissingItems<Pk>>
     where
-        Pk: ToPublicKey,
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+        S: Satisfier<Pk>,
     {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.blind_addr(blinder, params),
-            Descriptor::Pkh(ref pkh) => pkh.blind_addr(blinder, params),
-            Descriptor::Wpkh(ref wpkh) => wpkh.blind_addr(blinder, params),
-            Descriptor::Wsh(ref wsh) => wsh.blind_addr(blinder, params),
-            Descriptor::Sh(ref sh) => sh.blind_addr(blinder, params),
-            Descriptor::Cov(ref cov) => cov.blind_addr(blinder, params),
+        if let Ok(satisfaction) = self.get_satisfaction(&satisfier) {
+            return Ok(satisfaction);
         }
-    }
-}
 
-impl<Pk: MiniscriptKey> DescriptorTrait<Pk> for Descriptor<Pk>
-where
-    Pk: FromStr,
-    Pk::Hash: FromStr,
-    <Pk as FromStr>::Err: ToString,
-    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
-{
-    /// Whether the descriptor is safe
-    /// Checks whether all the spend paths in the descriptor are possible
-    /// on the bitcoin network under the current standardness and consensus rules
-    /// Also checks whether the descriptor requires signauture on all spend paths
-    /// And whether the script is malleable.
-    /// In general, all the guarantees of miniscript hold only for safe scripts.
-    /// All the analysis guarantees of miniscript only hold safe scripts.
-    /// The signer may not be able to find satisfactions even if one exists
-    fn sanity_check(&self) -> Result<(), Error> {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.sanity_check(),
-            Descriptor::Pkh(ref pkh) => pkh.sanity_check(),
-            Descriptor::Wpkh(ref wpkh) => wpkh.sanity_check(),
-            Descriptor::Wsh(ref wsh) => wsh.sanity_check(),
-            Descriptor::Sh(ref sh) => sh.sanity_check(),
-            Descriptor::Cov(ref cov) => cov.sanity_check(),
+        let logger = LoggingSatisfier::new(&satisfier);
+        let paths = match self.path_weights() {
+            Ok(paths) => paths,
+            Err(_) => {
+                let _ = self.get_satisfaction(&logger);
+                return Err(MissingItems {
+                    path: vec![],
+                    missing: logger.missing(),
+                });
+            }
+        };
+        let mut best: Option<(usize, MissingItems<Pk>)> = None;
+        for (path, weight) in paths {
+            logger.clear();
+            let _ = self.satisfy_path(&logger, &path);
+            let missing = logger.missing();
+            let is_cheaper = match best {
+                Some((best_weight, ref best)) => {
+                    (missing.len(), weight) < (best.missing.len(), best_weight)
+                }
+                None => true,
+            };
+            if is_cheaper {
+                best = Some((weight, MissingItems { path, missing }));
+            }
         }
+        Err(best.map(|(_, items)| items).unwrap_or(MissingItems {
+            path: vec![],
+            missing: vec![],
+        }))
     }
-    /// Computes the Bitcoin address of the descriptor, if one exists
-    fn address(&self, params: &'static elements::AddressParams) -> Result<elements::Address, Error>
+
+    /// Number of signatures needed to spend an output controlled by the
+    /// descriptor with only the `available_keys` signing, along the path
+    /// requiring the fewest of them. Hash preimages and timelocks are
+    /// assumed to be available.
+    ///
+    /// Returns `None` if no path can be satisfied by these keys, or for
+    /// covenant descriptors, whose satisfaction depends on the spending
+    /// transaction.
+    pub fn required_signature_count(&self, available_keys: &[Pk]) -> Option<usize>
     where
-        Pk: ToPublicKey,
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
     {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.address(params),
-            Descriptor::Pkh(ref pkh) => pkh.address(params),
-            Descriptor::Wpkh(ref wpkh) => wpkh.address(params),
-            Descriptor::Wsh(ref wsh) => wsh.address(params),
-            Descriptor::Sh(ref sh) => sh.address(params),
-            Descriptor::Cov(ref cov) => cov.address(params),
-        }
+        let mut satisfier = self.dummy_satisfier(None, None);
+        satisfier.signers = Some(available_keys.to_vec());
+        let paths = self.descriptor_spending_paths().ok()?;
+        paths
+            .iter()
+            .filter_map(|path| {
+                let (witness, script_sig) = self.satisfy_path(&satisfier, path).ok()?;
+                let (witness_sigs, script_sig_sigs) = count_dummy_sigs::<Pk>(&witness, &script_sig);
+                Some(witness_sigs + script_sig_sigs)
+            })
+            .min()
     }
 
-    /// Computes the scriptpubkey of the descriptor
-    fn script_pubkey(&self) -> Script
+    /// Hash preimages revealed when spending an output controlled by the
+    /// descriptor with only the `available_keys` signing, along the
+    /// cheapest path these keys can satisfy. Hashlocks on the other paths
+    /// are not listed. Timelocks are assumed to be satisfied.
+    ///
+    /// The returned lookups are all preimage ones, in the order of the
+    /// hashlocks in the descriptor. Returns `None` if no path can be
+    /// satisfied by these keys, or for covenant descriptors.
+    pub fn required_preimages(&self, available_keys: &[Pk]) -> Option<Vec<Lookup<Pk>>>
     where
-        Pk: ToPublicKey,
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
     {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.script_pubkey(),
-            Descriptor::Pkh(ref pkh) => pkh.script_pubkey(),
-            Descriptor::Wpkh(ref wpkh) => wpkh.script_pubkey(),
-            Descriptor::Wsh(ref wsh) => wsh.script_pubkey(),
-            Descriptor::Sh(ref sh) => sh.script_pubkey(),
-            Descriptor::Cov(ref cov) => cov.script_pubkey(),
+        let mut satisfier = self.dummy_satisfier(None, None);
+        satisfier.signers = Some(available_keys.to_vec());
+        let (witness, script_sig) = self
+            .descriptor_spending_paths()
+            .ok()?
+            .iter()
+            .filter_map(|path| self.satisfy_path(&satisfier, path).ok())
+            .min_by_key(|sat| satisfaction_weight(&sat.0, &sat.1, |_| false))?;
+
+        let mut pushes: Vec<&[u8]> = witness.iter().map(|elem| &elem[..]).collect();
+        for ins in script_sig.instructions().flatten() {
+            if let Instruction::PushBytes(bytes) = ins {
+                pushes.push(bytes);
+            }
         }
+        let mut hashlocks = vec![];
+        collect_hashlocks(&self.lift().ok()?, &mut hashlocks);
+        Some(
+            hashlocks
+                .into_iter()
+                .filter(|hashlock| pushes.contains(&&hashlock.1[..]))
+                .map(|(lookup, _)| lookup)
+                .collect(),
+        )
     }
 
-    /// Computes the scriptSig that will be in place for an unsigned
-    /// input spending an output with this descriptor. For pre-segwit
-    /// descriptors, which use the scriptSig for signatures, this
-    /// returns the empty script.
+    /// Computes the range of weights of the satisfactions of the
+    /// descriptor, over all its spending paths, in weight units.
     ///
-    /// This is used in Segwit transactions to produce an unsigned
-    /// transaction whose txid will not change during signing (since
-    /// only the witness data will change).
-    fn unsigned_script_sig(&self) -> Script
+    /// The maximum is the [DescriptorTrait::max_satisfaction_weight]. The
+    /// minimum assumes signatures are 71 bytes long with their sighash
+    /// byte, the size produced by signers grinding for a low R. Shorter
+    /// signatures are possible but rare.
+    ///
+    /// Errors when the descriptor cannot be satisfied, and for covenant
+    /// descriptors.
+    pub fn satisfaction_weight_range(&self) -> Result<(usize, usize), Error>
     where
-        Pk: ToPublicKey,
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
     {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.unsigned_script_sig(),
-            Descriptor::Pkh(ref pkh) => pkh.unsigned_script_sig(),
-            Descriptor::Wpkh(ref wpkh) => wpkh.unsigned_script_sig(),
-            Descriptor::Wsh(ref wsh) => wsh.unsigned_script_sig(),
-            Descriptor::Sh(ref sh) => sh.unsigned_script_sig(),
-            Descriptor::Cov(ref cov) => cov.unsigned_script_sig(),
+        let weights = self.map_dummy_satisfactions(|witness, script_sig| {
+            let max = satisfaction_weight(witness, script_sig, |_| false);
+            let (witness_sigs, script_sig_sigs) = count_dummy_sigs::<Pk>(witness, script_sig);
+            (max, max - witness_sigs - 4 * script_sig_sigs)
+        })?;
+        let min = weights.iter().map(|&(_, (_, min))| min).min();
+        let max = weights.iter().map(|&(_, (max, _))| max).max();
+        match (min, max) {
+            (Some(min), Some(max)) => Ok((min, max)),
+            _ => Err(Error::CouldNotSatisfy),
         }
     }
 
-    /// Computes the "witness script" of the descriptor, i.e. the underlying
-    /// script before any hashing is done. For `Bare`, `Pkh` and `Wpkh` this
-    /// is the scriptPubkey; for `ShWpkh` and `Sh` this is the redeemScript;
-    /// for the others it is the witness script.
-    fn explicit_script(&self) -> Script
+    /// Whether all the satisfactions of the descriptor have the same
+    /// weight, so that re-signing a transaction, for example to bump its
+    /// fee, cannot change its size. This requires a single satisfaction
+    /// weight over all the spending paths and no signatures, whose size
+    /// varies, see [Descriptor::satisfaction_weight_range].
+    ///
+    /// Returns false when the descriptor cannot be satisfied, and for
+    /// covenant descriptors.
+    pub fn witness_size_is_deterministic(&self) -> bool
     where
-        Pk: ToPublicKey,
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
     {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.explicit_script(),
-            Descriptor::Pkh(ref pkh) => pkh.explicit_script(),
-            Descriptor::Wpkh(ref wpkh) => wpkh.explicit_script(),
-            Descriptor::Wsh(ref wsh) => wsh.explicit_script(),
-            Descriptor::Sh(ref sh) => sh.explicit_script(),
-            Descriptor::Cov(ref cov) => cov.explicit_script(),
+        match self.satisfaction_weight_range() {
+            Ok((min, max)) => min == max,
+            Err(_) => false,
         }
     }
 
-    /// Returns satisfying witness and scriptSig to spend an
-    /// output controlled by the given descriptor if it possible to
-    /// construct one using the satisfier S.
-    fn get_satisfaction<S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), Error>
+    /// Computes the weight of satisfying the descriptor along each of its
+    /// spending paths, as returned by [Miniscript::spending_paths].
+    /// Descriptors without a Miniscript have the single empty path.
+    ///
+    /// The satisfactions use dummy signatures of the maximum size, and
+    /// assume all hash preimages and timelocks are available, so the
+    /// weight of the heaviest path is the
+    /// [DescriptorTrait::max_satisfaction_weight]. Paths which can never
+    /// be satisfied are skipped.
+    ///
+    /// Errors for covenant descriptors, whose satisfaction weight depends
+    /// on the spending transaction.
+    pub fn path_weights(&self) -> Result<Vec<(Vec<usize>, usize)>, Error>
     where
-        Pk: ToPublicKey,
-        S: Satisfier<Pk>,
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
     {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.get_satisfaction(satisfier),
-            Descriptor::Pkh(ref pkh) => pkh.get_satisfaction(satisfier),
-            Descriptor::Wpkh(ref wpkh) => wpkh.get_satisfaction(satisfier),
-            Descriptor::Wsh(ref wsh) => wsh.get_satisfaction(satisfier),
-            Descriptor::Sh(ref sh) => sh.get_satisfaction(satisfier),
-            Descriptor::Cov(ref cov) => cov.get_satisfaction(satisfier),
-        }
+        self.map_dummy_satisfactions(|witness, script_sig| {
+            satisfaction_weight(witness, script_sig, |_| false)
+        })
     }
 
-    /// Computes an upper bound on the weight of a satisfying witness to the
-    /// transaction. Assumes all signatures are 73 bytes, including push opcode
-    /// and sighash suffix. Includes the weight of the VarInts encoding the
-    /// scriptSig and witness stack length.
-    fn max_satisfaction_weight(&self) -> Result<usize, Error> {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.max_satisfaction_weight(),
-            Descriptor::Pkh(ref pkh) => pkh.max_satisfaction_weight(),
-            Descriptor::Wpkh(ref wpkh) => wpkh.max_satisfaction_weight(),
-            Descriptor::Wsh(ref wsh) => wsh.max_satisfaction_weight(),
-            Descriptor::Sh(ref sh) => sh.max_satisfaction_weight(),
-            Descriptor::Cov(ref cov) => cov.max_satisfaction_weight(),
-        }
+    /// Computes the fixed part of the [DescriptorTrait::max_satisfaction_weight],
+    /// that is the weight of the heaviest satisfaction without its
+    /// signatures and hash preimages. This covers the script pushes, the
+    /// branch selectors and the redeem or witness script.
+    ///
+    /// The maximum satisfaction weight is obtained back by adding 73 weight
+    /// units per signature and 33 per preimage in the witness, four times
+    /// as much for the ones in the `scriptSig`.
+    ///
+    /// Errors for covenant descriptors, whose satisfaction weight depends
+    /// on the spending transaction.
+    pub fn witness_scaffold_weight(&self) -> Result<usize, Error>
+    where
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+    {
+        let dummy_sig = elementssig_to_rawsig(&DummySatisfier::<Pk>::dummy_sig());
+        let mut hashlocks = vec![];
+        collect_hashlocks(&self.lift()?, &mut hashlocks);
+        let is_variable = |elem: &[u8]| {
+            elem == &dummy_sig[..] || hashlocks.iter().any(|hashlock| elem == &hashlock.1[..])
+        };
+        self.map_dummy_satisfactions(|witness, script_sig| {
+            (
+                satisfaction_weight(witness, script_sig, |_| false),
+                satisfaction_weight(witness, script_sig, is_variable),
+            )
+        })?
+        .into_iter()
+        .map(|(_, weights)| weights)
+        .max()
+        .map(|(_, scaffold)| scaffold)
+        .ok_or(Error::CouldNotSatisfy)
     }
 
-    /// Get the `scriptCode` of a transaction output.
+    /// Fills `txin` with a placeholder satisfaction of the descriptor, with
+    /// dummy signatures and hash preimages of the right size, so that the
+    /// transaction can be serialized to estimate its fee before signing.
     ///
-    /// The `scriptCode` is the Script of the previous transaction output being serialized in the
-    /// sighash when evaluating a `CHECKSIG` & co. OP code.
-    fn script_code(&self) -> Script
+    /// The heaviest spending path is used, so the input weighs exactly
+    /// [DescriptorTrait::max_satisfaction_weight] more than an unsigned
+    /// one. Signatures are assumed to be 72 bytes long with their sighash
+    /// byte, which real signatures never exceed.
+    ///
+    /// Errors when the descriptor cannot be satisfied, and for covenant
+    /// descriptors.
+    pub fn satisfy_with_dummy(&self, txin: &mut elements::TxIn) -> Result<(), Error>
     where
-        Pk: ToPublicKey,
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
     {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.script_code(),
-            Descriptor::Pkh(ref pkh) => pkh.script_code(),
-            Descriptor::Wpkh(ref wpkh) => wpkh.script_code(),
-            Descriptor::Wsh(ref wsh) => wsh.script_code(),
-            Descriptor::Sh(ref sh) => sh.script_code(),
-            Descriptor::Cov(ref cov) => cov.script_code(),
-        }
+        let (_, (_, witness, script_sig)) = self
+            .map_dummy_satisfactions(|witness, script_sig| {
+                (
+                    satisfaction_weight(witness, script_sig, |_| false),
+                    witness.to_vec(),
+                    script_sig.clone(),
+                )
+            })?
+            .into_iter()
+            .max_by_key(|&(_, (weight, _, _))| weight)
+            .ok_or(Error::CouldNotSatisfy)?;
+        txin.witness.script_witness = witness;
+        txin.script_sig = script_sig;
+        Ok(())
     }
-}
 
-impl<Pk: MiniscriptKey> ForEachKey<Pk> for Descriptor<Pk> {
-    fn for_each_key<'a, F: FnMut(ForEach<'a, Pk>) -> bool>(&'a self, pred: F) -> bool
+    /// Computes the dust value of an explicit output paying to this
+    /// descriptor, that is the value below which the output costs more to
+    /// create and spend than it is worth at a fee rate of `fee_rate`
+    /// satoshis per 1000 virtual bytes.
+    ///
+    /// The spending cost uses the [DescriptorTrait::max_satisfaction_weight].
+    /// For blinded outputs use [Blinded::dust_value], which accounts for the
+    /// confidential commitments and proofs.
+    ///
+    /// Errors when the descriptor cannot be satisfied.
+    pub fn dust_value(&self, fee_rate: u64) -> Result<u64, Error>
     where
-        Pk: 'a,
-        Pk::Hash: 'a,
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
     {
-        match *self {
-            Descriptor::Bare(ref bare) => bare.for_each_key(pred),
-            Descriptor::Pkh(ref pkh) => pkh.for_each_key(pred),
-            Descriptor::Wpkh(ref wpkh) => wpkh.for_each_key(pred),
-            Descriptor::Wsh(ref wsh) => wsh.for_each_key(pred),
-            Descriptor::Sh(ref sh) => sh.for_each_key(pred),
-            Descriptor::Cov(ref cov) => cov.for_any_key(pred),
+        self.dust_value_inner(fee_rate, false)
+    }
+
+    /// Computes the message to sign for spending input `input_index` of
+    /// `pset`, which must be locked by this descriptor, together with the
+    /// sighash type it commits to: the one requested by the input or
+    /// `SigHashType::All` by default.
+    ///
+    /// The message commits to the `scriptCode` of the descriptor and, for
+    /// segwit descriptors, to the value of the spent utxo. For covenant
+    /// descriptors this is the message signed by the covenant key, keys
+    /// inside the Miniscript sign over the [DescriptorTrait::script_code]
+    /// instead.
+    ///
+    /// Errors when the input has no utxo, or when the utxo is not locked
+    /// by this descriptor. Panics on out of bound input index.
+    pub fn sighash_message(
+        &self,
+        pset: &Pset,
+        input_index: usize,
+    ) -> Result<(secp256k1_zkp::Message, elements::SigHashType), pset::Error>
+    where
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+    {
+        let input_err = |e| pset::Error::InputError(e, input_index);
+        let script_pubkey = pset::get_scriptpubkey(pset, input_index).map_err(input_err)?;
+        let expected = self.script_pubkey();
+        if *script_pubkey != expected {
+            return Err(input_err(pset::InputError::InvalidScriptPubkey {
+                script_pubkey: script_pubkey.clone(),
+                expected,
+            }));
         }
+        let amount = pset::get_amt(pset, input_index).map_err(input_err)?;
+        let sighash_type = pset.inputs[input_index]
+            .sighash_type
+            .unwrap_or(elements::SigHashType::All);
+
+        let tx = pset.extract_tx()?;
+        let mut cache = SigHashCache::new(&tx);
+        let hash = match *self {
+            Descriptor::Bare(..) | Descriptor::Pkh(..) => {
+                cache.legacy_sighash(input_index, &self.script_code(), sighash_type)
+            }
+            Descriptor::Sh(ref sh) => match sh.as_inner() {
+                ShInner::Ms(..) | ShInner::SortedMulti(..) => {
+                    cache.legacy_sighash(input_index, &self.script_code(), sighash_type)
+                }
+                ShInner::Wsh(..) | ShInner::Wpkh(..) => {
+                    cache.segwitv0_sighash(input_index, &self.script_code(), amount, sighash_type)
+                }
+            },
+            Descriptor::Wpkh(..) | Descriptor::Wsh(..) => {
+                cache.segwitv0_sighash(input_index, &self.script_code(), amount, sighash_type)
+            }
+            Descriptor::Cov(ref cov) => {
+                cache.segwitv0_sighash(input_index, &cov.cov_script_code(), amount, sighash_type)
+            }
+        };
+        let msg = secp256k1_zkp::Message::from_slice(&hash[..])
+            .expect("cryptographically unreachable for this to fail");
+        Ok((msg, sighash_type))
     }
-}
 
-impl Descriptor<DescriptorPublicKey> {
-    /// Whether or not the descriptor has any wildcards
-    pub fn is_deriveable(&self) -> bool {
-        self.for_any_key(|key| key.as_key().is_deriveable())
+    /// Computes the maximum number of witness stack elements of a
+    /// satisfaction of the descriptor, including the witness script, for
+    /// comparison with the standardness limits of relay nodes. Descriptors
+    /// spent through the scriptSig alone have no witness elements.
+    ///
+    /// Errors when the descriptor cannot be satisfied.
+    pub fn max_witness_items(&self) -> Result<usize, Error> {
+        match *self {
+            Descriptor::Bare(ref bare) => bare
+                .as_inner()
+                .max_satisfaction_witness_elements()
+                .map(|_| 0),
+            Descriptor::Pkh(..) => Ok(0),
+            Descriptor::Wpkh(..) => Ok(2),
+            Descriptor::Sh(ref sh) => match *sh.as_inner() {
+                ShInner::Wsh(ref wsh) => Ok(wsh_witness_items(wsh)?),
+                ShInner::Wpkh(..) => Ok(2),
+                ShInner::SortedMulti(..) => Ok(0),
+                ShInner::Ms(ref ms) => ms.max_satisfaction_witness_elements().map(|_| 0),
+            },
+            Descriptor::Wsh(ref wsh) => wsh_witness_items(wsh),
+            // The covenant checks read the transaction from the witness
+            Descriptor::Cov(ref cov) => Ok(cov.ms.max_satisfaction_witness_elements()? + 12),
+        }
     }
 
-    /// Derives all wildcard keys in the descriptor using the supplied index
+    /// Computes an upper bound on the weight added to an input by its
+    /// satisfaction, for fee estimation. Unlike
+    /// [DescriptorTrait::max_satisfaction_weight], this excludes the weight
+    /// of the unsatisfied input: the length byte of its empty scriptSig and,
+    /// for segwit spends, the count of its empty script witness. The
+    /// issuance rangeproofs and peg-in witness of Elements inputs are not
+    /// affected by the satisfaction. Assumes all signatures are 73 bytes,
+    /// including push opcode and sighash suffix.
     ///
-    /// Panics if given an index ≥ 2^31
-    pub fn derive(&self, index: u32) -> Descriptor<DescriptorPublicKey> {
-        self.translate_pk2_infallible(|pk| pk.clone().derive(index))
+    /// Errors when the descriptor cannot be satisfied.
+    pub fn max_weight_to_satisfy(&self) -> Result<usize, Error>
+    where
+        Pk: FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+    {
+        let is_segwit = match *self {
+            Descriptor::Bare(..) | Descriptor::Pkh(..) => false,
+            Descriptor::Wpkh(..) | Descriptor::Wsh(..) | Descriptor::Cov(..) => true,
+            Descriptor::Sh(ref sh) => match *sh.as_inner() {
+                ShInner::Wsh(..) | ShInner::Wpkh(..) => true,
+                ShInner::SortedMulti(..) | ShInner::Ms(..) => false,
+            },
+        };
+        let weight = self.max_satisfaction_weight()?;
+        Ok(if is_segwit {
+            weight - 4 - 1
+        } else {
+            weight - 4
+        })
     }
 
-    /// Parse a descriptor that may contain secret keys
+    /// Checks that outputs paying to this descriptor, and the transactions
+    /// spending them along any path, are relayed under the standardness
+    /// policy of Liquid, which is the one of Bitcoin Core. Satisfactions
+    /// use maximum size signatures.
     ///
-    /// Internally turns every secret key found into the corresponding public key and then returns a
-    /// a descriptor that only contains public keys and a map to lookup the secret key given a public key.
-    pub fn parse_descriptor<C: secp256k1_zkp::Signing>(
-        secp: &secp256k1_zkp::Secp256k1<C>,
-        s: &str,
-    ) -> Result<(Descriptor<DescriptorPublicKey>, KeyMap), Error> {
-        let parse_key = |s: &String,
-                         key_map: &mut KeyMap|
-         -> Result<DescriptorPublicKey, DescriptorKeyParseError> {
-            let (public_key, secret_key) = match DescriptorSecretKey::from_str(s) {
-                Ok(sk) => (sk.as_public(&secp)?, Some(sk)),
-                Err(_) => (DescriptorPublicKey::from_str(s)?, None),
-            };
+    /// Covenant descriptors read transaction data from the witness, so
+    /// only the size of their witness script is checked.
+    pub fn is_standard_on_liquid(&self) -> Result<(), NonStandardReason>
+    where
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+    {
+        // Bare descriptors are restricted to the standard scriptPubKeys
+        // when they are created
+        let is_wsh = match *self {
+            Descriptor::Cov(..) => {
+                let script_size = self.explicit_script().len();
+                if script_size > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+                    return Err(NonStandardReason::WitnessScriptSize(script_size));
+                }
+                return Ok(());
+            }
+            Descriptor::Sh(ref sh) => match *sh.as_inner() {
+                ShInner::Wsh(..) => true,
+                _ => false,
+            },
+            Descriptor::Wsh(..) => true,
+            Descriptor::Bare(..) | Descriptor::Pkh(..) | Descriptor::Wpkh(..) => false,
+        };
 
-            if let Some(secret_key) = secret_key {
-                key_map.insert(public_key.clone(), secret_key);
+        // The satisfier refuses to produce witnesses above these limits,
+        // so they are checked against the worst case beforehand
+        match self.as_miniscript() {
+            Some(DescriptorMiniscript::Sh(ms)) => {
+                let redeem_script = ms.encode();
+                let script_sig_size = ms
+                    .max_satisfaction_size()
+                    .map_err(|_| NonStandardReason::Unsatisfiable)?
+                    + script::Builder::new()
+                        .push_slice(redeem_script.as_bytes())
+                        .into_script()
+                        .len();
+                if script_sig_size > MAX_SCRIPTSIG_SIZE {
+                    return Err(NonStandardReason::ScriptSigSize(script_sig_size));
+                }
+            }
+            Some(DescriptorMiniscript::Wsh(ms)) => {
+                let items = ms
+                    .max_satisfaction_witness_elements()
+                    .map_err(|_| NonStandardReason::Unsatisfiable)?
+                    - 1;
+                if items > MAX_STANDARD_P2WSH_STACK_ITEMS {
+                    return Err(NonStandardReason::WitnessItems(items));
+                }
             }
+            _ => {}
+        }
 
-            Ok(public_key)
+        let satisfactions = self
+            .map_dummy_satisfactions(|witness, script_sig| (witness.to_vec(), script_sig.len()))
+            .map_err(|_| NonStandardReason::Unsatisfiable)?;
+        if satisfactions.is_empty() {
+            return Err(NonStandardReason::Unsatisfiable);
+        }
+        for (_, (witness, script_sig_size)) in satisfactions {
+            if script_sig_size > MAX_SCRIPTSIG_SIZE {
+                return Err(NonStandardReason::ScriptSigSize(script_sig_size));
+            }
+            if !is_wsh {
+                continue;
+            }
+            let (script, items) = witness
+                .split_last()
+                .expect("wsh witnesses end with the witness script");
+            if script.len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+                return Err(NonStandardReason::WitnessScriptSize(script.len()));
+            }
+            if items.len() > MAX_STANDARD_P2WSH_STACK_ITEMS {
+                return Err(NonStandardReason::WitnessItems(items.len()));
+            }
+            if let Some(item) = items
+                .iter()
+                .find(|item| item.len() > MAX_STANDARD_P2WSH_STACK_ITEM_SIZE)
+            {
+                return Err(NonStandardReason::WitnessItemSize(item.len()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the dust value of an explicit or confidential output paying
+    /// to this descriptor
+    fn dust_value_inner(&self, fee_rate: u64, confidential: bool) -> Result<u64, Error>
+    where
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+    {
+        let spk_len = self.script_pubkey().len();
+        let spk_size = varint_len(spk_len) + spk_len;
+        // Asset, value and nonce, followed by the output witness
+        let output_weight = if confidential {
+            4 * (33 + 33 + 33 + spk_size)
+                + varint_len(SURJECTION_PROOF_SIZE)
+                + SURJECTION_PROOF_SIZE
+                + varint_len(RANGEPROOF_SIZE)
+                + RANGEPROOF_SIZE
+        } else {
+            // Empty surjection proof and rangeproof
+            4 * (33 + 9 + 1 + spk_size) + 2
         };
+        // Outpoint and sequence, followed by the empty issuance rangeproofs
+        // and pegin witness
+        let input_weight = 4 * (32 + 4 + 4) + 3 + self.max_satisfaction_weight()?;
 
-        let mut keymap_pk = KeyMap::new();
-        let mut keymap_pkh = KeyMap::new();
+        let vsize = (output_weight + input_weight + 3) / 4;
+        Ok(vsize as u64 * fee_rate / 1000)
+    }
 
-        let descriptor = Descriptor::<String>::from_str(s)?;
-        let descriptor = descriptor
-            .translate_pk(
-                |pk| parse_key(pk, &mut keymap_pk),
-                |pkh| parse_key(pkh, &mut keymap_pkh),
-            )
-            .map_err(|e| Error::Unexpected(e.to_string()))?;
+    /// Computes the earliest chain state from which the descriptor can be
+    /// spent, given the `current_height` and `current_mtp` (median time past)
+    /// of the chain tip, and the spending path to use then.
+    ///
+    /// Absolute timelocks are compared with the chain tip, while relative
+    /// timelocks are counted from it, as for a coin confirmed in the next
+    /// block. Paths are compared by height first, then by time. Keys,
+    /// signatures and hash preimages are assumed to be available.
+    ///
+    /// Errors when the descriptor cannot be satisfied, and for covenant
+    /// descriptors.
+    pub fn earliest_spend(
+        &self,
+        current_height: u32,
+        current_mtp: u32,
+    ) -> Result<SpendEstimate, Error>
+    where
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+    {
+        let policy = self.lift()?;
+        let mut lock_times = policy.absolute_timelocks();
+        lock_times.insert(0, 0);
+        let mut sequences = policy.relative_timelocks();
+        sequences.insert(0, 0);
+
+        let mut best: Option<SpendEstimate> = None;
+        for path in self.descriptor_spending_paths()? {
+            for &lock_time in &lock_times {
+                for &sequence in &sequences {
+                    let satisfier = self.dummy_satisfier(Some(lock_time), Some(sequence));
+                    match self.satisfy_path(&satisfier, &path) {
+                        Ok(..) => {}
+                        Err(Error::CouldNotSatisfy) => continue,
+                        Err(e) => return Err(e),
+                    }
+                    let mut height = current_height;
+                    let mut time = current_mtp;
+                    if lock_time >= HEIGHT_TIME_THRESHOLD {
+                        // The locktime must be strictly below the median time past
+                        time = cmp::max(time, lock_time.saturating_add(1));
+                    } else {
+                        height = cmp::max(height, lock_time);
+                    }
+                    let relative = sequence & SEQUENCE_LOCKTIME_MASK;
+                    if sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+                        time = cmp::max(time, current_mtp.saturating_add(relative * 512));
+                    } else {
+                        height = cmp::max(height, current_height.saturating_add(relative));
+                    }
+
+                    let is_earlier = match best {
+                        Some(ref best) => (height, time) < (best.height, best.time),
+                        None => true,
+                    };
+                    if is_earlier {
+                        best = Some(SpendEstimate {
+                            path: path.clone(),
+                            lock_time,
+                            sequence,
+                            height,
+                            time,
+                        });
+                    }
+                }
+            }
+        }
+        best.ok_or(Error::CouldNotSatisfy)
+    }
 
-        keymap_pk.extend(keymap_pkh.into_iter());
+    /// Satisfies the descriptor along each of its spending paths using a
+    /// `DummySatisfier`, skipping the paths which can never be satisfied,
+    /// and maps each witness and `scriptSig` through `f`
+    fn map_dummy_satisfactions<T, F>(&self, f: F) -> Result<Vec<(Vec<usize>, T)>, Error>
+    where
+        F: Fn(&[Vec<u8>], &Script) -> T,
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+    {
+        let satisfier = self.dummy_satisfier(None, None);
+        let mut ret = vec![];
+        for path in self.descriptor_spending_paths()? {
+            match self.satisfy_path(&satisfier, &path) {
+                Ok((witness, script_sig)) => {
+                    let res = f(&witness, &script_sig);
+                    ret.push((path, res));
+                }
+                Err(Error::CouldNotSatisfy) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(ret)
+    }
 
-        Ok((descriptor, keymap_pk))
+    /// The spending paths of the Miniscript of the descriptor, or the
+    /// single empty path for descriptors without a Miniscript
+    fn descriptor_spending_paths(&self) -> Result<Vec<Vec<usize>>, Error> {
+        match *self {
+            Descriptor::Bare(ref bare) => Ok(bare.as_inner().spending_paths()),
+            Descriptor::Sh(ref sh) => match sh.as_inner() {
+                ShInner::Ms(ref ms) => Ok(ms.spending_paths()),
+                ShInner::Wsh(ref wsh) => match wsh.as_inner() {
+                    WshInner::Ms(ref ms) => Ok(ms.spending_paths()),
+                    WshInner::SortedMulti(..) => Ok(vec![vec![]]),
+                },
+                ShInner::Wpkh(..) | ShInner::SortedMulti(..) => Ok(vec![vec![]]),
+            },
+            Descriptor::Wsh(ref wsh) => match wsh.as_inner() {
+                WshInner::Ms(ref ms) => Ok(ms.spending_paths()),
+                WshInner::SortedMulti(..) => Ok(vec![vec![]]),
+            },
+            Descriptor::Pkh(..) | Descriptor::Wpkh(..) => Ok(vec![vec![]]),
+            Descriptor::Cov(..) => Err(Error::BadDescriptor(
+                "spending paths are not supported for covenant descriptors".to_owned(),
+            )),
+        }
     }
 
-    /// Serialize a descriptor to string with its secret keys
-    pub fn to_string_with_secret(&self, key_map: &KeyMap) -> String {
-        fn key_to_string(pk: &DescriptorPublicKey, key_map: &KeyMap) -> Result<String, ()> {
-            Ok(match key_map.get(pk) {
-                Some(secret) => secret.to_string(),
-                None => pk.to_string(),
-            })
+    /// A `DummySatisfier` for the keys of the descriptor
+    fn dummy_satisfier(&self, lock_time: Option<u32>, sequence: Option<u32>) -> DummySatisfier<Pk>
+    where
+        Pk: ToPublicKey,
+    {
+        let mut pkhs = HashMap::new();
+        self.for_each_key(|key| {
+            if let ForEach::Key(pk) = key {
+                pkhs.insert(pk.to_pubkeyhash(), pk.clone());
+            }
+            true
+        });
+        DummySatisfier {
+            pkhs,
+            lock_time,
+            sequence,
+            signers: None,
         }
+    }
 
-        let descriptor = self
-            .translate_pk::<_, _, ()>(
-                |pk| key_to_string(pk, key_map),
-                |pkh| key_to_string(pkh, key_map),
-            )
-            .expect("Translation to string cannot fail");
+    /// Return a string without the checksum
+    pub fn to_string_no_chksum(&self) -> String {
+        format!("{:?}", self)
+    }
+}
 
-        descriptor.to_string()
+/// Size of a surjection proof with a single input asset
+const SURJECTION_PROOF_SIZE: usize = 67;
+/// Size of a rangeproof for a 52 bit value, the Elements default
+const RANGEPROOF_SIZE: usize = 4174;
+
+/// Maximum number of witness elements of a satisfaction of a wsh
+/// descriptor, including the witness script
+fn wsh_witness_items<Pk: MiniscriptKey>(wsh: &Wsh<Pk>) -> Result<usize, Error> {
+    match *wsh.as_inner() {
+        WshInner::SortedMulti(ref smv) => Ok(smv.max_satisfaction_witness_elements()),
+        WshInner::Ms(ref ms) => ms.max_satisfaction_witness_elements(),
     }
 }
 
-impl<Pk> expression::FromTree for Descriptor<Pk>
+/// Weight of a satisfaction, leaving out the witness elements and
+/// `scriptSig` pushes for which `skip` returns true
+fn satisfaction_weight<F>(witness: &[Vec<u8>], script_sig: &Script, skip: F) -> usize
 where
-    Pk: MiniscriptKey + str::FromStr,
-    Pk::Hash: str::FromStr,
-    <Pk as FromStr>::Err: ToString,
-    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+    F: Fn(&[u8]) -> bool,
 {
-    /// Parse an expression tree into a descriptor
-    fn from_tree(top: &expression::Tree) -> Result<Descriptor<Pk>, Error> {
-        Ok(match (top.name, top.args.len() as u32) {
-            ("elpkh", 1) => Descriptor::Pkh(Pkh::from_tree(top)?),
-            ("elwpkh", 1) => Descriptor::Wpkh(Wpkh::from_tree(top)?),
-            ("elsh", 1) => Descriptor::Sh(Sh::from_tree(top)?),
-            ("elcovwsh", 2) => Descriptor::Cov(CovenantDescriptor::from_tree(top)?),
-            ("elwsh", 1) => Descriptor::Wsh(Wsh::from_tree(top)?),
-            _ => Descriptor::Bare(Bare::from_tree(top)?),
-        })
+    let mut witness_weight = if witness.is_empty() {
+        0
+    } else {
+        varint_len(witness.len())
+            + witness
+                .iter()
+                .map(|elem| varint_len(elem.len()) + elem.len())
+                .sum::<usize>()
+    };
+    for elem in witness {
+        if skip(elem) {
+            witness_weight -= varint_len(elem.len()) + elem.len();
+        }
+    }
+    let mut script_sig_weight = 4 * (varint_len(script_sig.len()) + script_sig.len());
+    for ins in script_sig.instructions_minimal().flatten() {
+        if let Instruction::PushBytes(push) = ins {
+            if skip(push) {
+                // All skipped pushes are short enough for a single opcode
+                script_sig_weight -= 4 * (1 + push.len());
+            }
+        }
     }
+    script_sig_weight + witness_weight
 }
 
-impl<Pk> FromStr for Descriptor<Pk>
-where
-    Pk: MiniscriptKey + str::FromStr,
-    Pk::Hash: str::FromStr,
-    <Pk as FromStr>::Err: ToString,
-    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
-{
-    type Err = Error;
+/// Counts the dummy signatures of a satisfaction made by a
+/// `DummySatisfier`, in the witness and in the `scriptSig`
+fn count_dummy_sigs<Pk: MiniscriptKey>(witness: &[Vec<u8>], script_sig: &Script) -> (usize, usize) {
+    let dummy_sig = elementssig_to_rawsig(&DummySatisfier::<Pk>::dummy_sig());
+    let witness_sigs = witness.iter().filter(|elem| **elem == dummy_sig).count();
+    let script_sig_sigs = script_sig
+        .instructions()
+        .filter(|ins| match *ins {
+            Ok(Instruction::PushBytes(bytes)) => bytes == &dummy_sig[..],
+            _ => false,
+        })
+        .count();
+    (witness_sigs, script_sig_sigs)
+}
 
-    fn from_str(s: &str) -> Result<Descriptor<Pk>, Error> {
-        if !s.starts_with(ELMTS_STR) {
-            return Err(Error::BadDescriptor(String::from(
-                "Not an Elements Descriptor",
-            )));
+/// Collects the hashlocks of `policy`, without duplicates, along with the
+/// preimages the `DummySatisfier` provides for them
+fn collect_hashlocks<Pk: MiniscriptKey>(
+    policy: &semantic::Policy<Pk>,
+    hashlocks: &mut Vec<(Lookup<Pk>, Preimage32)>,
+) {
+    let hashlock = match *policy {
+        semantic::Policy::Sha256(hash) => (Lookup::Sha256(hash), dummy_preimage(&hash[..])),
+        semantic::Policy::Hash256(hash) => (Lookup::Hash256(hash), dummy_preimage(&hash[..])),
+        semantic::Policy::Ripemd160(hash) => (Lookup::Ripemd160(hash), dummy_preimage(&hash[..])),
+        semantic::Policy::Hash160(hash) => (Lookup::Hash160(hash), dummy_preimage(&hash[..])),
+        semantic::Policy::Threshold(_, ref subs) => {
+            for sub in subs {
+                collect_hashlocks(sub, hashlocks);
+            }
+            return;
         }
-        let desc_str = verify_checksum(&s)?;
-        let top = expression::Tree::from_str(desc_str)?;
-        expression::FromTree::from_tree(&top)
+        _ => return,
+    };
+    if !hashlocks.contains(&hashlock) {
+        hashlocks.push(hashlock);
     }
 }
 
-impl<Pk: MiniscriptKey> fmt::Debug for Descriptor<Pk> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+/// The preimage provided by the `DummySatisfier` for a hash, made of its
+/// bytes so that preimages can be told apart in satisfactions
+fn dummy_preimage(hash: &[u8]) -> Preimage32 {
+    let mut preimage = [0; 32];
+    preimage[..hash.len()].copy_from_slice(hash);
+    preimage
+}
+
+/// Derives the secret key of a non-wildcard `pk` from the master key
+/// `root_xprv`, if `pk` has the master fingerprint of `root_xprv` and its
+/// public key is the one derived from it
+fn derive_from_root<C: secp256k1_zkp::Signing>(
+    pk: &DescriptorPublicKey,
+    root_xprv: &bip32::ExtendedPrivKey,
+    secp: &secp256k1_zkp::Secp256k1<C>,
+) -> Option<bitcoin::PrivateKey> {
+    if pk.master_fingerprint() != root_xprv.fingerprint(secp) {
+        return None;
+    }
+    match *pk {
+        DescriptorPublicKey::XPub(ref xpub) => {
+            let origin_path = match xpub.origin {
+                Some((_, ref path)) => path.clone(),
+                None => bip32::DerivationPath::from(vec![]),
+            };
+            let xprv = root_xprv.derive_priv(secp, &origin_path).ok()?;
+            let derived_xpub = bip32::ExtendedPubKey::from_private(secp, &xprv);
+            if derived_xpub.public_key != xpub.xkey.public_key
+                || derived_xpub.chain_code != xpub.xkey.chain_code
+            {
+                return None;
+            }
+            let xprv = xprv.derive_priv(secp, &xpub.derivation_path).ok()?;
+            Some(xprv.private_key)
+        }
+        DescriptorPublicKey::SinglePub(ref single) => {
+            let (_, ref path) = *single.origin.as_ref()?;
+            let sk = root_xprv.derive_priv(secp, path).ok()?.private_key;
+            if sk.public_key(secp) == single.key {
+                Some(sk)
+            } else {
+                None
+            }
+        }
+        DescriptorPublicKey::MultiXPub(..) => None,
+    }
+}
+
+/// Satisfier providing a maximum size dummy signature for every key and a
+/// dummy preimage, see [dummy_preimage], for every hash, and accepting the
+/// timelocks satisfied by its locktime and sequence number, or every
+/// timelock if they are `None`
+struct DummySatisfier<Pk: MiniscriptKey> {
+    /// Keys of the descriptor, by their hash. Keys only known by their
+    /// hash are assumed to be compressed
+    pkhs: HashMap<Pk::Hash, Pk>,
+    /// Locktime of the spending transaction
+    lock_time: Option<u32>,
+    /// Sequence number of the spending input
+    sequence: Option<u32>,
+    /// Keys which can sign, all of them when `None`
+    signers: Option<Vec<Pk>>,
+}
+
+impl<Pk: MiniscriptKey> DummySatisfier<Pk> {
+    fn can_sign(&self, pk: &Pk) -> bool {
+        match self.signers {
+            Some(ref signers) => signers.contains(pk),
+            None => true,
+        }
+    }
+
+    fn dummy_sig() -> ElementsSig {
+        // A high R and low S give a 71 byte DER encoding, which is 73 bytes
+        // with the sighash byte and length prefix, as assumed by
+        // `max_satisfaction_weight`
+        let mut compact = [0x01; 64];
+        compact[0] = 0x80;
+        let sig = secp256k1_zkp::Signature::from_compact(&compact).expect("valid compact sig");
+        (sig, elements::SigHashType::All)
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for DummySatisfier<Pk> {
+    fn lookup_sig(&self, pk: &Pk) -> Option<ElementsSig> {
+        if self.can_sign(pk) {
+            Some(Self::dummy_sig())
+        } else {
+            None
+        }
+    }
+
+    fn lookup_pkh_pk(&self, pkh: &Pk::Hash) -> Option<Pk> {
+        self.pkhs.get(pkh).cloned()
+    }
+
+    fn lookup_pkh_sig(&self, pkh: &Pk::Hash) -> Option<(bitcoin::PublicKey, ElementsSig)> {
+        let pk = match self.pkhs.get(pkh) {
+            Some(pk) if self.can_sign(pk) => pk.to_public_key(),
+            Some(_) => return None,
+            // Any compressed key has the right size
+            None if self.signers.is_none() => bitcoin::PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .expect("valid key"),
+            None => return None,
+        };
+        Some((pk, Self::dummy_sig()))
+    }
+
+    fn lookup_sha256(&self, hash: sha256::Hash) -> Option<Preimage32> {
+        Some(dummy_preimage(&hash[..]))
+    }
+
+    fn lookup_hash256(&self, hash: sha256d::Hash) -> Option<Preimage32> {
+        Some(dummy_preimage(&hash[..]))
+    }
+
+    fn lookup_ripemd160(&self, hash: ripemd160::Hash) -> Option<Preimage32> {
+        Some(dummy_preimage(&hash[..]))
+    }
+
+    fn lookup_hash160(&self, hash: hash160::Hash) -> Option<Preimage32> {
+        Some(dummy_preimage(&hash[..]))
+    }
+
+    fn check_older(&self, n: u32) -> bool {
+        match self.sequence {
+            Some(sequence) => <Older as Satisfier<Pk>>::check_older(&Older(sequence), n),
+            None => true,
+        }
+    }
+
+    fn check_after(&self, n: u32) -> bool {
+        match self.lock_time {
+            Some(lock_time) => <After as Satisfier<Pk>>::check_after(&After(lock_time), n),
+            None => true,
+        }
+    }
+}
+
+impl<P: MiniscriptKey, Q: MiniscriptKey> TranslatePk<P, Q> for Descriptor<P> {
+    type Output = Descriptor<Q>;
+    /// Convert a descriptor using abstract keys to one using specific keys
+    /// This will panic if translatefpk returns an uncompressed key when
+    /// converting to a Segwit descriptor. To prevent this panic, ensure
+    /// translatefpk returns an error in this case instead.
+    fn translate_pk<Fpk, Fpkh, E>(
+        &self,
+        mut translatefpk: Fpk,
+        mut translatefpkh: Fpkh,
+    ) -> Result<Descriptor<Q>, E>
+    where
+        Fpk: FnMut(&P) -> Result<Q, E>,
+        Fpkh: FnMut(&P::Hash) -> Result<Q::Hash, E>,
+        Q: MiniscriptKey,
+    {
+        let desc = match *self {
+            Descriptor::Bare(ref bare) => {
+                Descriptor::Bare(bare.translate_pk(&mut translatefpk, &mut translatefpkh)?)
+            }
+            Descriptor::Pkh(ref pk) => {
+                Descriptor::Pkh(pk.translate_pk(&mut translatefpk, &mut translatefpkh)?)
+            }
+            Descriptor::Wpkh(ref pk) => {
+                Descriptor::Wpkh(pk.translate_pk(&mut translatefpk, &mut translatefpkh)?)
+            }
+            Descriptor::Sh(ref sh) => {
+                Descriptor::Sh(sh.translate_pk(&mut translatefpk, &mut translatefpkh)?)
+            }
+            Descriptor::Wsh(ref wsh) => {
+                Descriptor::Wsh(wsh.translate_pk(&mut translatefpk, &mut translatefpkh)?)
+            }
+            Descriptor::Cov(ref cov) => {
+                Descriptor::Cov(cov.translate_pk(&mut translatefpk, &mut translatefpkh)?)
+            }
+        };
+        Ok(desc)
+    }
+}
+
+impl<Pk: MiniscriptKey> ElementsTrait<Pk> for Descriptor<Pk>
+where
+    Pk: FromStr,
+    Pk::Hash: FromStr,
+    <Pk as FromStr>::Err: ToString,
+    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+{
+    fn blind_addr(
+        &self,
+        blinder: Option<secp256k1_zkp::PublicKey>,
+        params: &'static elements::AddressParams,
+    ) -> Result<elements::Address, Error>
+    where
+        Pk: ToPublicKey,
+    {
         match *self {
-            Descriptor::Bare(ref sub) => write!(f, "{:?}", sub),
-            Descriptor::Pkh(ref pkh) => write!(f, "{:?}", pkh),
-            Descriptor::Wpkh(ref wpkh) => write!(f, "{:?}", wpkh),
-            Descriptor::Sh(ref sub) => write!(f, "{:?}", sub),
-            Descriptor::Wsh(ref sub) => write!(f, "{:?}", sub),
-            Descriptor::Cov(ref cov) => write!(f, "{:?}", cov),
+            Descriptor::Bare(ref bare) => bare.blind_addr(blinder, params),
+            Descriptor::Pkh(ref pkh) => pkh.blind_addr(blinder, params),
+            Descriptor::Wpkh(ref wpkh) => wpkh.blind_addr(blinder, params),
+            Descriptor::Wsh(ref wsh) => wsh.blind_addr(blinder, params),
+            Descriptor::Sh(ref sh) => sh.blind_addr(blinder, params),
+            Descriptor::Cov(ref cov) => cov.blind_addr(blinder, params),
+        }
+    }
+}
+
+impl<Pk: MiniscriptKey> DescriptorTrait<Pk> for Descriptor<Pk>
+where
+    Pk: FromStr,
+    Pk::Hash: FromStr,
+    <Pk as FromStr>::Err: ToString,
+    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+{
+    /// Whether the descriptor is safe
+    /// Checks whether all the spend paths in the descriptor are possible
+    /// on the bitcoin network under the current standardness and consensus rules
+    /// Also checks whether the descriptor requires signauture on all spend paths
+    /// And whether the script is malleable.
+    /// In general, all the guarantees of miniscript hold only for safe scripts.
+    /// All the analysis guarantees of miniscript only hold safe scripts.
+    /// The signer may not be able to find satisfactions even if one exists
+    fn sanity_check(&self) -> Result<(), Error> {
+        match *self {
+            Descriptor::Bare(ref bare) => bare.sanity_check(),
+            Descriptor::Pkh(ref pkh) => pkh.sanity_check(),
+            Descriptor::Wpkh(ref wpkh) => wpkh.sanity_check(),
+            Descriptor::Wsh(ref wsh) => wsh.sanity_check(),
+            Descriptor::Sh(ref sh) => sh.sanity_check(),
+            Descriptor::Cov(ref cov) => cov.sanity_check(),
+        }
+    }
+    /// Computes the Bitcoin address of the descriptor, if one exists
+    fn address(&self, params: &'static elements::AddressParams) -> Result<elements::Address, Error>
+    where
+        Pk: ToPublicKey,
+    {
+        match *self {
+            Descriptor::Bare(ref bare) => bare.address(params),
+            Descriptor::Pkh(ref pkh) => pkh.address(params),
+            Descriptor::Wpkh(ref wpkh) => wpkh.address(params),
+            Descriptor::Wsh(ref wsh) => wsh.address(params),
+            Descriptor::Sh(ref sh) => sh.address(params),
+            Descriptor::Cov(ref cov) => cov.address(params),
+        }
+    }
+
+    /// Computes the scriptpubkey of the descriptor
+    fn script_pubkey(&self) -> Script
+    where
+        Pk: ToPublicKey,
+    {
+        match *self {
+            Descriptor::Bare(ref bare) => bare.script_pubkey(),
+            Descriptor::Pkh(ref pkh) => pkh.script_pubkey(),
+            Descriptor::Wpkh(ref wpkh) => wpkh.script_pubkey(),
+            Descriptor::Wsh(ref wsh) => wsh.script_pubkey(),
+            Descriptor::Sh(ref sh) => sh.script_pubkey(),
+            Descriptor::Cov(ref cov) => cov.script_pubkey(),
+        }
+    }
+
+    /// Computes the scriptSig that will be in place for an unsigned
+    /// input spending an output with this descriptor. For pre-segwit
+    /// descriptors, which use the scriptSig for signatures, this
+    /// returns the empty script.
+    ///
+    /// This is used in Segwit transactions to produce an unsigned
+    /// transaction whose txid will not change during signing (since
+    /// only the witness data will change).
+    fn unsigned_script_sig(&self) -> Script
+    where
+        Pk: ToPublicKey,
+    {
+        match *self {
+            Descriptor::Bare(ref bare) => bare.unsigned_script_sig(),
+            Descriptor::Pkh(ref pkh) => pkh.unsigned_script_sig(),
+            Descriptor::Wpkh(ref wpkh) => wpkh.unsigned_script_sig(),
+            Descriptor::Wsh(ref wsh) => wsh.unsigned_script_sig(),
+            Descriptor::Sh(ref sh) => sh.unsigned_script_sig(),
+            Descriptor::Cov(ref cov) => cov.unsigned_script_sig(),
+        }
+    }
+
+    /// Computes the "witness script" of the descriptor, i.e. the underlying
+    /// script before any hashing is done. For `Bare`, `Pkh` and `Wpkh` this
+    /// is the scriptPubkey; for `ShWpkh` and `Sh` this is the redeemScript;
+    /// for the others it is the witness script.
+    fn explicit_script(&self) -> Script
+    where
+        Pk: ToPublicKey,
+    {
+        match *self {
+            Descriptor::Bare(ref bare) => bare.explicit_script(),
+            Descriptor::Pkh(ref pkh) => pkh.explicit_script(),
+            Descriptor::Wpkh(ref wpkh) => wpkh.explicit_script(),
+            Descriptor::Wsh(ref wsh) => wsh.explicit_script(),
+            Descriptor::Sh(ref sh) => sh.explicit_script(),
+            Descriptor::Cov(ref cov) => cov.explicit_script(),
+        }
+    }
+
+    /// Returns satisfying witness and scriptSig to spend an
+    /// output controlled by the given descriptor if it possible to
+    /// construct one using the satisfier S.
+    fn get_satisfaction<S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), Error>
+    where
+        Pk: ToPublicKey,
+        S: Satisfier<Pk>,
+    {
+        match *self {
+            Descriptor::Bare(ref bare) => bare.get_satisfaction(satisfier),
+            Descriptor::Pkh(ref pkh) => pkh.get_satisfaction(satisfier),
+            Descriptor::Wpkh(ref wpkh) => wpkh.get_satisfaction(satisfier),
+            Descriptor::Wsh(ref wsh) => wsh.get_satisfaction(satisfier),
+            Descriptor::Sh(ref sh) => sh.get_satisfaction(satisfier),
+            Descriptor::Cov(ref cov) => cov.get_satisfaction(satisfier),
+        }
+    }
+
+    /// Computes an upper bound on the weight of a satisfying witness to the
+    /// transaction. Assumes all signatures are 73 bytes, including push opcode
+    /// and sighash suffix. Includes the weight of the VarInts encoding the
+    /// scriptSig and witness stack length.
+    fn max_satisfaction_weight(&self) -> Result<usize, Error> {
+        match *self {
+            Descriptor::Bare(ref bare) => bare.max_satisfaction_weight(),
+            Descriptor::Pkh(ref pkh) => pkh.max_satisfaction_weight(),
+            Descriptor::Wpkh(ref wpkh) => wpkh.max_satisfaction_weight(),
+            Descriptor::Wsh(ref wsh) => wsh.max_satisfaction_weight(),
+            Descriptor::Sh(ref sh) => sh.max_satisfaction_weight(),
+            Descriptor::Cov(ref cov) => cov.max_satisfaction_weight(),
         }
     }
-}
 
-impl<Pk: MiniscriptKey> fmt::Display for Descriptor<Pk> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            Descriptor::Bare(ref sub) => write!(f, "{}", sub),
-            Descriptor::Pkh(ref pkh) => write!(f, "{}", pkh),
-            Descriptor::Wpkh(ref wpkh) => write!(f, "{}", wpkh),
-            Descriptor::Sh(ref sub) => write!(f, "{}", sub),
-            Descriptor::Wsh(ref sub) => write!(f, "{}", sub),
-            Descriptor::Cov(ref cov) => write!(f, "{}", cov),
+    /// Get the `scriptCode` of a transaction output.
+    ///
+    /// The `scriptCode` is the Script of the previous transaction output being serialized in the
+    /// sighash when evaluating a `CHECKSIG` & co. OP code.
+    fn script_code(&self) -> Script
+    where
+        Pk: ToPublicKey,
+    {
+        match *self {
+            Descriptor::Bare(ref bare) => bare.script_code(),
+            Descriptor::Pkh(ref pkh) => pkh.script_code(),
+            Descriptor::Wpkh(ref wpkh) => wpkh.script_code(),
+            Descriptor::Wsh(ref wsh) => wsh.script_code(),
+            Descriptor::Sh(ref sh) => sh.script_code(),
+            Descriptor::Cov(ref cov) => cov.script_code(),
+        }
+    }
+}
+
+impl<Pk: MiniscriptKey> ForEachKey<Pk> for Descriptor<Pk> {
+    fn for_each_key<'a, F: FnMut(ForEach<'a, Pk>) -> bool>(&'a self, pred: F) -> bool
+    where
+        Pk: 'a,
+        Pk::Hash: 'a,
+    {
+        match *self {
+            Descriptor::Bare(ref bare) => bare.for_each_key(pred),
+            Descriptor::Pkh(ref pkh) => pkh.for_each_key(pred),
+            Descriptor::Wpkh(ref wpkh) => wpkh.for_each_key(pred),
+            Descriptor::Wsh(ref wsh) => wsh.for_each_key(pred),
+            Descriptor::Sh(ref sh) => sh.for_each_key(pred),
+            Descriptor::Cov(ref cov) => cov.for_any_key(pred),
+        }
+    }
+}
+
+impl Descriptor<DescriptorPublicKey> {
+    /// Whether or not the descriptor has any wildcards
+    pub fn is_deriveable(&self) -> bool {
+        self.for_any_key(|key| key.as_key().is_deriveable())
+    }
+
+    /// Derives all wildcard keys in the descriptor using the supplied index
+    ///
+    /// Keys keep their extended form, the index being appended to their
+    /// derivation path, so the result prints as a stable descriptor without
+    /// wildcard. Use [Descriptor::to_definite_string] to resolve the keys to
+    /// public keys instead.
+    ///
+    /// Panics if given an index ≥ 2^31
+    pub fn derive(&self, index: u32) -> Descriptor<DescriptorPublicKey> {
+        self.translate_pk2_infallible(|pk| pk.clone().derive(index))
+    }
+
+    /// Splits a descriptor with multipath keys into one descriptor per
+    /// derivation path, the i-th descriptor taking the i-th path of every
+    /// multipath key. Descriptors without multipath keys are returned as is.
+    ///
+    /// Errors if the multipath keys have different numbers of paths.
+    pub fn into_single_descriptors(&self) -> Result<Vec<Descriptor<DescriptorPublicKey>>, Error> {
+        let mut n_paths = None;
+        let mut mismatch = false;
+        self.for_each_key(|key| {
+            if let DescriptorPublicKey::MultiXPub(ref xpub) = *key.as_key() {
+                match n_paths {
+                    None => n_paths = Some(xpub.derivation_paths.len()),
+                    Some(n) => mismatch |= n != xpub.derivation_paths.len(),
+                }
+            }
+            true
+        });
+        if mismatch {
+            return Err(Error::BadDescriptor(
+                "multipath keys with different numbers of derivation paths".to_owned(),
+            ));
+        }
+        Ok(match n_paths {
+            None => vec![self.clone()],
+            Some(n) => (0..n)
+                .map(|i| {
+                    self.translate_pk2_infallible(|pk| {
+                        if pk.is_multipath() {
+                            pk.into_single_keys().swap_remove(i)
+                        } else {
+                            pk.clone()
+                        }
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    /// Derives every key of the descriptor at `index` and prints the
+    /// result with concrete public keys, dropping the key origins. This is
+    /// a fully resolved descriptor for the single address at `index`,
+    /// which can be shared with software unaware of extended keys.
+    ///
+    /// Errors if a key cannot be derived because of hardened derivation
+    /// steps. Panics if given an index ≥ 2^31
+    pub fn to_definite_string<C: secp256k1_zkp::Verification>(
+        &self,
+        index: u32,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<String, ConversionError> {
+        Ok(self.derived_public_descriptor(index, secp)?.to_string())
+    }
+
+    /// Computes the Electrum style hash of the scriptPubKey derived at
+    /// `index`, used to subscribe to the script with indexers. This is the
+    /// SHA256 of the scriptPubKey with its bytes reversed, so that it
+    /// displays as the hex string Electrum servers expect.
+    ///
+    /// Errors if a key cannot be derived because of hardened derivation
+    /// steps. Panics if given an index ≥ 2^31
+    pub fn script_hash<C: secp256k1_zkp::Verification>(
+        &self,
+        index: u32,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<sha256::Hash, ConversionError> {
+        let spk = self.derived_public_descriptor(index, secp)?.script_pubkey();
+        let mut hash = sha256::Hash::hash(spk.as_bytes()).into_inner();
+        hash.reverse();
+        Ok(sha256::Hash::from_inner(hash))
+    }
+
+    /// Computes the SHA256 of the witness script derived at `index`, which
+    /// is the witness program of P2WSH outputs. Returns `None` for
+    /// descriptors without a witness script.
+    ///
+    /// Errors if a key cannot be derived because of hardened derivation
+    /// steps. Panics if given an index ≥ 2^31
+    pub fn witness_script_hash<C: secp256k1_zkp::Verification>(
+        &self,
+        index: u32,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<Option<sha256::Hash>, ConversionError> {
+        let desc = self.derived_public_descriptor(index, secp)?;
+        match desc.desc_type() {
+            DescriptorType::Wsh
+            | DescriptorType::ShWsh
+            | DescriptorType::WshSortedMulti
+            | DescriptorType::ShWshSortedMulti
+            | DescriptorType::Cov => {
+                Ok(Some(sha256::Hash::hash(desc.explicit_script().as_bytes())))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The descriptor with every key derived at `index`
+    fn derived_public_descriptor<C: secp256k1_zkp::Verification>(
+        &self,
+        index: u32,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<Descriptor<bitcoin::PublicKey>, ConversionError> {
+        self.derive(index)
+            .translate_pk2(|xpk| xpk.derive_public_key(secp))
+    }
+
+    /// Whether the descriptor derived at `index` has the scriptPubKey `spk`.
+    /// This is the usual check that a descriptor produces a known address,
+    /// without deriving a whole range.
+    ///
+    /// Returns false when the keys cannot be derived, for example because
+    /// of hardened derivation steps. Panics if given an index ≥ 2^31
+    pub fn matches_spk<C: secp256k1_zkp::Verification>(
+        &self,
+        spk: &Script,
+        index: u32,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> bool {
+        self.derived_public_descriptor(index, secp)
+            .map(|desc| desc.script_pubkey() == *spk)
+            .unwrap_or(false)
+    }
+
+    /// Like [Descriptor::matches_spk], but tries the first two indices,
+    /// returning the one which produces `spk` if any
+    pub fn matches_spk_first_two<C: secp256k1_zkp::Verification>(
+        &self,
+        spk: &Script,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Option<u32> {
+        (0..2).find(|&index| self.matches_spk(spk, index, secp))
+    }
+
+    /// Lazily computes the addresses of the descriptor at consecutive
+    /// indices, starting at `start`. Descriptors without wildcard yield a
+    /// single address.
+    ///
+    /// The parent of every wildcard key is derived once, each address then
+    /// costing a single derivation step per key, like
+    /// [DescriptorPublicKey::derive_range].
+    ///
+    /// Errors if a key cannot be derived because of hardened derivation
+    /// steps, and for descriptors without address. Panics if `start` is
+    /// ≥ 2^31
+    pub fn addresses<'a, C: secp256k1_zkp::Verification>(
+        &self,
+        params: &'static elements::AddressParams,
+        start: u32,
+        secp: &'a secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<Addresses<'a, C>, Error> {
+        let descriptor = self
+            .translate_pk2(|pk| match *pk {
+                DescriptorPublicKey::XPub(ref xpk) if xpk.wildcard == Wildcard::Unhardened => {
+                    let parent = xpk
+                        .xkey
+                        .derive_pub(secp, &xpk.derivation_path)
+                        .map_err(|_| ConversionError::HardenedChild)?;
+                    Ok(DescriptorPublicKey::XPub(DescriptorXKey {
+                        origin: None,
+                        xkey: parent,
+                        derivation_path: bip32::DerivationPath::from(vec![]),
+                        wildcard: Wildcard::Unhardened,
+                    }))
+                }
+                _ => Ok(pk.clone()),
+            })
+            .map_err(|e: ConversionError| Error::BadDescriptor(e.to_string()))?;
+        let first = descriptor
+            .derived_public_descriptor(start, secp)
+            .map_err(|e| Error::BadDescriptor(e.to_string()))?
+            .address(params)?;
+        Ok(Addresses {
+            deriveable: descriptor.is_deriveable(),
+            descriptor,
+            params,
+            secp,
+            first: Some(first),
+            index: start,
+        })
+    }
+
+    /// Derives the keys of the descriptor at every index of `range` and
+    /// reports the indices at which two distinct keys derive to the same
+    /// public key, for example `xpub/1/*` and `xpub/1/5` at index 5. Such
+    /// a collision silently turns a multisig into one with fewer keys.
+    ///
+    /// Keys which cannot be derived because of hardened derivation steps
+    /// are skipped, and so are indices ≥ 2^31. Multipath descriptors are
+    /// checked for each of their derivation paths, see
+    /// [Descriptor::into_single_descriptors], collisions being reported
+    /// between the single path keys. Multipath keys with different numbers
+    /// of paths never derive together, and have no collision.
+    pub fn check_key_collisions<C: secp256k1_zkp::Verification>(
+        &self,
+        range: Range<u32>,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Vec<KeyCollision> {
+        let mut collisions = vec![];
+        for desc in self.into_single_descriptors().unwrap_or_default() {
+            let mut keys: Vec<DescriptorPublicKey> = vec![];
+            desc.for_each_key(|key| {
+                if !keys.contains(key.as_key()) {
+                    keys.push(key.as_key().clone());
+                }
+                true
+            });
+
+            let end = cmp::min(range.end, 1 << 31);
+            for index in range.start..end {
+                let derived: Vec<_> = keys
+                    .iter()
+                    .map(|key| key.clone().derive(index).derive_public_key(secp).ok())
+                    .collect();
+                for (i, pk) in derived.iter().enumerate() {
+                    let pk = match *pk {
+                        Some(pk) => pk,
+                        None => continue,
+                    };
+                    for (j, other) in derived.iter().enumerate().skip(i + 1) {
+                        let collision = KeyCollision {
+                            index,
+                            keys: (keys[i].clone(), keys[j].clone()),
+                            public_key: pk,
+                        };
+                        if *other == Some(pk) && !collisions.contains(&collision) {
+                            collisions.push(collision);
+                        }
+                    }
+                }
+            }
+        }
+        collisions
+    }
+
+    /// Signs the inputs of `pset` locked by this descriptor with the keys
+    /// derived from the master key `root_xprv`, adding the signatures to
+    /// the partial signatures of the inputs. Returns the number of
+    /// signatures added.
+    ///
+    /// Watch-only descriptors usually have xpubs below a hardened origin,
+    /// from which the signing keys cannot be derived. Instead, the keys
+    /// whose origin fingerprint is the one of `root_xprv` are derived from
+    /// it along their full derivation path. For descriptors with wildcards,
+    /// the derivation index of each input is taken from its BIP32
+    /// derivations, and each derivation path of multipath descriptors is
+    /// tried. Inputs which are not locked by this descriptor, or without
+    /// utxo, are left untouched.
+    pub fn sign_with_root_xprv<C: secp256k1_zkp::Signing + secp256k1_zkp::Verification>(
+        &self,
+        pset: &mut Pset,
+        root_xprv: &bip32::ExtendedPrivKey,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<usize, pset::Error> {
+        let singles = self.into_single_descriptors().unwrap_or_default();
+        let mut n_sigs = 0;
+        for index in 0..pset.inputs.len() {
+            let spk = match pset::get_scriptpubkey(pset, index) {
+                Ok(spk) => spk.clone(),
+                Err(..) => continue,
+            };
+            let indices = if self.is_deriveable() {
+                pset.inputs[index]
+                    .bip32_derivation
+                    .values()
+                    .filter_map(|(_, path)| match path.as_ref().last() {
+                        Some(&bip32::ChildNumber::Normal { index })
+                        | Some(&bip32::ChildNumber::Hardened { index }) => Some(index),
+                        None => None,
+                    })
+                    .collect()
+            } else {
+                vec![0]
+            };
+
+            let candidates: Vec<_> = indices
+                .into_iter()
+                .flat_map(|i| singles.iter().map(move |desc| desc.derive(i)))
+                .collect();
+            for derived in candidates {
+                let desc =
+                    derived.translate_pk2(|pk| match derive_from_root(pk, root_xprv, secp) {
+                        Some(sk) => Ok(sk.public_key(secp)),
+                        None => pk.derive_public_key(secp),
+                    });
+                let desc = match desc {
+                    Ok(desc) if desc.script_pubkey() == spk => desc,
+                    _ => continue,
+                };
+
+                let mut sks = vec![];
+                derived.for_each_key(|pk| {
+                    if let Some(sk) = derive_from_root(pk.as_key(), root_xprv, secp) {
+                        sks.push(sk);
+                    }
+                    true
+                });
+                let (msg, sighash_type) = desc.sighash_message(pset, index)?;
+                for sk in sks {
+                    let mut sig = secp.sign(&msg, &sk.key).serialize_der().to_vec();
+                    sig.push(sighash_type.as_u32() as u8);
+                    pset.inputs[index]
+                        .partial_sigs
+                        .insert(sk.public_key(secp), sig);
+                    n_sigs += 1;
+                }
+                break;
+            }
+        }
+        Ok(n_sigs)
+    }
+
+    /// Number of distinct scriptPubKeys the descriptor produces when derived
+    /// at every index of `range`. Indices ≥ 2^31 cannot be derived and are
+    /// not counted.
+    ///
+    /// Multipath descriptors produce one scriptPubKey per derivation path at
+    /// every index. A descriptor without wildcards produces the same
+    /// scriptPubKeys whatever the index, so the range is then ignored.
+    pub fn scriptpubkey_count(&self, range: Range<u32>) -> usize {
+        let mut branches = 1;
+        self.for_each_key(|key| {
+            if let DescriptorPublicKey::MultiXPub(ref xpub) = *key.as_key() {
+                branches = cmp::max(branches, xpub.derivation_paths.len());
+            }
+            true
+        });
+        if self.is_deriveable() {
+            let end = cmp::min(range.end, 1 << 31);
+            end.saturating_sub(range.start) as usize * branches
+        } else {
+            branches
+        }
+    }
+
+    /// The network shared by all the extended keys of the descriptor, or
+    /// `None` if it has no extended key. Single keys carry no network and
+    /// are compatible with any.
+    ///
+    /// Errors if extended keys from different networks are mixed, listing
+    /// every extended key with its network.
+    pub fn network_consistency(&self) -> Result<Option<bitcoin::Network>, Error> {
+        let mut networks = vec![];
+        self.for_each_key(|key| {
+            let network = match *key.as_key() {
+                DescriptorPublicKey::SinglePub(..) => return true,
+                DescriptorPublicKey::XPub(ref xpub) => xpub.xkey.network,
+                DescriptorPublicKey::MultiXPub(ref xpub) => xpub.xkey.network,
+            };
+            networks.push((key.as_key().clone(), network));
+            true
+        });
+        match networks.first() {
+            None => Ok(None),
+            Some(&(_, network)) if networks.iter().all(|&(_, n)| n == network) => Ok(Some(network)),
+            Some(..) => {
+                let keys: Vec<String> = networks
+                    .iter()
+                    .map(|&(ref key, network)| format!("{} ({})", key, network))
+                    .collect();
+                Err(Error::BadDescriptor(format!(
+                    "keys from different networks: {}",
+                    keys.join(", ")
+                )))
+            }
+        }
+    }
+
+    /// The string representation of the descriptor, with the hardened
+    /// steps of its keys written with `marker` and the checksum updated
+    /// accordingly
+    pub fn to_string_with_hardened_marker(&self, marker: HardenedMarker) -> String {
+        self.translate_pk2_infallible(|pk| pk.to_string_with_hardened_marker(marker))
+            .to_string()
+    }
+
+    /// Computes the change descriptor for a receive descriptor following
+    /// the usual `/0/*` (receive) and `/1/*` (change) convention
+    ///
+    /// Errors if any key is not an extended key with a wildcard directly
+    /// following an unhardened `0` step.
+    pub fn to_change_descriptor(&self) -> Result<Descriptor<DescriptorPublicKey>, Error> {
+        self.translate_pk2(|pk| {
+            if let DescriptorPublicKey::XPub(ref xpub) = *pk {
+                let path = xpub.derivation_path.as_ref();
+                if xpub.wildcard != Wildcard::None
+                    && path.last() == Some(&bip32::ChildNumber::Normal { index: 0 })
+                {
+                    let mut path = path.to_vec();
+                    path.pop();
+                    path.push(bip32::ChildNumber::Normal { index: 1 });
+                    let mut xpub = xpub.clone();
+                    xpub.derivation_path = bip32::DerivationPath::from(path);
+                    return Ok(DescriptorPublicKey::XPub(xpub));
+                }
+            }
+            Err(Error::BadDescriptor(format!(
+                "key {} is not a receive key ending in /0/*",
+                pk
+            )))
+        })
+    }
+
+    /// Build a single key descriptor from an extended key, inferring the
+    /// descriptor type from its SLIP-132 version bytes: `xpub` keys give a
+    /// `pkh`, `ypub` keys a `sh(wpkh)` and `zpub` keys a `wpkh` descriptor
+    /// (`tpub`, `upub` and `vpub` on testnet). The key may have an origin
+    /// and a derivation path like any descriptor key, and is normalized to
+    /// the standard `xpub` encoding.
+    ///
+    /// This is the only parser accepting SLIP-132 keys: descriptor strings
+    /// must use the standard encoding, since the version bytes would
+    /// otherwise be lost when printing the descriptor.
+    ///
+    /// Errors for multisig keys (`Ypub`, `Zpub`), since a single key is
+    /// not enough to build their descriptor.
+    pub fn from_slip132(s: &str) -> Result<Descriptor<DescriptorPublicKey>, Error> {
+        let (key, desc_type) = key::normalize_slip132_key(s).ok_or(Error::BadDescriptor(
+            format!("{} is not a SLIP-132 extended public key", s),
+        ))?;
+        let pk =
+            DescriptorPublicKey::from_str(&key).map_err(|e| Error::BadDescriptor(e.to_string()))?;
+        match desc_type {
+            DescriptorType::Pkh => Ok(Descriptor::new_pkh(pk)),
+            DescriptorType::Wpkh => Descriptor::new_wpkh(pk),
+            DescriptorType::ShWpkh => Descriptor::new_sh_wpkh(pk),
+            _ => Err(Error::BadDescriptor(format!(
+                "SLIP-132 key {} is for a multisig {} descriptor",
+                s, desc_type
+            ))),
+        }
+    }
+
+    /// Parse a descriptor that may contain secret keys
+    ///
+    /// Internally turns every secret key found into the corresponding public key and then returns a
+    /// a descriptor that only contains public keys and a map to lookup the secret key given a public key.
+    pub fn parse_descriptor<C: secp256k1_zkp::Signing>(
+        secp: &secp256k1_zkp::Secp256k1<C>,
+        s: &str,
+    ) -> Result<(Descriptor<DescriptorPublicKey>, KeyMap), Error> {
+        let parse_key = |s: &String,
+                         key_map: &mut KeyMap|
+         -> Result<DescriptorPublicKey, DescriptorKeyParseError> {
+            let (public_key, secret_key) = match DescriptorSecretKey::from_str(s) {
+                Ok(sk) => (sk.as_public(&secp)?, Some(sk)),
+                Err(_) => (DescriptorPublicKey::from_str(s)?, None),
+            };
+
+            if let Some(secret_key) = secret_key {
+                key_map.insert(public_key.clone(), secret_key);
+            }
+
+            Ok(public_key)
+        };
+
+        let mut keymap_pk = KeyMap::new();
+        let mut keymap_pkh = KeyMap::new();
+
+        let descriptor = Descriptor::<String>::from_str(s)?;
+        let descriptor = descriptor
+            .translate_pk(
+                |pk| parse_key(pk, &mut keymap_pk),
+                |pkh| parse_key(pkh, &mut keymap_pkh),
+            )
+            .map_err(|e| Error::Unexpected(e.to_string()))?;
+
+        keymap_pk.extend(keymap_pkh.into_iter());
+
+        Ok((descriptor, keymap_pk))
+    }
+
+    /// Serialize a descriptor to string with its secret keys
+    pub fn to_string_with_secret(&self, key_map: &KeyMap) -> String {
+        fn key_to_string(pk: &DescriptorPublicKey, key_map: &KeyMap) -> Result<String, ()> {
+            Ok(match key_map.get(pk) {
+                Some(secret) => secret.to_string(),
+                None => pk.to_string(),
+            })
+        }
+
+        let descriptor = self
+            .translate_pk::<_, _, ()>(
+                |pk| key_to_string(pk, key_map),
+                |pkh| key_to_string(pkh, key_map),
+            )
+            .expect("Translation to string cannot fail");
+
+        descriptor.to_string()
+    }
+
+    /// Whether any of the keys of the descriptor has its secret key in
+    /// `key_map`, that is whether a signature can be contributed.
+    /// Descriptors without secret keys are watch-only.
+    pub fn is_signable(&self, key_map: &KeyMap) -> bool {
+        !self.for_each_key(|key| !key_map.contains_key(key.as_key()))
+    }
+
+    /// Master fingerprints of the keys of the descriptor which have their
+    /// secret key in `key_map`, in the order they appear in the descriptor
+    /// and without duplicates
+    pub fn signable_keys(&self, key_map: &KeyMap) -> Vec<bip32::Fingerprint> {
+        let mut fingerprints = vec![];
+        self.for_each_key(|key| {
+            if key_map.contains_key(key.as_key()) {
+                let fingerprint = key.as_key().master_fingerprint();
+                if !fingerprints.contains(&fingerprint) {
+                    fingerprints.push(fingerprint);
+                }
+            }
+            true
+        });
+        fingerprints
+    }
+
+    /// Master fingerprints of all the keys of the descriptor, as returned by
+    /// [DescriptorPublicKey::master_fingerprint]. These identify the signers
+    /// involved in the descriptor, for instance to route a PSET to them.
+    pub fn signer_fingerprints(&self) -> BTreeSet<bip32::Fingerprint> {
+        let mut fingerprints = BTreeSet::new();
+        self.for_each_key(|key| {
+            fingerprints.insert(key.as_key().master_fingerprint());
+            true
+        });
+        fingerprints
+    }
+
+    /// The BIP standard derivation scheme the descriptor follows, if its
+    /// script type is the one of a standard and the full derivation path of
+    /// every key starts with the purpose of that standard followed by a
+    /// hardened coin type and account. Keys without origin are only
+    /// standard if their derivation path is.
+    ///
+    /// Returns `None` for custom derivations, which includes keys whose
+    /// path does not match the script type.
+    pub fn derivation_standard(&self) -> Option<DerivationStandard> {
+        let standard = match self.desc_type() {
+            DescriptorType::Pkh => DerivationStandard::Bip44,
+            DescriptorType::ShWpkh => DerivationStandard::Bip49,
+            DescriptorType::Wpkh => DerivationStandard::Bip84,
+            _ => return None,
+        };
+        let purpose = bip32::ChildNumber::from_hardened_idx(standard.purpose())
+            .expect("purposes are valid indices");
+        let is_standard = self.for_each_key(|key| {
+            let path = key.as_key().full_derivation_path();
+            let path = path.as_ref();
+            path.len() >= 3 && path[0] == purpose && path[1].is_hardened() && path[2].is_hardened()
+        });
+        if is_standard {
+            Some(standard)
+        } else {
+            None
+        }
+    }
+}
+
+impl Descriptor<DescriptorSecretKey> {
+    /// Converts every secret key of the descriptor to its public key with
+    /// [DescriptorSecretKey::as_public], giving the watch-only descriptor.
+    /// Use [Descriptor::parse_descriptor] to also keep track of the secret
+    /// key of each public key.
+    pub fn to_public<C: secp256k1_zkp::Signing>(
+        &self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<Descriptor<DescriptorPublicKey>, DescriptorKeyParseError> {
+        self.translate_pk2(|sk| sk.as_public(secp))
+    }
+}
+
+impl<Pk> expression::FromTree for Descriptor<Pk>
+where
+    Pk: MiniscriptKey + str::FromStr,
+    Pk::Hash: str::FromStr,
+    <Pk as FromStr>::Err: ToString,
+    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+{
+    /// Parse an expression tree into a descriptor
+    fn from_tree(top: &expression::Tree) -> Result<Descriptor<Pk>, Error> {
+        Ok(match (top.name, top.args.len() as u32) {
+            ("elpkh", 1) => Descriptor::Pkh(Pkh::from_tree(top)?),
+            ("elwpkh", 1) => Descriptor::Wpkh(Wpkh::from_tree(top)?),
+            ("elsh", 1) => Descriptor::Sh(Sh::from_tree(top)?),
+            ("elcovwsh", 2) => Descriptor::Cov(CovenantDescriptor::from_tree(top)?),
+            ("elwsh", 1) => Descriptor::Wsh(Wsh::from_tree(top)?),
+            _ => Descriptor::Bare(Bare::from_tree(top)?),
+        })
+    }
+}
+
+impl<Pk> Descriptor<Pk>
+where
+    Pk: MiniscriptKey + str::FromStr,
+    Pk::Hash: str::FromStr,
+    <Pk as FromStr>::Err: ToString,
+    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+{
+    /// Parse a descriptor in lenient mode
+    ///
+    /// In addition to everything accepted by `from_str`, a bare key with no
+    /// wrapping function (as emitted by some tools) is parsed as `elpk(key)`.
+    /// Strict callers should use `from_str`, which requires explicit functions.
+    pub fn from_str_lenient(s: &str) -> Result<Descriptor<Pk>, Error> {
+        Descriptor::from_str(s).or_else(|e| Pk::from_str(s).map(Descriptor::new_pk).map_err(|_| e))
+    }
+}
+
+impl<Pk> FromStr for Descriptor<Pk>
+where
+    Pk: MiniscriptKey + str::FromStr,
+    Pk::Hash: str::FromStr,
+    <Pk as FromStr>::Err: ToString,
+    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+{
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Descriptor<Pk>, Error> {
+        if !s.starts_with(ELMTS_STR) {
+            return Err(Error::BadDescriptor(String::from(
+                "Not an Elements Descriptor",
+            )));
+        }
+        let desc_str = verify_checksum(&s)?;
+        let top = expression::Tree::from_str(desc_str)?;
+        expression::FromTree::from_tree(&top)
+    }
+}
+
+impl<Pk: MiniscriptKey> fmt::Debug for Descriptor<Pk> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Descriptor::Bare(ref sub) => write!(f, "{:?}", sub),
+            Descriptor::Pkh(ref pkh) => write!(f, "{:?}", pkh),
+            Descriptor::Wpkh(ref wpkh) => write!(f, "{:?}", wpkh),
+            Descriptor::Sh(ref sub) => write!(f, "{:?}", sub),
+            Descriptor::Wsh(ref sub) => write!(f, "{:?}", sub),
+            Descriptor::Cov(ref cov) => write!(f, "{:?}", cov),
+        }
+    }
+}
+
+impl<Pk: MiniscriptKey> fmt::Display for Descriptor<Pk> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Descriptor::Bare(ref sub) => write!(f, "{}", sub),
+            Descriptor::Pkh(ref pkh) => write!(f, "{}", pkh),
+            Descriptor::Wpkh(ref wpkh) => write!(f, "{}", wpkh),
+            Descriptor::Sh(ref sub) => write!(f, "{}", sub),
+            Descriptor::Wsh(ref sub) => write!(f, "{}", sub),
+            Descriptor::Cov(ref cov) => write!(f, "{}", cov),
+        }
+    }
+}
+
+serde_string_impl_pk!(Descriptor, "a script descriptor");
+
+#[cfg(test)]
+mod tests {
+    use super::checksum::desc_checksum;
+    use super::DescriptorTrait;
+    use bitcoin;
+    use bitcoin::hashes::hex::{FromHex, ToHex};
+    use bitcoin::hashes::{hash160, ripemd160, sha256, sha256d, Hash};
+    use bitcoin::util::bip32;
+    use bitcoin::PublicKey;
+    use descriptor::key::Wildcard;
+    use descriptor::{
+        Blinded, DescriptorPublicKey, DescriptorSecretKey, DescriptorSinglePub, DescriptorXKey,
+        HardenedMarker,
+    };
+
+    use super::{
+        satisfaction_weight, ConversionError, DerivationStandard, DescriptorMiniscript,
+        DescriptorType, KeyCollision, KeyMap, MissingItems, NonStandardReason, Pset,
+        ScriptPubKeyType, SpendEstimate, WshInner,
+    };
+    use elements::opcodes::{
+        self,
+        all::{OP_CLTV, OP_CSV},
+    };
+    use elements::script::Instruction;
+    use elements::{self, secp256k1_zkp};
+    use elements::{script, Script};
+    use hex_script;
+    use interpreter::SatisfiedConstraint;
+    use miniscript::limits::SEQUENCE_LOCKTIME_TYPE_FLAG;
+    use miniscript::satisfy::ElementsSig;
+    use miniscript::satisfy::{elementssig_to_rawsig, Lookup, Older, Preimage32};
+    use policy::Liftable;
+    use pset;
+    use std::collections::{BTreeSet, HashMap, HashSet};
+    use std::ops::Range;
+    use std::str::FromStr;
+    use {
+        Descriptor, DummyKey, Error, ForEach, ForEachKey, Interpreter, LoggingSatisfier,
+        Miniscript, Satisfier, Segwitv0, TranslatePk2,
+    };
+
+    #[cfg(feature = "compiler")]
+    use policy;
+
+    type StdDescriptor = Descriptor<PublicKey>;
+    const TEST_PK: &'static str =
+        "elpk(020000000000000000000000000000000000000000000000000000000000000002)";
+
+    fn roundtrip_descriptor(s: &str) {
+        let desc = Descriptor::<DummyKey>::from_str(&s).unwrap();
+        let output = desc.to_string();
+        let normalize_aliases = s.replace("c:pk_k(", "pk(").replace("c:pk_h(", "pkh(");
+        assert_eq!(
+            format!(
+                "{}#{}",
+                &normalize_aliases,
+                desc_checksum(&normalize_aliases).unwrap()
+            ),
+            output
+        );
+    }
+
+    // helper function to create elements txin from scriptsig and witness
+    fn elements_txin(script_sig: Script, witness: Vec<Vec<u8>>) -> elements::TxIn {
+        let mut txin_witness = elements::TxInWitness::default();
+        txin_witness.script_witness = witness;
+        elements::TxIn {
+            previous_output: elements::OutPoint::default(),
+            script_sig: script_sig,
+            sequence: 100,
+            is_pegin: false,
+            has_issuance: false,
+            asset_issuance: elements::AssetIssuance::default(),
+            witness: txin_witness,
+        }
+    }
+
+    #[test]
+    fn desc_rtt_tests() {
+        roundtrip_descriptor("elc:pk_k()");
+        roundtrip_descriptor("elwsh(pk())");
+        roundtrip_descriptor("elwsh(c:pk_k())");
+        roundtrip_descriptor("elc:pk_h()");
+    }
+    #[test]
+    fn parse_descriptor() {
+        StdDescriptor::from_str("(").unwrap_err();
+        StdDescriptor::from_str("(x()").unwrap_err();
+        StdDescriptor::from_str("(\u{7f}()3").unwrap_err();
+        StdDescriptor::from_str("pk()").unwrap_err();
+        StdDescriptor::from_str("nl:0").unwrap_err(); //issue 63
+        let compressed_pk = DummyKey.to_string();
+        assert_eq!(
+            StdDescriptor::from_str("elsh(sortedmulti)")
+                .unwrap_err()
+                .to_string(),
+            "unexpected «no arguments given for sortedmulti»"
+        ); //issue 202
+        assert_eq!(
+            StdDescriptor::from_str(&format!("elsh(sortedmulti(2,{}))", compressed_pk))
+                .unwrap_err()
+                .to_string(),
+            "unexpected «higher threshold than there were keys in sortedmulti»"
+        ); //issue 202
+
+        StdDescriptor::from_str(TEST_PK).unwrap();
+        // fuzzer
+        StdDescriptor::from_str("slip77").unwrap_err();
+
+        let uncompressed_pk =
+        "0414fc03b8df87cd7b872996810db8458d61da8448e531569c8517b469a119d267be5645686309c6e6736dbd93940707cc9143d3cf29f1b877ff340e2cb2d259cf";
+
+        // Context tests
+        StdDescriptor::from_str(&format!("elpk({})", uncompressed_pk)).unwrap();
+        StdDescriptor::from_str(&format!("elpkh({})", uncompressed_pk)).unwrap();
+        StdDescriptor::from_str(&format!("elsh(pk({}))", uncompressed_pk)).unwrap();
+        StdDescriptor::from_str(&format!("elwpkh({})", uncompressed_pk)).unwrap_err();
+        StdDescriptor::from_str(&format!("elsh(wpkh({}))", uncompressed_pk)).unwrap_err();
+        StdDescriptor::from_str(&format!("elwsh(pk{})", uncompressed_pk)).unwrap_err();
+        StdDescriptor::from_str(&format!("elsh(wsh(pk{}))", uncompressed_pk)).unwrap_err();
+        StdDescriptor::from_str(&format!(
+            "elor_i(pk({}),pk({}))",
+            uncompressed_pk, uncompressed_pk
+        ))
+        .unwrap_err();
+    }
+
+    #[test]
+    pub fn script_pubkey() {
+        let bare = StdDescriptor::from_str(&format!(
+            "elmulti(1,020000000000000000000000000000000000000000000000000000000000000002)"
+        ))
+        .unwrap();
+        assert_eq!(
+            bare.script_pubkey(),
+            hex_script(
+                "512102000000000000000000000000000000000000000000000000000000000000000251ae"
+            )
+        );
+        assert_eq!(
+            bare.address(&elements::AddressParams::ELEMENTS)
+                .unwrap_err()
+                .to_string(),
+            "Bare descriptors don't have address"
+        );
+
+        let pk = StdDescriptor::from_str(TEST_PK).unwrap();
+        assert_eq!(
+            pk.script_pubkey(),
+            elements::Script::from(vec![
+                0x21, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xac,
+            ])
+        );
+
+        let pkh = StdDescriptor::from_str(
+            "elpkh(\
+             020000000000000000000000000000000000000000000000000000000000000002\
+             )",
+        )
+        .unwrap();
+        assert_eq!(
+            pkh.script_pubkey(),
+            script::Builder::new()
+                .push_opcode(opcodes::all::OP_DUP)
+                .push_opcode(opcodes::all::OP_HASH160)
+                .push_slice(
+                    &hash160::Hash::from_hex("84e9ed95a38613f0527ff685a9928abe2d4754d4",).unwrap()
+                        [..]
+                )
+                .push_opcode(opcodes::all::OP_EQUALVERIFY)
+                .push_opcode(opcodes::all::OP_CHECKSIG)
+                .into_script()
+        );
+        assert_eq!(
+            pkh.address(&elements::AddressParams::ELEMENTS,)
+                .unwrap()
+                .to_string(),
+            "2dmYXpSu8YP6aLcJYhHfB1C19mdzSx2GPB9"
+        );
+
+        let wpkh = StdDescriptor::from_str(
+            "elwpkh(\
+             020000000000000000000000000000000000000000000000000000000000000002\
+             )",
+        )
+        .unwrap();
+        assert_eq!(
+            wpkh.script_pubkey(),
+            script::Builder::new()
+                .push_opcode(opcodes::all::OP_PUSHBYTES_0)
+                .push_slice(
+                    &hash160::Hash::from_hex("84e9ed95a38613f0527ff685a9928abe2d4754d4",).unwrap()
+                        [..]
+                )
+                .into_script()
+        );
+        assert_eq!(
+            wpkh.address(&elements::AddressParams::ELEMENTS,)
+                .unwrap()
+                .to_string(),
+            "ert1qsn57m9drscflq5nl76z6ny52hck5w4x57m69k3"
+        );
+
+        let shwpkh = StdDescriptor::from_str(
+            "elsh(wpkh(\
+             020000000000000000000000000000000000000000000000000000000000000002\
+             ))",
+        )
+        .unwrap();
+        assert_eq!(
+            shwpkh.script_pubkey(),
+            script::Builder::new()
+                .push_opcode(opcodes::all::OP_HASH160)
+                .push_slice(
+                    &hash160::Hash::from_hex("f1c3b9a431134cb90a500ec06e0067cfa9b8bba7",).unwrap()
+                        [..]
+                )
+                .push_opcode(opcodes::all::OP_EQUAL)
+                .into_script()
+        );
+        assert_eq!(
+            shwpkh
+                .address(&elements::AddressParams::ELEMENTS,)
+                .unwrap()
+                .to_string(),
+            "XZPaAbg6M83Fq5NqvbEGZ5kwy9RKSTke2s"
+        );
+
+        let sh = StdDescriptor::from_str(
+            "elsh(c:pk_k(\
+             020000000000000000000000000000000000000000000000000000000000000002\
+             ))",
+        )
+        .unwrap();
+        assert_eq!(
+            sh.script_pubkey(),
+            script::Builder::new()
+                .push_opcode(opcodes::all::OP_HASH160)
+                .push_slice(
+                    &hash160::Hash::from_hex("aa5282151694d3f2f32ace7d00ad38f927a33ac8",).unwrap()
+                        [..]
+                )
+                .push_opcode(opcodes::all::OP_EQUAL)
+                .into_script()
+        );
+        assert_eq!(
+            sh.address(&elements::AddressParams::ELEMENTS,)
+                .unwrap()
+                .to_string(),
+            "XSspZXDJu2XVh8AKC7qF3L7x79Qy67JhQb"
+        );
+
+        let wsh = StdDescriptor::from_str(
+            "elwsh(c:pk_k(\
+             020000000000000000000000000000000000000000000000000000000000000002\
+             ))",
+        )
+        .unwrap();
+        assert_eq!(
+            wsh.script_pubkey(),
+            script::Builder::new()
+                .push_opcode(opcodes::all::OP_PUSHBYTES_0)
+                .push_slice(
+                    &sha256::Hash::from_hex(
+                        "\
+                         f9379edc8983152dc781747830075bd5\
+                         3896e4b0ce5bff73777fd77d124ba085\
+                         "
+                    )
+                    .unwrap()[..]
+                )
+                .into_script()
+        );
+        assert_eq!(
+            wsh.address(&elements::AddressParams::ELEMENTS,)
+                .unwrap()
+                .to_string(),
+            "ert1qlymeahyfsv2jm3upw3urqp6m65ufde9seedl7umh0lth6yjt5zzsan9u2t"
+        );
+
+        let shwsh = StdDescriptor::from_str(
+            "elsh(wsh(c:pk_k(\
+             020000000000000000000000000000000000000000000000000000000000000002\
+             )))",
+        )
+        .unwrap();
+        assert_eq!(
+            shwsh.script_pubkey(),
+            script::Builder::new()
+                .push_opcode(opcodes::all::OP_HASH160)
+                .push_slice(
+                    &hash160::Hash::from_hex("4bec5d7feeed99e1d0a23fe32a4afe126a7ff07e",).unwrap()
+                        [..]
+                )
+                .push_opcode(opcodes::all::OP_EQUAL)
+                .into_script()
+        );
+        assert_eq!(
+            shwsh
+                .address(&elements::AddressParams::ELEMENTS,)
+                .unwrap()
+                .to_string(),
+            "XJGggUb965TvGF2VCxp9EQGmZTxMeDjwQQ"
+        );
+    }
+
+    #[test]
+    fn satisfy() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sk =
+            secp256k1_zkp::SecretKey::from_slice(&b"sally was a secret key, she said"[..]).unwrap();
+        let pk = bitcoin::PublicKey {
+            key: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+            compressed: true,
+        };
+        let msg = secp256k1_zkp::Message::from_slice(&b"michael was a message, amusingly"[..])
+            .expect("32 bytes");
+        let sig = secp.sign(&msg, &sk);
+        let mut sigser = sig.serialize_der().to_vec();
+        sigser.push(0x01); // sighash_all
+
+        struct SimpleSat {
+            sig: secp256k1_zkp::Signature,
+            pk: bitcoin::PublicKey,
+        }
+
+        impl Satisfier<bitcoin::PublicKey> for SimpleSat {
+            fn lookup_sig(&self, pk: &bitcoin::PublicKey) -> Option<ElementsSig> {
+                if *pk == self.pk {
+                    Some((self.sig, elements::SigHashType::All))
+                } else {
+                    None
+                }
+            }
+        }
+
+        let satisfier = SimpleSat { sig, pk };
+        let ms = ms_str!("c:pk_k({})", pk);
+
+        let mut txin = elements::TxIn {
+            previous_output: elements::OutPoint::default(),
+            script_sig: Script::new(),
+            sequence: 100,
+            is_pegin: false,
+            has_issuance: false,
+            asset_issuance: elements::AssetIssuance::default(),
+            witness: elements::TxInWitness::default(),
+        };
+        let bare = Descriptor::new_bare(ms.clone()).unwrap();
+
+        bare.satisfy(&mut txin, &satisfier).expect("satisfaction");
+        assert_eq!(
+            txin,
+            elements_txin(
+                script::Builder::new().push_slice(&sigser[..]).into_script(),
+                vec![]
+            ),
+        );
+        assert_eq!(bare.unsigned_script_sig(), elements::Script::new());
+
+        let pkh = Descriptor::new_pkh(pk);
+        pkh.satisfy(&mut txin, &satisfier).expect("satisfaction");
+        assert_eq!(
+            txin,
+            elements_txin(
+                script::Builder::new()
+                    .push_slice(&sigser[..])
+                    .push_key(&pk)
+                    .into_script(),
+                vec![]
+            )
+        );
+        assert_eq!(pkh.unsigned_script_sig(), elements::Script::new());
+
+        let wpkh = Descriptor::new_wpkh(pk).unwrap();
+        wpkh.satisfy(&mut txin, &satisfier).expect("satisfaction");
+        assert_eq!(
+            txin,
+            elements_txin(Script::new(), vec![sigser.clone(), pk.to_bytes(),])
+        );
+        assert_eq!(wpkh.unsigned_script_sig(), elements::Script::new());
+
+        let shwpkh = Descriptor::new_sh_wpkh(pk).unwrap();
+        shwpkh.satisfy(&mut txin, &satisfier).expect("satisfaction");
+        let redeem_script = script::Builder::new()
+            .push_opcode(opcodes::all::OP_PUSHBYTES_0)
+            .push_slice(
+                &hash160::Hash::from_hex("d1b2a1faf62e73460af885c687dee3b7189cd8ab").unwrap()[..],
+            )
+            .into_script();
+        let expected_ssig = script::Builder::new()
+            .push_slice(&redeem_script[..])
+            .into_script();
+        assert_eq!(
+            txin,
+            elements_txin(expected_ssig.clone(), vec![sigser.clone(), pk.to_bytes()])
+        );
+        assert_eq!(shwpkh.unsigned_script_sig(), expected_ssig);
+
+        let ms = ms_str!("c:pk_k({})", pk);
+        let sh = Descriptor::new_sh(ms.clone()).unwrap();
+        sh.satisfy(&mut txin, &satisfier).expect("satisfaction");
+        let expected_ssig = script::Builder::new()
+            .push_slice(&sigser[..])
+            .push_slice(&ms.encode()[..])
+            .into_script();
+        assert_eq!(txin, elements_txin(expected_ssig, vec![]));
+        assert_eq!(sh.unsigned_script_sig(), Script::new());
+
+        let ms = ms_str!("c:pk_k({})", pk);
+
+        let wsh = Descriptor::new_wsh(ms.clone()).unwrap();
+        wsh.satisfy(&mut txin, &satisfier).expect("satisfaction");
+        assert_eq!(
+            txin,
+            elements_txin(
+                Script::new(),
+                vec![sigser.clone(), ms.encode().into_bytes()]
+            )
+        );
+        assert_eq!(wsh.unsigned_script_sig(), Script::new());
+
+        let shwsh = Descriptor::new_sh_wsh(ms.clone()).unwrap();
+        shwsh.satisfy(&mut txin, &satisfier).expect("satisfaction");
+        let expected_ssig = script::Builder::new()
+            .push_slice(&ms.encode().to_v0_p2wsh()[..])
+            .into_script();
+        assert_eq!(
+            txin,
+            elements_txin(
+                expected_ssig.clone(),
+                vec![sigser.clone(), ms.encode().into_bytes(),]
+            )
+        );
+        assert_eq!(shwsh.unsigned_script_sig(), expected_ssig);
+    }
+
+    #[test]
+    fn satisfy_path() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let msg = secp256k1_zkp::Message::from_slice(&b"michael was a message, amusingly"[..])
+            .expect("32 bytes");
+        let mut pks = vec![];
+        let mut sigs = HashMap::new();
+        for i in 1..3 {
+            let sk = secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap();
+            let pk = bitcoin::PublicKey {
+                key: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+                compressed: true,
+            };
+            let sig = secp.sign(&msg, &sk);
+            sigs.insert(pk, (sig, elements::SigHashType::All));
+            pks.push(pk);
+        }
+        let sigser = |pk: &bitcoin::PublicKey| {
+            let mut sigser = sigs[pk].0.serialize_der().to_vec();
+            sigser.push(0x01); // sighash_all
+            sigser
+        };
+        let satisfier = (&sigs, Older(10));
+
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(or_i(pk({}),and_v(v:pk({}),older(10))))",
+            pks[0], pks[1]
+        ))
+        .unwrap();
+        let script = desc.explicit_script().into_bytes();
+
+        let (witness, script_sig) = desc.satisfy_path(&satisfier, &[0]).unwrap();
+        assert_eq!(witness, vec![sigser(&pks[0]), vec![1], script.clone()]);
+        assert_eq!(script_sig, Script::new());
+        let (witness, _) = desc.satisfy_path(&satisfier, &[1]).unwrap();
+        assert_eq!(witness, vec![sigser(&pks[1]), vec![], script]);
+        // A selector missing entries or with entries left over is rejected
+        desc.satisfy_path(&satisfier, &[]).unwrap_err();
+        desc.satisfy_path(&satisfier, &[0, 0]).unwrap_err();
+        // The timelocked path cannot be taken if the timelock is not met
+        assert_eq!(
+            desc.satisfy_path(&sigs, &[1]).unwrap_err().to_string(),
+            Error::CouldNotSatisfy.to_string()
+        );
+        desc.satisfy_path(&satisfier, &[2]).unwrap_err();
+
+        // Nested selections are consumed in script order along the chosen path
+        let desc = StdDescriptor::from_str(&format!(
+            "elsh(wsh(andor(pk({}),or_i(older(10),pk({})),pk({}))))",
+            pks[0], pks[1], pks[1]
+        ))
+        .unwrap();
+        let script = desc.explicit_script().into_bytes();
+        let (witness, _) = desc.satisfy_path(&satisfier, &[0, 0]).unwrap();
+        assert_eq!(witness, vec![vec![1], sigser(&pks[0]), script.clone()]);
+        let (witness, _) = desc.satisfy_path(&satisfier, &[0, 1]).unwrap();
+        assert_eq!(
+            witness,
+            vec![sigser(&pks[1]), vec![], sigser(&pks[0]), script.clone()]
+        );
+        let (witness, _) = desc.satisfy_path(&satisfier, &[1]).unwrap();
+        assert_eq!(witness, vec![sigser(&pks[1]), vec![], script]);
+        // The `or_i` of the first branch needs an entry, the second one
+        // has no fragment to select
+        desc.satisfy_path(&satisfier, &[0]).unwrap_err();
+        desc.satisfy_path(&satisfier, &[1, 0]).unwrap_err();
+
+        // Descriptors without branches only take an empty selector
+        let desc = StdDescriptor::from_str(&format!("elwpkh({})", pks[0])).unwrap();
+        assert_eq!(
+            desc.satisfy_path(&satisfier, &[]).unwrap(),
+            desc.get_satisfaction(&satisfier).unwrap()
+        );
+        desc.satisfy_path(&satisfier, &[0]).unwrap_err();
+    }
+
+    #[test]
+    fn after_is_cltv() {
+        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str("elwsh(after(1000))").unwrap();
+        let script = descriptor.explicit_script();
+
+        let actual_instructions: Vec<_> = script.instructions().collect();
+        let check = actual_instructions.last().unwrap();
+
+        assert_eq!(check, &Ok(Instruction::Op(OP_CLTV)))
+    }
+
+    #[test]
+    fn older_is_csv() {
+        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str("elwsh(older(1000))").unwrap();
+        let script = descriptor.explicit_script();
+
+        let actual_instructions: Vec<_> = script.instructions().collect();
+        let check = actual_instructions.last().unwrap();
+
+        assert_eq!(check, &Ok(Instruction::Op(OP_CSV)))
+    }
+
+    #[test]
+    fn roundtrip_tests() {
+        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str("elmulti");
+        assert_eq!(
+            descriptor.unwrap_err().to_string(),
+            "unexpected «no arguments given»"
+        )
+    }
+
+    #[test]
+    fn empty_thresh() {
+        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str("elthresh");
+        assert_eq!(
+            descriptor.unwrap_err().to_string(),
+            "unexpected «no arguments given»"
+        )
+    }
+
+    #[test]
+    fn witness_stack_for_andv_is_arranged_in_correct_order() {
+        // arrange
+        let a = bitcoin::PublicKey::from_str(
+            "02937402303919b3a2ee5edd5009f4236f069bf75667b8e6ecf8e5464e20116a0e",
+        )
+        .unwrap();
+        let sig_a = secp256k1_zkp::Signature::from_str("3045022100a7acc3719e9559a59d60d7b2837f9842df30e7edcd754e63227e6168cec72c5d022066c2feba4671c3d99ea75d9976b4da6c86968dbf3bab47b1061e7a1966b1778c").unwrap();
+
+        let b = bitcoin::PublicKey::from_str(
+            "02eb64639a17f7334bb5a1a3aad857d6fec65faef439db3de72f85c88bc2906ad3",
+        )
+        .unwrap();
+        let sig_b = secp256k1_zkp::Signature::from_str("3044022075b7b65a7e6cd386132c5883c9db15f9a849a0f32bc680e9986398879a57c276022056d94d12255a4424f51c700ac75122cb354895c9f2f88f0cbb47ba05c9c589ba").unwrap();
+
+        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elwsh(and_v(v:pk({A}),pk({B})))",
+            A = a,
+            B = b
+        ))
+        .unwrap();
+
+        let mut txin = elements_txin(Script::new(), vec![]);
+        let satisfier = {
+            let mut satisfier = HashMap::with_capacity(2);
+
+            satisfier.insert(a, (sig_a.clone(), ::elements::SigHashType::All));
+            satisfier.insert(b, (sig_b.clone(), ::elements::SigHashType::All));
+
+            satisfier
+        };
+
+        // act
+        descriptor.satisfy(&mut txin, &satisfier).unwrap();
+
+        // assert
+        let witness0 = &txin.witness.script_witness[0];
+        let witness1 = &txin.witness.script_witness[1];
+
+        let sig0 = secp256k1_zkp::Signature::from_der(&witness0[..witness0.len() - 1]).unwrap();
+        let sig1 = secp256k1_zkp::Signature::from_der(&witness1[..witness1.len() - 1]).unwrap();
+
+        // why are we asserting this way?
+        // The witness stack is evaluated from top to bottom. Given an `and` instruction, the left arm of the and is going to evaluate first,
+        // meaning the next witness element (on a three element stack, that is the middle one) needs to be the signature for the left side of the `and`.
+        // The left side of the `and` performs a CHECKSIG against public key `a` so `sig1` needs to be `sig_a` and `sig0` needs to be `sig_b`.
+        assert_eq!(sig1, sig_a);
+        assert_eq!(sig0, sig_b);
+    }
+
+    #[test]
+    fn test_scriptcode() {
+        // P2WPKH (from bip143 test vectors)
+        let descriptor = Descriptor::<PublicKey>::from_str(
+            "elwpkh(025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357)",
+        )
+        .unwrap();
+        assert_eq!(
+            *descriptor.script_code().as_bytes(),
+            Vec::<u8>::from_hex("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap()[..]
+        );
+
+        // P2SH-P2WPKH (from bip143 test vectors)
+        let descriptor = Descriptor::<PublicKey>::from_str(
+            "elsh(wpkh(03ad1d8e89212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a26873))",
+        )
+        .unwrap();
+        assert_eq!(
+            *descriptor.script_code().as_bytes(),
+            Vec::<u8>::from_hex("76a91479091972186c449eb1ded22b78e40d009bdf008988ac").unwrap()[..]
+        );
+
+        // P2WSH (from bitcoind's `createmultisig`)
+        let descriptor = Descriptor::<PublicKey>::from_str(
+            "elwsh(multi(2,03789ed0bb717d88f7d321a368d905e7430207ebbd82bd342cf11ae157a7ace5fd,03dbc6764b8884a92e871274b87583e6d5c2a58819473e17e107ef3f6aa5a61626))",
+        )
+        .unwrap();
+        assert_eq!(
+            *descriptor
+                .script_code()
+                .as_bytes(),
+            Vec::<u8>::from_hex("522103789ed0bb717d88f7d321a368d905e7430207ebbd82bd342cf11ae157a7ace5fd2103dbc6764b8884a92e871274b87583e6d5c2a58819473e17e107ef3f6aa5a6162652ae").unwrap()[..]
+        );
+
+        // P2SH-P2WSH (from bitcoind's `createmultisig`)
+        let descriptor = Descriptor::<PublicKey>::from_str("elsh(wsh(multi(2,03789ed0bb717d88f7d321a368d905e7430207ebbd82bd342cf11ae157a7ace5fd,03dbc6764b8884a92e871274b87583e6d5c2a58819473e17e107ef3f6aa5a61626)))").unwrap();
+        assert_eq!(
+            *descriptor
+                .script_code()
+                .as_bytes(),
+            Vec::<u8>::from_hex("522103789ed0bb717d88f7d321a368d905e7430207ebbd82bd342cf11ae157a7ace5fd2103dbc6764b8884a92e871274b87583e6d5c2a58819473e17e107ef3f6aa5a6162652ae")
+                .unwrap()[..]
+        );
+    }
+
+    #[test]
+    fn parse_descriptor_key() {
+        // With a wildcard
+        let key = "[78412e3a/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*";
+        let expected = DescriptorPublicKey::XPub(DescriptorXKey {
+            origin: Some((
+                bip32::Fingerprint::from(&[0x78, 0x41, 0x2e, 0x3a][..]),
+                (&[
+                    bip32::ChildNumber::from_hardened_idx(44).unwrap(),
+                    bip32::ChildNumber::from_hardened_idx(0).unwrap(),
+                    bip32::ChildNumber::from_hardened_idx(0).unwrap(),
+                ][..])
+                .into(),
+            )),
+            xkey: bip32::ExtendedPubKey::from_str("xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL").unwrap(),
+            derivation_path: (&[bip32::ChildNumber::from_normal_idx(1).unwrap()][..]).into(),
+            wildcard: Wildcard::Unhardened,
+        });
+        assert_eq!(expected, key.parse().unwrap());
+        assert_eq!(format!("{}", expected), key);
+
+        // Without origin
+        let key = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1";
+        let expected = DescriptorPublicKey::XPub(DescriptorXKey {
+            origin: None,
+            xkey: bip32::ExtendedPubKey::from_str("xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL").unwrap(),
+            derivation_path: (&[bip32::ChildNumber::from_normal_idx(1).unwrap()][..]).into(),
+            wildcard: Wildcard::None,
+        });
+        assert_eq!(expected, key.parse().unwrap());
+        assert_eq!(format!("{}", expected), key);
+
+        // Testnet tpub
+        let key = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/1";
+        let expected = DescriptorPublicKey::XPub(DescriptorXKey {
+            origin: None,
+            xkey: bip32::ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed").unwrap(),
+            derivation_path: (&[bip32::ChildNumber::from_normal_idx(1).unwrap()][..]).into(),
+            wildcard: Wildcard::None,
+        });
+        assert_eq!(expected, key.parse().unwrap());
+        assert_eq!(format!("{}", expected), key);
+
+        // Without derivation path
+        let key = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let expected = DescriptorPublicKey::XPub(DescriptorXKey {
+            origin: None,
+            xkey: bip32::ExtendedPubKey::from_str("xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL").unwrap(),
+            derivation_path: bip32::DerivationPath::from(&[][..]),
+            wildcard: Wildcard::None,
+        });
+        assert_eq!(expected, key.parse().unwrap());
+        assert_eq!(format!("{}", expected), key);
+
+        // Raw (compressed) pubkey
+        let key = "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8";
+        let expected = DescriptorPublicKey::SinglePub(DescriptorSinglePub {
+            key: bitcoin::PublicKey::from_str(
+                "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8",
+            )
+            .unwrap(),
+            origin: None,
+        });
+        assert_eq!(expected, key.parse().unwrap());
+        assert_eq!(format!("{}", expected), key);
+
+        // Raw (uncompressed) pubkey
+        let key = "04f5eeb2b10c944c6b9fbcfff94c35bdeecd93df977882babc7f3a2cf7f5c81d3b09a68db7f0e04f21de5d4230e75e6dbe7ad16eefe0d4325a62067dc6f369446a";
+        let expected = DescriptorPublicKey::SinglePub(DescriptorSinglePub {
+            key: bitcoin::PublicKey::from_str(
+                "04f5eeb2b10c944c6b9fbcfff94c35bdeecd93df977882babc7f3a2cf7f5c81d3b09a68db7f0e04f21de5d4230e75e6dbe7ad16eefe0d4325a62067dc6f369446a",
+            )
+            .unwrap(),
+            origin: None,
+        });
+        assert_eq!(expected, key.parse().unwrap());
+        assert_eq!(format!("{}", expected), key);
+
+        // Raw pubkey with origin
+        let desc =
+            "[78412e3a/0'/42/0']0231c7d3fc85c148717848033ce276ae2b464a4e2c367ed33886cc428b8af48ff8";
+        let expected = DescriptorPublicKey::SinglePub(DescriptorSinglePub {
+            key: bitcoin::PublicKey::from_str(
+                "0231c7d3fc85c148717848033ce276ae2b464a4e2c367ed33886cc428b8af48ff8",
+            )
+            .unwrap(),
+            origin: Some((
+                bip32::Fingerprint::from(&[0x78, 0x41, 0x2e, 0x3a][..]),
+                (&[
+                    bip32::ChildNumber::from_hardened_idx(0).unwrap(),
+                    bip32::ChildNumber::from_normal_idx(42).unwrap(),
+                    bip32::ChildNumber::from_hardened_idx(0).unwrap(),
+                ][..])
+                    .into(),
+            )),
+        });
+        assert_eq!(expected, desc.parse().expect("Parsing desc"));
+        assert_eq!(format!("{}", expected), desc);
+    }
+
+    #[test]
+    fn test_sortedmulti() {
+        fn _test_sortedmulti(raw_desc_one: &str, raw_desc_two: &str, raw_addr_expected: &str) {
+            let secp_ctx = secp256k1_zkp::Secp256k1::verification_only();
+            let index = 5;
+
+            // Parse descriptor
+            let mut desc_one = Descriptor::<DescriptorPublicKey>::from_str(raw_desc_one).unwrap();
+            let mut desc_two = Descriptor::<DescriptorPublicKey>::from_str(raw_desc_two).unwrap();
+
+            // Same string formatting
+            assert_eq!(desc_one.to_string(), raw_desc_one);
+            assert_eq!(desc_two.to_string(), raw_desc_two);
+
+            // Derive a child if the descriptor is ranged
+            if raw_desc_one.contains("*") && raw_desc_two.contains("*") {
+                desc_one = desc_one.derive(index);
+                desc_two = desc_two.derive(index);
+            }
+
+            // Same address
+            let addr_one = desc_one
+                .translate_pk2(|xpk| xpk.derive_public_key(&secp_ctx))
+                .unwrap()
+                .address(&elements::AddressParams::ELEMENTS)
+                .unwrap();
+            let addr_two = desc_two
+                .translate_pk2(|xpk| xpk.derive_public_key(&secp_ctx))
+                .unwrap()
+                .address(&elements::AddressParams::ELEMENTS)
+                .unwrap();
+            let addr_expected = elements::Address::from_str(raw_addr_expected).unwrap();
+            assert_eq!(addr_one, addr_expected);
+            assert_eq!(addr_two, addr_expected);
+        }
+
+        // P2SH and pubkeys
+        _test_sortedmulti(
+            "elsh(sortedmulti(1,03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556,0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352))#tse3qz98",
+            "elsh(sortedmulti(1,0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352,03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556))#ptnf05qc",
+            "XUDXJZnP2GXsKRKdxSLKzJM1iZ4gbbyrGh",
+        );
+
+        // P2WSH and single-xpub descriptor
+        _test_sortedmulti(
+            "elwsh(sortedmulti(1,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH))#a8h2v83d",
+            "elwsh(sortedmulti(1,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB))#qfcn7ujk",
+            "ert1qpq2cfgz5lktxzr5zqv7nrzz46hsvq3492ump9pz8rzcl8wqtwqcs2yqnuv",
+        );
+
+        // P2WSH-P2SH and ranged descriptor
+        _test_sortedmulti(
+            "elsh(wsh(sortedmulti(1,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB/1/0/*,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH/0/0/*)))#l7qy253t",
+            "elsh(wsh(sortedmulti(1,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH/0/0/*,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB/1/0/*)))#0gpee5cl",
+            "XBkDY63XnRTz6BbwzJi3ifGhBwLTomEzkq",
+        );
+    }
+
+    #[test]
+    fn test_parse_descriptor() {
+        let secp = &secp256k1_zkp::Secp256k1::signing_only();
+        let (descriptor, key_map) = Descriptor::parse_descriptor(secp, "elwpkh(tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc/44'/0'/0'/0/*)").unwrap();
+        assert_eq!(descriptor.to_string(), "elwpkh([2cbe2a6d/44'/0'/0']tpubDCvNhURocXGZsLNqWcqD3syHTqPXrMSTwi8feKVwAcpi29oYKsDD3Vex7x2TDneKMVN23RbLprfxB69v94iYqdaYHsVz3kPR37NQXeqouVz/0/*)#pznhhta9");
+        assert_eq!(key_map.len(), 1);
+
+        // https://github.com/bitcoin/bitcoin/blob/7ae86b3c6845873ca96650fc69beb4ae5285c801/src/test/descriptor_tests.cpp#L355-L360
+        macro_rules! check_invalid_checksum {
+            ($secp: ident,$($desc: expr),*) => {
+                $(
+                    match Descriptor::parse_descriptor($secp, $desc) {
+                        Err(Error::BadDescriptor(_)) => {},
+                        Err(e) => panic!("Expected bad checksum for {}, got '{}'", $desc, e),
+                        _ => panic!("Invalid checksum treated as valid: {}", $desc),
+                    };
+                )*
+            };
+        }
+        check_invalid_checksum!(secp,
+            "elsh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#",
+            "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#",
+            "elsh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#ggrsrxf",
+            "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#tjg09x5tq",
+            "elsh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#ggrsrxf",
+            "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#tjg09x5",
+            "elsh(multi(3,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#ggrsrxfy",
+            "elsh(multi(3,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#tjg09x5t",
+            "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#tjq09x4t",
+            "elsh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))##ggssrxfy",
+            "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))##tjq09x4t"
+        );
+
+        Descriptor::parse_descriptor(&secp, "elsh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#9s2ngs7u").expect("Valid descriptor with checksum");
+        Descriptor::parse_descriptor(&secp, "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#uklept69").expect("Valid descriptor with checksum");
+    }
+
+    #[test]
+    fn secret_descriptor_to_public() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let s = "elwsh(multi(2,[aabbccdd/90']tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc/0'/1'/*,tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc/2/*,cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy))";
+        let secret = Descriptor::<DescriptorSecretKey>::from_str(s).unwrap();
+        let public = secret.to_public(&secp).unwrap();
+        // Hardened steps are moved to the origin, as done by `as_public`
+        let (expected, key_map) = Descriptor::parse_descriptor(&secp, s).unwrap();
+        assert_eq!(public, expected);
+        assert_eq!(key_map.len(), 3);
+        assert!(public
+            .to_string()
+            .starts_with("elwsh(multi(2,[aabbccdd/90'/0'/1']tpub"));
+        assert_eq!(secret.to_string().split('#').next().unwrap(), s);
+    }
+
+    #[test]
+    #[cfg(feature = "compiler")]
+    fn parse_and_derive() {
+        let descriptor_str = "thresh(2,\
+pk([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*),\
+pk(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1),\
+pk(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8))";
+        let policy: policy::concrete::Policy<DescriptorPublicKey> = descriptor_str.parse().unwrap();
+        let descriptor = Descriptor::new_sh(policy.compile().unwrap()).unwrap();
+        let derived_descriptor = descriptor.derive(42);
+
+        let res_descriptor_str = "thresh(2,\
+pk([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/42),\
+pk(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1),\
+pk(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8))";
+        let res_policy: policy::concrete::Policy<DescriptorPublicKey> =
+            res_descriptor_str.parse().unwrap();
+        let res_descriptor = Descriptor::new_sh(res_policy.compile().unwrap()).unwrap();
+
+        assert_eq!(res_descriptor, derived_descriptor);
+    }
+
+    #[test]
+    fn sorted_form() {
+        // 02.. sorts before 03..
+        let a = "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8";
+        let b = "02e493dbf1c10d80f3581e4904930b1404cc6c13900ee0758474fa94abe8c4cd13";
+        for (multi, sortedmulti) in &[
+            ("elwsh(multi(1,{b},{a}))", "elwsh(sortedmulti(1,{b},{a}))"),
+            ("elsh(multi(1,{b},{a}))", "elsh(sortedmulti(1,{b},{a}))"),
+            (
+                "elsh(wsh(multi(1,{b},{a})))",
+                "elsh(wsh(sortedmulti(1,{b},{a})))",
+            ),
+        ] {
+            let multi =
+                StdDescriptor::from_str(&multi.replace("{a}", a).replace("{b}", b)).unwrap();
+            let sortedmulti =
+                StdDescriptor::from_str(&sortedmulti.replace("{a}", a).replace("{b}", b)).unwrap();
+            assert_eq!(multi.to_sorted_form(), sortedmulti);
+            assert_eq!(multi.script_pubkey(), sortedmulti.script_pubkey());
         }
-    }
-}
 
-serde_string_impl_pk!(Descriptor, "a script descriptor");
+        // Unsorted keys, or descriptors without a sortedmulti form, are unchanged
+        for desc in &[
+            format!("elwsh(multi(1,{},{}))", a, b),
+            format!("elsh(multi(1,{},{}))", a, b),
+            format!("elwsh(sortedmulti(1,{},{}))", a, b),
+            format!("elmulti(1,{},{})", b, a),
+            format!("elwsh(or_d(multi(1,{},{}),older(10)))", b, a),
+            format!("elwpkh({})", a),
+        ] {
+            let desc = StdDescriptor::from_str(desc).unwrap();
+            assert_eq!(desc.to_sorted_form(), desc);
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::checksum::desc_checksum;
-    use super::DescriptorTrait;
-    use bitcoin;
-    use bitcoin::hashes::hex::FromHex;
-    use bitcoin::hashes::{hash160, sha256};
-    use bitcoin::util::bip32;
-    use bitcoin::PublicKey;
-    use descriptor::key::Wildcard;
-    use descriptor::{
-        DescriptorPublicKey, DescriptorSecretKey, DescriptorSinglePub, DescriptorXKey,
-    };
+    #[test]
+    fn from_slip132() {
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let ypub = "ypub6ZFS8LErd4QBBVwLiy55q3icFPkSNde7zAnE895MfQsATRevcQsFDpxjadiSgUazAT5PpnAqyQtc3cu6kPGC4fRB5hgpvXVoC4NYx7B2Uih";
+        let zpub_multi = "Zpub74ynZEeDLhW2TNHqVzKgsD9v99w9XcKDDYx7poEnRC5T8i34dUR5w1UoQYdWEpTooZGBSqmXKHdf458RmJqA1PDSnWVeuqnHMWi4vf8xWkc";
 
-    use elements::opcodes::{
-        self,
-        all::{OP_CLTV, OP_CSV},
-    };
-    use elements::script::Instruction;
-    use elements::{self, secp256k1_zkp};
-    use elements::{script, Script};
-    use hex_script;
-    use miniscript::satisfy::ElementsSig;
-    use std::cmp;
-    use std::collections::HashMap;
-    use std::str::FromStr;
-    use {Descriptor, DummyKey, Error, Miniscript, Satisfier, TranslatePk2};
+        let desc = Descriptor::from_slip132(&format!("[d34db33f/49'/0'/0']{}/0/*", ypub)).unwrap();
+        assert_eq!(
+            desc,
+            Descriptor::from_str(&format!("elsh(wpkh([d34db33f/49'/0'/0']{}/0/*))", xpub)).unwrap()
+        );
+        let desc = Descriptor::from_slip132(xpub).unwrap();
+        assert_eq!(desc.desc_type(), DescriptorType::Pkh);
+        // Descriptor strings only accept the standard encoding
+        assert!(Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(1,{}/0/*))",
+            zpub_multi
+        ))
+        .is_err());
+        assert!(
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elpkh({}/0/*)", ypub)).is_err()
+        );
 
-    #[cfg(feature = "compiler")]
-    use policy;
+        assert!(Descriptor::from_slip132(zpub_multi).is_err());
+        assert!(Descriptor::from_slip132(
+            "020000000000000000000000000000000000000000000000000000000000000002"
+        )
+        .is_err());
+    }
 
-    type StdDescriptor = Descriptor<PublicKey>;
-    const TEST_PK: &'static str =
-        "elpk(020000000000000000000000000000000000000000000000000000000000000002)";
+    #[test]
+    fn hashlock_descriptors() {
+        struct PreimageSat(Preimage32);
 
-    impl cmp::PartialEq for DescriptorSecretKey {
-        fn eq(&self, other: &Self) -> bool {
-            match (self, other) {
-                (
-                    &DescriptorSecretKey::SinglePriv(ref a),
-                    &DescriptorSecretKey::SinglePriv(ref b),
-                ) => a.origin == b.origin && a.key == b.key,
-                (&DescriptorSecretKey::XPrv(ref a), &DescriptorSecretKey::XPrv(ref b)) => {
-                    a.origin == b.origin
-                        && a.xkey == b.xkey
-                        && a.derivation_path == b.derivation_path
-                        && a.wildcard == b.wildcard
-                }
-                _ => false,
+        impl Satisfier<bitcoin::PublicKey> for PreimageSat {
+            fn lookup_sha256(&self, h: sha256::Hash) -> Option<Preimage32> {
+                Some(self.0).filter(|p| sha256::Hash::hash(p) == h)
+            }
+            fn lookup_hash256(&self, h: sha256d::Hash) -> Option<Preimage32> {
+                Some(self.0).filter(|p| sha256d::Hash::hash(p) == h)
+            }
+            fn lookup_ripemd160(&self, h: ripemd160::Hash) -> Option<Preimage32> {
+                Some(self.0).filter(|p| ripemd160::Hash::hash(p) == h)
+            }
+            fn lookup_hash160(&self, h: hash160::Hash) -> Option<Preimage32> {
+                Some(self.0).filter(|p| hash160::Hash::hash(p) == h)
             }
         }
-    }
 
-    fn roundtrip_descriptor(s: &str) {
-        let desc = Descriptor::<DummyKey>::from_str(&s).unwrap();
-        let output = desc.to_string();
-        let normalize_aliases = s.replace("c:pk_k(", "pk(").replace("c:pk_h(", "pkh(");
-        assert_eq!(
-            format!(
-                "{}#{}",
-                &normalize_aliases,
-                desc_checksum(&normalize_aliases).unwrap()
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[4; 32]).unwrap();
+        let pk = bitcoin::PublicKey {
+            key: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+            compressed: true,
+        };
+        let msg = secp256k1_zkp::Message::from_slice(&[1; 32]).unwrap();
+        let sig = (secp.sign(&msg, &sk), elements::SigHashType::All);
+        let mut sigs = HashMap::new();
+        sigs.insert(pk, sig);
+
+        let preimage = [0x42; 32];
+        let hashlocks = [
+            (
+                "sha256",
+                sha256::Hash::hash(&preimage).to_vec(),
+                opcodes::all::OP_SHA256,
             ),
-            output
-        );
-    }
-
-    // helper function to create elements txin from scriptsig and witness
-    fn elements_txin(script_sig: Script, witness: Vec<Vec<u8>>) -> elements::TxIn {
-        let mut txin_witness = elements::TxInWitness::default();
-        txin_witness.script_witness = witness;
-        elements::TxIn {
-            previous_output: elements::OutPoint::default(),
-            script_sig: script_sig,
-            sequence: 100,
-            is_pegin: false,
-            has_issuance: false,
-            asset_issuance: elements::AssetIssuance::default(),
-            witness: txin_witness,
+            (
+                "hash256",
+                sha256d::Hash::hash(&preimage).to_vec(),
+                opcodes::all::OP_HASH256,
+            ),
+            (
+                "ripemd160",
+                ripemd160::Hash::hash(&preimage).to_vec(),
+                opcodes::all::OP_RIPEMD160,
+            ),
+            (
+                "hash160",
+                hash160::Hash::hash(&preimage).to_vec(),
+                opcodes::all::OP_HASH160,
+            ),
+        ];
+        for &(name, ref hash, opcode) in hashlocks.iter() {
+            // Hashes are written in script byte order, which for hash256
+            // is the reverse of the usual sha256d display order
+            let ms_str = format!("and_v(v:{}({}),pk({}))", name, hash.to_hex(), pk);
+            // Hashlocks are valid in segwit contexts
+            Miniscript::<bitcoin::PublicKey, Segwitv0>::from_str(&ms_str).unwrap();
+            let desc = StdDescriptor::from_str(&format!("elwsh({})", ms_str)).unwrap();
+
+            let witness_script = script::Builder::new()
+                .push_opcode(opcodes::all::OP_SIZE)
+                .push_int(32)
+                .push_opcode(opcodes::all::OP_EQUALVERIFY)
+                .push_opcode(opcode)
+                .push_slice(hash)
+                .push_opcode(opcodes::all::OP_EQUALVERIFY)
+                .push_slice(&pk.to_bytes())
+                .push_opcode(opcodes::all::OP_CHECKSIG)
+                .into_script();
+            assert_eq!(desc.explicit_script(), witness_script);
+
+            // Satisfaction requires both the preimage and the signature
+            assert!(desc.get_satisfaction(&sigs).is_err());
+            assert!(desc.get_satisfaction(PreimageSat(preimage)).is_err());
+            assert!(desc
+                .get_satisfaction((&sigs, PreimageSat([0x43; 32])))
+                .is_err());
+            let (witness, script_sig) = desc
+                .get_satisfaction((&sigs, PreimageSat(preimage)))
+                .unwrap();
+            assert_eq!(
+                witness,
+                vec![
+                    elementssig_to_rawsig(&sig),
+                    preimage.to_vec(),
+                    witness_script.to_bytes(),
+                ]
+            );
+
+            // The interpreter accepts the satisfaction
+            let mut interpreter =
+                Interpreter::from_txdata(&desc.script_pubkey(), &script_sig, &witness, 0, 0)
+                    .unwrap();
+            assert!(interpreter
+                .iter(|_, _| true)
+                .all(|constraint| constraint.is_ok()));
         }
     }
 
     #[test]
-    fn desc_rtt_tests() {
-        roundtrip_descriptor("elc:pk_k()");
-        roundtrip_descriptor("elwsh(pk())");
-        roundtrip_descriptor("elwsh(c:pk_k())");
-        roundtrip_descriptor("elc:pk_h()");
-    }
-    #[test]
-    fn parse_descriptor() {
-        StdDescriptor::from_str("(").unwrap_err();
-        StdDescriptor::from_str("(x()").unwrap_err();
-        StdDescriptor::from_str("(\u{7f}()3").unwrap_err();
-        StdDescriptor::from_str("pk()").unwrap_err();
-        StdDescriptor::from_str("nl:0").unwrap_err(); //issue 63
-        let compressed_pk = DummyKey.to_string();
-        assert_eq!(
-            StdDescriptor::from_str("elsh(sortedmulti)")
-                .unwrap_err()
-                .to_string(),
-            "unexpected «no arguments given for sortedmulti»"
-        ); //issue 202
-        assert_eq!(
-            StdDescriptor::from_str(&format!("elsh(sortedmulti(2,{}))", compressed_pk))
-                .unwrap_err()
-                .to_string(),
-            "unexpected «higher threshold than there were keys in sortedmulti»"
-        ); //issue 202
+    fn nonzero_wrapper_satisfaction() {
+        struct PreimageSat(Preimage32);
 
-        StdDescriptor::from_str(TEST_PK).unwrap();
-        // fuzzer
-        StdDescriptor::from_str("slip77").unwrap_err();
+        impl Satisfier<bitcoin::PublicKey> for PreimageSat {
+            fn lookup_sha256(&self, h: sha256::Hash) -> Option<Preimage32> {
+                Some(self.0).filter(|p| sha256::Hash::hash(p) == h)
+            }
+        }
 
-        let uncompressed_pk =
-        "0414fc03b8df87cd7b872996810db8458d61da8448e531569c8517b469a119d267be5645686309c6e6736dbd93940707cc9143d3cf29f1b877ff340e2cb2d259cf";
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let msg = secp256k1_zkp::Message::from_slice(&[1; 32]).unwrap();
+        let mut pks = vec![];
+        let mut sigs = HashMap::new();
+        for i in 5..7 {
+            let sk = secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap();
+            let pk = bitcoin::PublicKey {
+                key: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+                compressed: true,
+            };
+            sigs.insert(pk, (secp.sign(&msg, &sk), elements::SigHashType::All));
+            pks.push(pk);
+        }
+        let (a, b) = (pks[0], pks[1]);
+        let preimage = [0x42; 32];
+        let hash = sha256::Hash::hash(&preimage);
+        let mut sigs_a = HashMap::new();
+        sigs_a.insert(a, sigs[&a]);
+        let mut sigs_b = HashMap::new();
+        sigs_b.insert(b, sigs[&b]);
+
+        // `j:` skips its sub-fragment when the top stack element is empty,
+        // which dissatisfies it
+        let ms = format!("or_d(j:and_v(v:pk({}),sha256({})),pk({}))", a, hash, b);
+        for desc in [format!("elwsh({})", ms), format!("elsh({})", ms)].iter() {
+            let desc = StdDescriptor::from_str(desc).unwrap();
+            let items = |witness: &[Vec<u8>], script_sig: &Script| -> Vec<Vec<u8>> {
+                let mut items = witness.to_vec();
+                for ins in script_sig.instructions().flatten() {
+                    if let Instruction::PushBytes(push) = ins {
+                        items.push(push.to_vec());
+                    }
+                }
+                // Drop the witness or redeem script
+                items.pop();
+                items
+            };
 
-        // Context tests
-        StdDescriptor::from_str(&format!("elpk({})", uncompressed_pk)).unwrap();
-        StdDescriptor::from_str(&format!("elpkh({})", uncompressed_pk)).unwrap();
-        StdDescriptor::from_str(&format!("elsh(pk({}))", uncompressed_pk)).unwrap();
-        StdDescriptor::from_str(&format!("elwpkh({})", uncompressed_pk)).unwrap_err();
-        StdDescriptor::from_str(&format!("elsh(wpkh({}))", uncompressed_pk)).unwrap_err();
-        StdDescriptor::from_str(&format!("elwsh(pk{})", uncompressed_pk)).unwrap_err();
-        StdDescriptor::from_str(&format!("elsh(wsh(pk{}))", uncompressed_pk)).unwrap_err();
-        StdDescriptor::from_str(&format!(
-            "elor_i(pk({}),pk({}))",
-            uncompressed_pk, uncompressed_pk
-        ))
-        .unwrap_err();
+            // Satisfied: the preimage is the non-empty top element
+            let (witness, script_sig) = desc
+                .get_satisfaction((&sigs_a, PreimageSat(preimage)))
+                .unwrap();
+            assert_eq!(
+                items(&witness, &script_sig),
+                vec![preimage.to_vec(), elementssig_to_rawsig(&sigs[&a])]
+            );
+            let sat_weight = satisfaction_weight(&witness, &script_sig, |_| false);
+            let mut interpreter =
+                Interpreter::from_txdata(&desc.script_pubkey(), &script_sig, &witness, 0, 0)
+                    .unwrap();
+            let constraints: Vec<_> = interpreter
+                .iter(|_, _| true)
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(constraints.len(), 2);
+            match constraints[0] {
+                SatisfiedConstraint::PublicKey { key, .. } => assert_eq!(*key, a),
+                ref c => panic!("unexpected constraint {:?}", c),
+            }
+            match constraints[1] {
+                SatisfiedConstraint::HashLock { preimage: p, .. } => assert_eq!(p, &preimage[..]),
+                ref c => panic!("unexpected constraint {:?}", c),
+            }
+
+            // Dissatisfied: a single empty push, even though the
+            // signature of `a` is available
+            let (witness, script_sig) = desc.get_satisfaction(&sigs_b).unwrap();
+            assert_eq!(
+                items(&witness, &script_sig),
+                vec![elementssig_to_rawsig(&sigs[&b]), vec![]]
+            );
+            let (witness_a, script_sig_a) = desc.get_satisfaction(&sigs).unwrap();
+            assert_eq!(
+                (witness_a, script_sig_a),
+                (witness.clone(), script_sig.clone())
+            );
+            let dissat_weight = satisfaction_weight(&witness, &script_sig, |_| false);
+            let mut interpreter =
+                Interpreter::from_txdata(&desc.script_pubkey(), &script_sig, &witness, 0, 0)
+                    .unwrap();
+            let constraints: Vec<_> = interpreter
+                .iter(|_, _| true)
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(constraints.len(), 1);
+            match constraints[0] {
+                SatisfiedConstraint::PublicKey { key, .. } => assert_eq!(*key, b),
+                ref c => panic!("unexpected constraint {:?}", c),
+            }
+
+            // A non-empty top element which is not the preimage fails
+            let mut bad_witness = witness.clone();
+            let mut bad_script_sig = script_sig.clone();
+            if witness.is_empty() {
+                let mut pushes: Vec<Vec<u8>> = vec![];
+                for ins in script_sig.instructions().flatten() {
+                    if let Instruction::PushBytes(push) = ins {
+                        pushes.push(push.to_vec());
+                    }
+                }
+                pushes[1] = vec![0x43; 32];
+                let mut builder = script::Builder::new();
+                for push in &pushes {
+                    builder = builder.push_slice(push);
+                }
+                bad_script_sig = builder.into_script();
+            } else {
+                bad_witness[1] = vec![0x43; 32];
+            }
+            let mut interpreter = Interpreter::from_txdata(
+                &desc.script_pubkey(),
+                &bad_script_sig,
+                &bad_witness,
+                0,
+                0,
+            )
+            .unwrap();
+            assert!(interpreter.iter(|_, _| true).any(|c| c.is_err()));
+
+            // Weights of both paths are accounted for
+            let (min, max) = desc.satisfaction_weight_range().unwrap();
+            assert_eq!(max, desc.max_satisfaction_weight().unwrap());
+            for &weight in [sat_weight, dissat_weight].iter() {
+                assert!(min <= weight && weight <= max, "{} {} {}", min, weight, max);
+            }
+            let mut txin = elements_txin(Script::new(), vec![]);
+            desc.satisfy_with_dummy(&mut txin).unwrap();
+            assert_eq!(
+                satisfaction_weight(&txin.witness.script_witness, &txin.script_sig, |_| false),
+                max
+            );
+        }
     }
 
     #[test]
-    pub fn script_pubkey() {
-        let bare = StdDescriptor::from_str(&format!(
-            "elmulti(1,020000000000000000000000000000000000000000000000000000000000000002)"
-        ))
-        .unwrap();
-        assert_eq!(
-            bare.script_pubkey(),
-            hex_script(
-                "512102000000000000000000000000000000000000000000000000000000000000000251ae"
-            )
-        );
+    fn logging_satisfier() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[2; 32]).unwrap();
+        let pk = bitcoin::PublicKey {
+            key: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+            compressed: true,
+        };
+        let msg = secp256k1_zkp::Message::from_slice(&[1; 32]).unwrap();
+        let sig = (secp.sign(&msg, &sk), elements::SigHashType::All);
+        let hash = sha256::Hash::hash(&[0; 32]);
+        let desc = StdDescriptor::from_str(&format!("elwsh(and_v(v:pk({}),sha256({})))", pk, hash))
+            .unwrap();
+
+        let mut sigs = HashMap::new();
+        sigs.insert(pk, sig);
+        let satisfier = LoggingSatisfier::new(sigs);
+        assert!(desc.get_satisfaction(&satisfier).is_err());
+        assert!(satisfier.lookups().contains(&(Lookup::Sig(pk), true)));
+        assert_eq!(satisfier.missing(), vec![Lookup::Sha256(hash)]);
+
+        satisfier.clear();
+        assert!(satisfier.lookups().is_empty());
+        let mut sigs = satisfier.into_inner();
+        sigs.clear();
+        let satisfier = LoggingSatisfier::new(sigs);
+        assert!(desc.get_satisfaction(&satisfier).is_err());
         assert_eq!(
-            bare.address(&elements::AddressParams::ELEMENTS)
-                .unwrap_err()
-                .to_string(),
-            "Bare descriptors don't have address"
+            satisfier.missing(),
+            vec![Lookup::Sig(pk), Lookup::Sha256(hash)]
         );
+    }
 
-        let pk = StdDescriptor::from_str(TEST_PK).unwrap();
-        assert_eq!(
-            pk.script_pubkey(),
-            elements::Script::from(vec![
-                0x21, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xac,
-            ])
-        );
+    #[test]
+    fn required_signature_count() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let keys: Vec<PublicKey> = (1..4u8)
+            .map(|i| PublicKey {
+                key: secp256k1_zkp::PublicKey::from_secret_key(
+                    &secp,
+                    &secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap(),
+                ),
+                compressed: true,
+            })
+            .collect();
+        let (a, b, c) = (keys[0], keys[1], keys[2]);
 
-        let pkh = StdDescriptor::from_str(
-            "elpkh(\
-             020000000000000000000000000000000000000000000000000000000000000002\
-             )",
-        )
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(or_d(multi(2,{},{}),and_v(v:pk({}),older(144))))",
+            a, b, c
+        ))
         .unwrap();
-        assert_eq!(
-            pkh.script_pubkey(),
-            script::Builder::new()
-                .push_opcode(opcodes::all::OP_DUP)
-                .push_opcode(opcodes::all::OP_HASH160)
-                .push_slice(
-                    &hash160::Hash::from_hex("84e9ed95a38613f0527ff685a9928abe2d4754d4",).unwrap()
-                        [..]
-                )
-                .push_opcode(opcodes::all::OP_EQUALVERIFY)
-                .push_opcode(opcodes::all::OP_CHECKSIG)
-                .into_script()
-        );
-        assert_eq!(
-            pkh.address(&elements::AddressParams::ELEMENTS,)
-                .unwrap()
-                .to_string(),
-            "2dmYXpSu8YP6aLcJYhHfB1C19mdzSx2GPB9"
-        );
-
-        let wpkh = StdDescriptor::from_str(
-            "elwpkh(\
-             020000000000000000000000000000000000000000000000000000000000000002\
-             )",
-        )
+        assert_eq!(desc.required_signature_count(&[a, b, c]), Some(1));
+        assert_eq!(desc.required_signature_count(&[a, b]), Some(2));
+        assert_eq!(desc.required_signature_count(&[a]), None);
+        assert_eq!(desc.required_signature_count(&[]), None);
+
+        // Signatures in the scriptSig and thresholds
+        let desc = StdDescriptor::from_str(&format!("elsh(multi(2,{},{},{}))", a, b, c)).unwrap();
+        assert_eq!(desc.required_signature_count(&[a, b, c]), Some(2));
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(thresh(2,pk({}),s:pk({}),s:pk({})))",
+            a, b, c
+        ))
         .unwrap();
-        assert_eq!(
-            wpkh.script_pubkey(),
-            script::Builder::new()
-                .push_opcode(opcodes::all::OP_PUSHBYTES_0)
-                .push_slice(
-                    &hash160::Hash::from_hex("84e9ed95a38613f0527ff685a9928abe2d4754d4",).unwrap()
-                        [..]
-                )
-                .into_script()
-        );
-        assert_eq!(
-            wpkh.address(&elements::AddressParams::ELEMENTS,)
-                .unwrap()
-                .to_string(),
-            "ert1qsn57m9drscflq5nl76z6ny52hck5w4x57m69k3"
-        );
+        assert_eq!(desc.required_signature_count(&[b, c]), Some(2));
+        assert_eq!(desc.required_signature_count(&[c]), None);
+        let desc = StdDescriptor::from_str(&format!("elpkh({})", a)).unwrap();
+        assert_eq!(desc.required_signature_count(&[a]), Some(1));
+    }
 
-        let shwpkh = StdDescriptor::from_str(
-            "elsh(wpkh(\
-             020000000000000000000000000000000000000000000000000000000000000002\
-             ))",
-        )
+    #[test]
+    fn required_preimages() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let keys: Vec<PublicKey> = (1..4u8)
+            .map(|i| PublicKey {
+                key: secp256k1_zkp::PublicKey::from_secret_key(
+                    &secp,
+                    &secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap(),
+                ),
+                compressed: true,
+            })
+            .collect();
+        let (a, b, c) = (keys[0], keys[1], keys[2]);
+        let sha = sha256::Hash::hash(&[1; 32]);
+        let h160 = hash160::Hash::hash(&[2; 32]);
+
+        // Swap-like descriptor: claim with a preimage, or refund after a
+        // timeout with another one
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(andor(pk({}),sha256({}),and_v(v:pk({}),and_v(v:hash160({}),older(144)))))",
+            a, sha, b, h160
+        ))
         .unwrap();
         assert_eq!(
-            shwpkh.script_pubkey(),
-            script::Builder::new()
-                .push_opcode(opcodes::all::OP_HASH160)
-                .push_slice(
-                    &hash160::Hash::from_hex("f1c3b9a431134cb90a500ec06e0067cfa9b8bba7",).unwrap()
-                        [..]
-                )
-                .push_opcode(opcodes::all::OP_EQUAL)
-                .into_script()
+            desc.required_preimages(&[a]),
+            Some(vec![Lookup::Sha256(sha)])
         );
         assert_eq!(
-            shwpkh
-                .address(&elements::AddressParams::ELEMENTS,)
-                .unwrap()
-                .to_string(),
-            "XZPaAbg6M83Fq5NqvbEGZ5kwy9RKSTke2s"
+            desc.required_preimages(&[b]),
+            Some(vec![Lookup::Hash160(h160)])
+        );
+        // The first branch is cheaper, as the second one needs the key of
+        // the first to be dissatisfied
+        assert_eq!(
+            desc.required_preimages(&[a, b]),
+            Some(vec![Lookup::Sha256(sha)])
         );
+        assert_eq!(desc.required_preimages(&[c]), None);
 
-        let sh = StdDescriptor::from_str(
-            "elsh(c:pk_k(\
-             020000000000000000000000000000000000000000000000000000000000000002\
-             ))",
-        )
+        // Preimages in the scriptSig, and descriptors without hashlocks
+        let desc =
+            StdDescriptor::from_str(&format!("elsh(and_v(v:pk({}),sha256({})))", a, sha)).unwrap();
+        assert_eq!(
+            desc.required_preimages(&[a]),
+            Some(vec![Lookup::Sha256(sha)])
+        );
+        let desc = StdDescriptor::from_str(&format!("elwpkh({})", a)).unwrap();
+        assert_eq!(desc.required_preimages(&[a]), Some(vec![]));
+        assert_eq!(desc.required_preimages(&[b]), None);
+    }
+
+    #[test]
+    fn try_satisfy() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let msg = secp256k1_zkp::Message::from_slice(&[1; 32]).unwrap();
+        let mut keys = vec![];
+        let mut sigs = vec![];
+        for i in 1..4 {
+            let sk = secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap();
+            keys.push(bitcoin::PublicKey {
+                key: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+                compressed: true,
+            });
+            sigs.push((secp.sign(&msg, &sk), elements::SigHashType::All));
+        }
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(or_d(multi(2,{},{}),and_v(v:pk({}),older(144))))",
+            keys[0], keys[1], keys[2]
+        ))
         .unwrap();
+
+        // One signature away from the multisig, two items away from the
+        // recovery path
+        let mut available = HashMap::new();
+        available.insert(keys[0], sigs[0]);
         assert_eq!(
-            sh.script_pubkey(),
-            script::Builder::new()
-                .push_opcode(opcodes::all::OP_HASH160)
-                .push_slice(
-                    &hash160::Hash::from_hex("aa5282151694d3f2f32ace7d00ad38f927a33ac8",).unwrap()
-                        [..]
-                )
-                .push_opcode(opcodes::all::OP_EQUAL)
-                .into_script()
+            desc.try_satisfy(&available),
+            Err(MissingItems {
+                path: vec![0],
+                missing: vec![Lookup::Sig(keys[1])],
+            })
         );
+
+        available.clear();
+        available.insert(keys[2], sigs[2]);
         assert_eq!(
-            sh.address(&elements::AddressParams::ELEMENTS,)
-                .unwrap()
-                .to_string(),
-            "XSspZXDJu2XVh8AKC7qF3L7x79Qy67JhQb"
+            desc.try_satisfy(&available),
+            Err(MissingItems {
+                path: vec![1],
+                missing: vec![Lookup::Older(144)],
+            })
+        );
+        assert_eq!(
+            desc.try_satisfy((&available, Older(144))),
+            desc.get_satisfaction((&available, Older(144)))
+                .map_err(|_| unreachable!())
         );
 
-        let wsh = StdDescriptor::from_str(
-            "elwsh(c:pk_k(\
-             020000000000000000000000000000000000000000000000000000000000000002\
-             ))",
-        )
-        .unwrap();
+        available.insert(keys[0], sigs[0]);
+        available.insert(keys[1], sigs[1]);
+        let (witness, _) = desc.try_satisfy(&available).unwrap();
+        assert_eq!(witness.len(), 4);
+    }
+
+    #[test]
+    fn from_str_lenient() {
+        let key = "020000000000000000000000000000000000000000000000000000000000000002";
+        let xpub = "[d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*";
+
+        // Bare keys are only accepted in lenient mode
+        assert!(StdDescriptor::from_str(key).is_err());
         assert_eq!(
-            wsh.script_pubkey(),
-            script::Builder::new()
-                .push_opcode(opcodes::all::OP_PUSHBYTES_0)
-                .push_slice(
-                    &sha256::Hash::from_hex(
-                        "\
-                         f9379edc8983152dc781747830075bd5\
-                         3896e4b0ce5bff73777fd77d124ba085\
-                         "
-                    )
-                    .unwrap()[..]
-                )
-                .into_script()
+            StdDescriptor::from_str_lenient(key).unwrap(),
+            StdDescriptor::from_str(&format!("elpk({})", key)).unwrap()
         );
+        assert!(Descriptor::<DescriptorPublicKey>::from_str(xpub).is_err());
         assert_eq!(
-            wsh.address(&elements::AddressParams::ELEMENTS,)
-                .unwrap()
-                .to_string(),
-            "ert1qlymeahyfsv2jm3upw3urqp6m65ufde9seedl7umh0lth6yjt5zzsan9u2t"
+            Descriptor::<DescriptorPublicKey>::from_str_lenient(xpub).unwrap(),
+            Descriptor::from_str(&format!("elpk({})", xpub)).unwrap()
         );
 
-        let shwsh = StdDescriptor::from_str(
-            "elsh(wsh(c:pk_k(\
-             020000000000000000000000000000000000000000000000000000000000000002\
-             )))",
-        )
-        .unwrap();
+        // Anything else parses exactly as in strict mode
+        let desc = format!("elwpkh({})", key);
         assert_eq!(
-            shwsh.script_pubkey(),
-            script::Builder::new()
-                .push_opcode(opcodes::all::OP_HASH160)
-                .push_slice(
-                    &hash160::Hash::from_hex("4bec5d7feeed99e1d0a23fe32a4afe126a7ff07e",).unwrap()
-                        [..]
-                )
-                .push_opcode(opcodes::all::OP_EQUAL)
-                .into_script()
+            StdDescriptor::from_str_lenient(&desc).unwrap(),
+            StdDescriptor::from_str(&desc).unwrap()
         );
+        assert!(StdDescriptor::from_str_lenient("wpkh(02)").is_err());
         assert_eq!(
-            shwsh
-                .address(&elements::AddressParams::ELEMENTS,)
-                .unwrap()
+            StdDescriptor::from_str_lenient("not a key")
+                .unwrap_err()
                 .to_string(),
-            "XJGggUb965TvGF2VCxp9EQGmZTxMeDjwQQ"
+            StdDescriptor::from_str("not a key")
+                .unwrap_err()
+                .to_string()
         );
     }
 
     #[test]
-    fn satisfy() {
-        let secp = secp256k1_zkp::Secp256k1::new();
-        let sk =
-            secp256k1_zkp::SecretKey::from_slice(&b"sally was a secret key, she said"[..]).unwrap();
-        let pk = bitcoin::PublicKey {
-            key: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
-            compressed: true,
-        };
-        let msg = secp256k1_zkp::Message::from_slice(&b"michael was a message, amusingly"[..])
-            .expect("32 bytes");
-        let sig = secp.sign(&msg, &sk);
-        let mut sigser = sig.serialize_der().to_vec();
-        sigser.push(0x01); // sighash_all
-
-        struct SimpleSat {
-            sig: secp256k1_zkp::Signature,
-            pk: bitcoin::PublicKey,
+    fn change_descriptor() {
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let receive = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(1,[78412e3a/48'/1'/0'/2']{}/0/*,{}/0/*h))",
+            xpub, xpub
+        ))
+        .unwrap();
+        let change = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(1,[78412e3a/48'/1'/0'/2']{}/1/*,{}/1/*h))",
+            xpub, xpub
+        ))
+        .unwrap();
+        assert_eq!(receive.to_change_descriptor().unwrap(), change);
+
+        // Single keys, keys without wildcard or not ending in /0/* are rejected
+        for desc in &[
+            "elwpkh(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8)".to_owned(),
+            format!("elwpkh({}/0)", xpub),
+            format!("elwpkh({}/1/*)", xpub),
+            format!("elwpkh({}/0'/*)", xpub),
+            format!("elwpkh({}/*)", xpub),
+        ] {
+            let desc = Descriptor::<DescriptorPublicKey>::from_str(desc).unwrap();
+            desc.to_change_descriptor().unwrap_err();
         }
+    }
 
-        impl Satisfier<bitcoin::PublicKey> for SimpleSat {
-            fn lookup_sig(&self, pk: &bitcoin::PublicKey) -> Option<ElementsSig> {
-                if *pk == self.pk {
-                    Some((self.sig, elements::SigHashType::All))
-                } else {
-                    None
-                }
+    #[test]
+    fn parse_with_secrets() {
+        let secp = &secp256k1_zkp::Secp256k1::signing_only();
+        let descriptor_str = "elwpkh(xprv9s21ZrQH143K4CTb63EaMxja1YiTnSEWKMbn23uoEnAzxjdUJRQkazCAtzxGm4LSoTSVTptoV9RbchnKPW9HxKtZumdyxyikZFDLhogJ5Uj/44'/0'/0'/0/*)#xldrpn5u";
+        let (descriptor, keymap) =
+            Descriptor::<DescriptorPublicKey>::parse_descriptor(&secp, descriptor_str).unwrap();
+
+        let expected = "elwpkh([a12b02f4/44'/0'/0']xpub6BzhLAQUDcBUfHRQHZxDF2AbcJqp4Kaeq6bzJpXrjrWuK26ymTFwkEFbxPra2bJ7yeZKbDjfDeFwxe93JMqpo5SsPJH6dZdvV9kMzJkAZ69/0/*)#20ufqv7z";
+        assert_eq!(expected, descriptor.to_string());
+        assert_eq!(keymap.len(), 1);
+
+        // try to turn it back into a string with the secrets
+        assert_eq!(descriptor_str, descriptor.to_string_with_secret(&keymap));
+    }
+
+    #[test]
+    fn parse_wif_with_origin() {
+        let secp = &secp256k1_zkp::Secp256k1::signing_only();
+        let wif = "cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy";
+        let descriptor_str = format!("elwpkh([aabbccdd/0']{})", wif);
+        let (descriptor, keymap) =
+            Descriptor::<DescriptorPublicKey>::parse_descriptor(secp, &descriptor_str).unwrap();
+
+        // The origin is kept on both the public and the secret key
+        let (public_key, secret_key) = keymap.iter().next().unwrap();
+        assert_eq!(public_key.master_fingerprint().to_string(), "aabbccdd");
+        assert_eq!(public_key.full_derivation_path().to_string(), "m/0'");
+        match *secret_key {
+            DescriptorSecretKey::SinglePriv(ref sk) => {
+                let (fingerprint, ref path) = *sk.origin.as_ref().unwrap();
+                assert_eq!(fingerprint.to_string(), "aabbccdd");
+                assert_eq!(path.to_string(), "m/0'");
             }
+            ref sk => panic!("unexpected secret key {}", sk),
         }
+        assert!(descriptor.to_string().starts_with("elwpkh([aabbccdd/0']"));
+        assert!(descriptor
+            .to_string_with_secret(&keymap)
+            .starts_with(&descriptor_str));
+    }
 
-        let satisfier = SimpleSat { sig, pk };
-        let ms = ms_str!("c:pk_k({})", pk);
+    #[test]
+    fn sortedmulti_at_index() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpubs = [
+            "tpubD6NzVbkrYhZ4WQdzxL7NmJN7b85ePo4p6RSj9QQHF7te2RR9iUeVSGgnGkoUsB9LBRosgvNbjRv9bcsJgzgBd7QKuxDm23ZewkTRzNSLEDr/*",
+            "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/*",
+        ];
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(sortedmulti(2,{},{}))",
+            xpubs[0], xpubs[1]
+        ))
+        .unwrap();
+        let smv = match desc {
+            Descriptor::Wsh(ref wsh) => match *wsh.as_inner() {
+                WshInner::SortedMulti(ref smv) => smv.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
 
-        let mut txin = elements::TxIn {
-            previous_output: elements::OutPoint::default(),
-            script_sig: Script::new(),
-            sequence: 100,
-            is_pegin: false,
-            has_issuance: false,
-            asset_issuance: elements::AssetIssuance::default(),
-            witness: elements::TxInWitness::default(),
+        // The first key at some index comes second at another
+        let first_key = |index: u32| {
+            let key = DescriptorPublicKey::from_str(xpubs[0]).unwrap();
+            key.derive(index).derive_public_key(&secp).unwrap()
         };
-        let bare = Descriptor::new_bare(ms.clone()).unwrap();
+        let swapped = (1..20)
+            .find(|&i| {
+                let sorted = smv.sorted_at_index(i, &secp).unwrap();
+                let sorted_0 = smv.sorted_at_index(0, &secp).unwrap();
+                (sorted[0] == first_key(i)) != (sorted_0[0] == first_key(0))
+            })
+            .unwrap();
+
+        // The scripts are made of the keys sorted at each index
+        for &index in &[0, swapped] {
+            let sorted = smv.sorted_at_index(index, &secp).unwrap();
+            let multi =
+                StdDescriptor::from_str(&format!("elwsh(multi(2,{},{}))", sorted[0], sorted[1]))
+                    .unwrap();
+            assert_eq!(
+                desc.derived_public_descriptor(index, &secp)
+                    .unwrap()
+                    .script_pubkey(),
+                multi.script_pubkey()
+            );
+        }
+    }
 
-        bare.satisfy(&mut txin, &satisfier).expect("satisfaction");
+    #[test]
+    fn spk_type() {
+        let pk = "020000000000000000000000000000000000000000000000000000000000000002";
+        let spk_ty = |s: String| StdDescriptor::from_str(&s).unwrap().spk_type();
+        assert_eq!(spk_ty(format!("elpk({})", pk)), ScriptPubKeyType::Bare);
+        assert_eq!(spk_ty(format!("elpkh({})", pk)), ScriptPubKeyType::P2pkh);
+        assert_eq!(spk_ty(format!("elwpkh({})", pk)), ScriptPubKeyType::P2wpkh);
         assert_eq!(
-            txin,
-            elements_txin(
-                script::Builder::new().push_slice(&sigser[..]).into_script(),
-                vec![]
-            ),
+            spk_ty(format!("elsh(wpkh({}))", pk)),
+            ScriptPubKeyType::P2sh
         );
-        assert_eq!(bare.unsigned_script_sig(), elements::Script::new());
-
-        let pkh = Descriptor::new_pkh(pk);
-        pkh.satisfy(&mut txin, &satisfier).expect("satisfaction");
         assert_eq!(
-            txin,
-            elements_txin(
-                script::Builder::new()
-                    .push_slice(&sigser[..])
-                    .push_key(&pk)
-                    .into_script(),
-                vec![]
-            )
+            spk_ty(format!("elsh(wsh(pk({})))", pk)),
+            ScriptPubKeyType::P2sh
         );
-        assert_eq!(pkh.unsigned_script_sig(), elements::Script::new());
+        assert_eq!(
+            spk_ty(format!("elsh(sortedmulti(1,{}))", pk)),
+            ScriptPubKeyType::P2sh
+        );
+        assert_eq!(
+            spk_ty(format!("elwsh(pk({}))", pk)),
+            ScriptPubKeyType::P2wsh
+        );
+        assert_eq!(
+            spk_ty(format!("elcovwsh({},pk({}))", pk, pk)),
+            ScriptPubKeyType::P2wsh
+        );
+    }
 
-        let wpkh = Descriptor::new_wpkh(pk).unwrap();
-        wpkh.satisfy(&mut txin, &satisfier).expect("satisfaction");
+    #[test]
+    fn signable_keys() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let tprv = "tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc";
+        let tpub = "[aabbccdd/1']tpubD6NzVbkrYhZ4WQdzxL7NmJN7b85ePo4p6RSj9QQHF7te2RR9iUeVSGgnGkoUsB9LBRosgvNbjRv9bcsJgzgBd7QKuxDm23ZewkTRzNSLEDr";
+        let wif = "cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy";
+        let wif_pk = bitcoin::PrivateKey::from_str(wif)
+            .unwrap()
+            .public_key(&secp);
+        let wif_fingerprint = hash160::Hash::hash(&wif_pk.to_bytes())[..4].to_hex();
+
+        let (desc, key_map) = Descriptor::parse_descriptor(
+            &secp,
+            &format!("elwsh(multi(1,{}/0/*,{}/0/*))", tpub, tpub),
+        )
+        .unwrap();
+        assert!(!desc.is_signable(&key_map));
+        assert!(desc.signable_keys(&key_map).is_empty());
+
+        let (desc, key_map) = Descriptor::parse_descriptor(
+            &secp,
+            &format!(
+                "elwsh(or_d(multi(2,{}/0/*,{},{}/1/*),pkh({})))",
+                tprv, tpub, tprv, wif
+            ),
+        )
+        .unwrap();
+        assert!(desc.is_signable(&key_map));
         assert_eq!(
-            txin,
-            elements_txin(Script::new(), vec![sigser.clone(), pk.to_bytes(),])
+            desc.signable_keys(&key_map)
+                .iter()
+                .map(|fp| fp.to_string())
+                .collect::<Vec<_>>(),
+            vec!["2cbe2a6d".to_owned(), wif_fingerprint]
         );
-        assert_eq!(wpkh.unsigned_script_sig(), elements::Script::new());
+        // Without the key map, the descriptor is watch-only
+        assert!(!desc.is_signable(&KeyMap::new()));
+    }
 
-        let shwpkh = Descriptor::new_sh_wpkh(pk).unwrap();
-        shwpkh.satisfy(&mut txin, &satisfier).expect("satisfaction");
-        let redeem_script = script::Builder::new()
-            .push_opcode(opcodes::all::OP_PUSHBYTES_0)
-            .push_slice(
-                &hash160::Hash::from_hex("d1b2a1faf62e73460af885c687dee3b7189cd8ab").unwrap()[..],
-            )
-            .into_script();
-        let expected_ssig = script::Builder::new()
-            .push_slice(&redeem_script[..])
-            .into_script();
+    #[test]
+    fn derive_keeps_xkeys() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let pk = "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8";
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(or_d(multi(1,[aabbccdd/1']{}/0/*,{}/1/*h),pk({})))",
+            xpub, xpub, pk
+        ))
+        .unwrap();
+        let derived = desc.derive(7);
+        assert!(!derived.is_deriveable());
+        let expected = format!(
+            "elwsh(or_d(multi(1,[aabbccdd/1']{}/0/7,{}/1/7'),pk({})))",
+            xpub, xpub, pk
+        );
         assert_eq!(
-            txin,
-            elements_txin(expected_ssig.clone(), vec![sigser.clone(), pk.to_bytes()])
+            derived.to_string(),
+            format!("{}#{}", expected, desc_checksum(&expected).unwrap())
         );
-        assert_eq!(shwpkh.unsigned_script_sig(), expected_ssig);
+        assert_eq!(
+            Descriptor::<DescriptorPublicKey>::from_str(&derived.to_string()).unwrap(),
+            derived
+        );
+        // Unlike the definite string, which resolves the keys
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(or_d(multi(1,[aabbccdd/1']{}/0/*,{}/1/*),pk({})))",
+            xpub, xpub, pk
+        ))
+        .unwrap();
+        let xkey = bip32::ExtendedPubKey::from_str(xpub).unwrap();
+        let child = |i: u32| {
+            let path = [
+                bip32::ChildNumber::from_normal_idx(i).unwrap(),
+                bip32::ChildNumber::from_normal_idx(7).unwrap(),
+            ];
+            xkey.derive_pub(&secp, &path).unwrap().public_key
+        };
+        let expected = format!("elwsh(or_d(multi(1,{},{}),pk({})))", child(0), child(1), pk);
+        assert_eq!(
+            desc.to_definite_string(7, &secp).unwrap(),
+            format!("{}#{}", expected, desc_checksum(&expected).unwrap())
+        );
+        assert!(desc.derive(7).to_string().contains(xpub));
+    }
 
-        let ms = ms_str!("c:pk_k({})", pk);
-        let sh = Descriptor::new_sh(ms.clone()).unwrap();
-        sh.satisfy(&mut txin, &satisfier).expect("satisfaction");
-        let expected_ssig = script::Builder::new()
-            .push_slice(&sigser[..])
-            .push_slice(&ms.encode()[..])
-            .into_script();
-        assert_eq!(txin, elements_txin(expected_ssig, vec![]));
-        assert_eq!(sh.unsigned_script_sig(), Script::new());
+    #[test]
+    fn derivation_standard() {
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let standard = |s: String| {
+            Descriptor::<DescriptorPublicKey>::from_str(&s)
+                .unwrap()
+                .derivation_standard()
+        };
 
-        let ms = ms_str!("c:pk_k({})", pk);
+        let cases = [
+            ("elpkh", "44'/1776'/0'", Some(DerivationStandard::Bip44)),
+            ("elsh(wpkh", "49'/1776'/0'", Some(DerivationStandard::Bip49)),
+            ("elwpkh", "84'/1776'/3'", Some(DerivationStandard::Bip84)),
+            // The path does not match the script type
+            ("elwpkh", "44'/1776'/0'", None),
+            ("elpkh", "84'/1776'/0'", None),
+            // The coin type and account must be hardened
+            ("elwpkh", "84'/1776'/0", None),
+            ("elwpkh", "84'/1776'", None),
+        ];
+        for &(script, path, expected) in cases.iter() {
+            let close = if script.contains('(') { "))" } else { ")" };
+            let desc = format!("{}([aabbccdd/{}]{}/0/*{}", script, path, xpub, close);
+            assert_eq!(standard(desc.clone()), expected, "{}", desc);
+        }
 
-        let wsh = Descriptor::new_wsh(ms.clone()).unwrap();
-        wsh.satisfy(&mut txin, &satisfier).expect("satisfaction");
+        // Keys without origin can be standard through their own path
         assert_eq!(
-            txin,
-            elements_txin(
-                Script::new(),
-                vec![sigser.clone(), ms.encode().into_bytes()]
-            )
+            standard(format!("elwpkh({}/84'/1776'/0'/0/*)", xpub)),
+            Some(DerivationStandard::Bip84)
         );
-        assert_eq!(wsh.unsigned_script_sig(), Script::new());
+        assert_eq!(standard(format!("elwpkh({}/0/*)", xpub)), None);
+        // Multisig descriptors follow none of the standards
+        assert_eq!(
+            standard(format!(
+                "elwsh(multi(1,[aabbccdd/84'/1776'/0']{}/0/*))",
+                xpub
+            )),
+            None
+        );
+        assert_eq!(
+            DerivationStandard::Bip84.to_string(),
+            "Native SegWit (BIP-84)"
+        );
+    }
 
-        let shwsh = Descriptor::new_sh_wsh(ms.clone()).unwrap();
-        shwsh.satisfy(&mut txin, &satisfier).expect("satisfaction");
-        let expected_ssig = script::Builder::new()
-            .push_slice(&ms.encode().to_v0_p2wsh()[..])
-            .into_script();
+    #[test]
+    fn signer_fingerprints() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let pk = "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8";
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(or_d(multi(2,[aabbccdd/1']{}/0/*,[aabbccdd/2']{}/0/*,{}/1/*),pkh({})))",
+            xpub, xpub, xpub, pk
+        ))
+        .unwrap();
+        let xpub_fingerprint = bip32::ExtendedPubKey::from_str(xpub).unwrap().fingerprint();
+        let pk_fingerprint =
+            bip32::Fingerprint::from(&hash160::Hash::hash(&Vec::<u8>::from_hex(pk).unwrap())[..4]);
+        let expected: BTreeSet<_> = vec![
+            bip32::Fingerprint::from_str("aabbccdd").unwrap(),
+            xpub_fingerprint,
+            pk_fingerprint,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(desc.signer_fingerprints(), expected);
+
+        // Secret keys are identified like their public key
+        let tprv = "tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc";
+        let (desc, _) =
+            Descriptor::parse_descriptor(&secp, &format!("elwpkh({}/0/*)", tprv)).unwrap();
+        let root = bip32::ExtendedPrivKey::from_str(tprv).unwrap();
         assert_eq!(
-            txin,
-            elements_txin(
-                expected_ssig.clone(),
-                vec![sigser.clone(), ms.encode().into_bytes(),]
-            )
+            desc.signer_fingerprints().into_iter().collect::<Vec<_>>(),
+            vec![root.fingerprint(&secp)]
         );
-        assert_eq!(shwsh.unsigned_script_sig(), expected_ssig);
     }
 
     #[test]
-    fn after_is_cltv() {
-        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str("elwsh(after(1000))").unwrap();
-        let script = descriptor.explicit_script();
+    fn dust_value() {
+        let pk = "020000000000000000000000000000000000000000000000000000000000000002";
+        let desc = StdDescriptor::from_str(&format!("elwpkh({})", pk)).unwrap();
+        // Output: 4 * 66 + 2 witness bytes, input: 4 * 40 + 3 + 112 for the
+        // satisfaction, for a total of 541 weight units or 136 vbytes
+        assert_eq!(desc.max_satisfaction_weight().unwrap(), 112);
+        assert_eq!(desc.dust_value(1000).unwrap(), 136);
+        assert_eq!(desc.dust_value(3000).unwrap(), 408);
+        assert_eq!(desc.dust_value(100).unwrap(), 13);
+        assert_eq!(desc.dust_value(0).unwrap(), 0);
+
+        // Confidential outputs are much more expensive
+        let blinded = Blinded::new(PublicKey::from_str(pk).unwrap(), desc.clone());
+        // Output: 4 * 122 + 68 + 4177 witness bytes
+        assert_eq!(blinded.dust_value(1000).unwrap(), 1252);
+
+        // Larger satisfactions have a higher dust value
+        let multi = StdDescriptor::from_str(&format!("elwsh(multi(1,{},{}))", pk, pk)).unwrap();
+        assert!(multi.dust_value(1000).unwrap() > desc.dust_value(1000).unwrap());
+        let unsat = StdDescriptor::from_str("elwsh(0)").unwrap();
+        assert!(unsat.dust_value(1000).is_err());
+    }
 
-        let actual_instructions: Vec<_> = script.instructions().collect();
-        let check = actual_instructions.last().unwrap();
+    #[test]
+    fn witness_scaffold_weight() {
+        let pks = [
+            "020000000000000000000000000000000000000000000000000000000000000002",
+            "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        ];
+        let hash = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        // Descriptor, signatures and preimages in the witness, signatures in the scriptSig
+        let cases = [
+            (format!("elwpkh({})", pks[0]), 1, 0, 0),
+            (format!("elsh(wpkh({}))", pks[0]), 1, 0, 0),
+            (format!("elpkh({})", pks[0]), 0, 0, 1),
+            (format!("elwsh(multi(2,{},{}))", pks[0], pks[1]), 2, 0, 0),
+            (format!("elsh(multi(2,{},{}))", pks[0], pks[1]), 0, 0, 2),
+            (
+                format!("elsh(wsh(and_v(v:pk({}),sha256({}))))", pks[0], hash),
+                1,
+                1,
+                0,
+            ),
+            (
+                format!(
+                    "elwsh(or_d(pk({}),and_v(v:pk({}),older(144))))",
+                    pks[0], pks[1]
+                ),
+                1,
+                0,
+                0,
+            ),
+        ];
+        for &(ref s, wit_sigs, preimages, script_sig_sigs) in cases.iter() {
+            let desc = StdDescriptor::from_str(s).unwrap();
+            let scaffold = desc.witness_scaffold_weight().unwrap();
+            assert_eq!(
+                scaffold + 73 * wit_sigs + 33 * preimages + 4 * 73 * script_sig_sigs,
+                desc.max_satisfaction_weight().unwrap(),
+                "{}",
+                s
+            );
+        }
 
-        assert_eq!(check, &Ok(Instruction::Op(OP_CLTV)))
+        let desc = StdDescriptor::from_str(&format!("elwsh(multi(1,{}))", pks[0])).unwrap();
+        // Witness count, multisig dummy and witness script
+        let witness_script_len = 1 + 34 + 1 + 1;
+        assert_eq!(
+            desc.witness_scaffold_weight().unwrap(),
+            4 + 1 + 1 + 1 + witness_script_len
+        );
     }
 
     #[test]
-    fn older_is_csv() {
-        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str("elwsh(older(1000))").unwrap();
-        let script = descriptor.explicit_script();
+    fn satisfy_with_dummy() {
+        let pks = [
+            "020000000000000000000000000000000000000000000000000000000000000002",
+            "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        ];
+        let hash = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        for s in [
+            format!("elwpkh({})", pks[0]),
+            format!("elsh(wpkh({}))", pks[0]),
+            format!("elpkh({})", pks[0]),
+            format!("elsh(multi(2,{},{}))", pks[0], pks[1]),
+            format!("elsh(wsh(and_v(v:pk({}),sha256({}))))", pks[0], hash),
+            format!(
+                "elwsh(or_d(pk({}),and_v(v:pk({}),older(144))))",
+                pks[0], pks[1]
+            ),
+        ]
+        .iter()
+        {
+            let desc = StdDescriptor::from_str(s).unwrap();
+            let mut txin = elements_txin(Script::new(), vec![]);
+            desc.satisfy_with_dummy(&mut txin).unwrap();
+            assert_eq!(
+                satisfaction_weight(&txin.witness.script_witness, &txin.script_sig, |_| false),
+                desc.max_satisfaction_weight().unwrap(),
+                "{}",
+                s
+            );
+        }
 
-        let actual_instructions: Vec<_> = script.instructions().collect();
-        let check = actual_instructions.last().unwrap();
+        // Signatures and preimages have their real sizes
+        let desc =
+            StdDescriptor::from_str(&format!("elwsh(and_v(v:pk({}),sha256({})))", pks[0], hash))
+                .unwrap();
+        let mut txin = elements_txin(Script::new(), vec![]);
+        desc.satisfy_with_dummy(&mut txin).unwrap();
+        let lens: Vec<_> = txin.witness.script_witness.iter().map(Vec::len).collect();
+        assert_eq!(lens, vec![32, 72, desc.explicit_script().len()]);
 
-        assert_eq!(check, &Ok(Instruction::Op(OP_CSV)))
+        let desc =
+            StdDescriptor::from_str(&format!("elcovwsh({},pk({}))", pks[0], pks[1])).unwrap();
+        assert!(desc
+            .satisfy_with_dummy(&mut elements_txin(Script::new(), vec![]))
+            .is_err());
     }
 
     #[test]
-    fn roundtrip_tests() {
-        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str("elmulti");
+    fn max_weight_to_satisfy() {
+        let pks = [
+            "020000000000000000000000000000000000000000000000000000000000000002",
+            "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+            "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8",
+        ];
+        let weight = |s: String| {
+            StdDescriptor::from_str(&s)
+                .unwrap()
+                .max_weight_to_satisfy()
+                .unwrap()
+        };
+
+        // The witness script is 1 + 3 * 34 + 1 + 1 = 105 bytes, and the
+        // witness the empty dummy, two signatures and the witness script
+        let multi = format!("multi(2,{},{},{})", pks[0], pks[1], pks[2]);
         assert_eq!(
-            descriptor.unwrap_err().to_string(),
-            "unexpected «no arguments given»"
-        )
-    }
+            weight(format!("elwsh({})", multi)),
+            (1 + 1 + 2 * 73 + 1 + 105) - 1
+        );
+        // The redeem script push only counts once in the scriptSig
+        assert_eq!(
+            weight(format!("elsh({})", multi)),
+            4 * (2 + 1 + 2 * 73 + 2 + 105)
+        );
+        assert_eq!(weight(format!("elpkh({})", pks[0])), 4 * (73 + 34));
+        assert_eq!(weight(format!("elwpkh({})", pks[0])), 1 + 73 + 34 - 1);
+        // The 22 byte witness program is pushed in the scriptSig
+        assert_eq!(
+            weight(format!("elsh(wpkh({}))", pks[0])),
+            4 * 23 + 1 + 73 + 34 - 1
+        );
 
-    #[test]
-    fn empty_thresh() {
-        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str("elthresh");
+        // The satisfaction excludes the weight of the unsatisfied input
+        let desc = StdDescriptor::from_str(&format!("elwsh({})", multi)).unwrap();
         assert_eq!(
-            descriptor.unwrap_err().to_string(),
-            "unexpected «no arguments given»"
-        )
+            desc.max_satisfaction_weight().unwrap(),
+            desc.max_weight_to_satisfy().unwrap() + 4 + 1
+        );
     }
 
     #[test]
-    fn witness_stack_for_andv_is_arranged_in_correct_order() {
-        // arrange
-        let a = bitcoin::PublicKey::from_str(
-            "02937402303919b3a2ee5edd5009f4236f069bf75667b8e6ecf8e5464e20116a0e",
-        )
-        .unwrap();
-        let sig_a = secp256k1_zkp::Signature::from_str("3045022100a7acc3719e9559a59d60d7b2837f9842df30e7edcd754e63227e6168cec72c5d022066c2feba4671c3d99ea75d9976b4da6c86968dbf3bab47b1061e7a1966b1778c").unwrap();
+    fn max_witness_items() {
+        let pks = [
+            "020000000000000000000000000000000000000000000000000000000000000002",
+            "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        ];
+        let cases = [
+            (format!("elpkh({})", pks[0]), 0),
+            (format!("elsh(multi(2,{},{}))", pks[0], pks[1]), 0),
+            (format!("elwpkh({})", pks[0]), 2),
+            (format!("elsh(wpkh({}))", pks[0]), 2),
+            (format!("elwsh(sortedmulti(2,{},{}))", pks[0], pks[1]), 4),
+            (format!("elsh(wsh(multi(2,{},{})))", pks[0], pks[1]), 4),
+            // The dissatisfaction of pk(A) along with the signature of B
+            (
+                format!(
+                    "elwsh(or_d(pk({}),and_v(v:pk({}),older(144))))",
+                    pks[0], pks[1]
+                ),
+                3,
+            ),
+            (format!("elcovwsh({},pk({}))", pks[0], pks[1]), 14),
+        ];
+        for &(ref s, items) in cases.iter() {
+            let desc = StdDescriptor::from_str(s).unwrap();
+            assert_eq!(desc.max_witness_items().unwrap(), items, "{}", s);
+        }
 
-        let b = bitcoin::PublicKey::from_str(
-            "02eb64639a17f7334bb5a1a3aad857d6fec65faef439db3de72f85c88bc2906ad3",
-        )
-        .unwrap();
-        let sig_b = secp256k1_zkp::Signature::from_str("3044022075b7b65a7e6cd386132c5883c9db15f9a849a0f32bc680e9986398879a57c276022056d94d12255a4424f51c700ac75122cb354895c9f2f88f0cbb47ba05c9c589ba").unwrap();
+        // The dummy satisfactions reach the bound
+        for s in &[
+            format!("elwsh(sortedmulti(2,{},{}))", pks[0], pks[1]),
+            format!(
+                "elwsh(or_d(pk({}),and_v(v:pk({}),older(144))))",
+                pks[0], pks[1]
+            ),
+        ] {
+            let desc = StdDescriptor::from_str(s).unwrap();
+            let mut txin = elements_txin(Script::new(), vec![]);
+            desc.satisfy_with_dummy(&mut txin).unwrap();
+            assert_eq!(
+                txin.witness.script_witness.len(),
+                desc.max_witness_items().unwrap()
+            );
+        }
+    }
 
-        let descriptor = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
-            "elwsh(and_v(v:pk({A}),pk({B})))",
-            A = a,
-            B = b
+    #[test]
+    fn satisfaction_weight_range() {
+        let pks = [
+            "020000000000000000000000000000000000000000000000000000000000000002",
+            "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        ];
+        let hash = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+
+        let desc = StdDescriptor::from_str(&format!("elwpkh({})", pks[0])).unwrap();
+        let max = desc.max_satisfaction_weight().unwrap();
+        assert_eq!(desc.satisfaction_weight_range().unwrap(), (max - 1, max));
+        assert!(!desc.witness_size_is_deterministic());
+
+        // Signatures in the scriptSig weigh four times as much
+        let desc =
+            StdDescriptor::from_str(&format!("elsh(multi(2,{},{}))", pks[0], pks[1])).unwrap();
+        let max = desc.max_satisfaction_weight().unwrap();
+        assert_eq!(desc.satisfaction_weight_range().unwrap(), (max - 8, max));
+
+        // The lightest path has a single signature
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(or_d(pk({}),and_v(v:pk({}),older(144))))",
+            pks[0], pks[1]
         ))
         .unwrap();
+        let weights = desc.path_weights().unwrap();
+        assert_eq!(
+            desc.satisfaction_weight_range().unwrap(),
+            (weights[0].1 - 1, desc.max_satisfaction_weight().unwrap())
+        );
 
-        let mut txin = elements_txin(Script::new(), vec![]);
-        let satisfier = {
-            let mut satisfier = HashMap::with_capacity(2);
+        // Without signatures the size only depends on the path
+        let desc = StdDescriptor::from_str(&format!("elwsh(sha256({}))", hash)).unwrap();
+        assert!(desc.witness_size_is_deterministic());
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(or_i(sha256({}),and_v(v:sha256({}),older(10))))",
+            hash, hash
+        ))
+        .unwrap();
+        let (min, max) = desc.satisfaction_weight_range().unwrap();
+        assert!(min < max);
+        assert!(!desc.witness_size_is_deterministic());
 
-            satisfier.insert(a, (sig_a.clone(), ::elements::SigHashType::All));
-            satisfier.insert(b, (sig_b.clone(), ::elements::SigHashType::All));
+        let desc =
+            StdDescriptor::from_str(&format!("elcovwsh({},pk({}))", pks[0], pks[1])).unwrap();
+        assert!(desc.satisfaction_weight_range().is_err());
+        assert!(!desc.witness_size_is_deterministic());
+    }
 
-            satisfier
-        };
+    #[test]
+    fn path_weights() {
+        let pks = [
+            "020000000000000000000000000000000000000000000000000000000000000002",
+            "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        ];
+        // Descriptors with a single spending path
+        for s in [
+            format!("elwpkh({})", pks[0]),
+            format!("elsh(wpkh({}))", pks[0]),
+            format!("elwsh(multi(2,{},{}))", pks[0], pks[1]),
+            format!("elsh(sortedmulti(1,{},{}))", pks[0], pks[1]),
+            format!(
+                "elwsh(and_v(v:pk({}),sha256({})))",
+                pks[0], "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+            ),
+        ]
+        .iter()
+        {
+            let desc = StdDescriptor::from_str(s).unwrap();
+            assert_eq!(
+                desc.path_weights().unwrap(),
+                vec![(vec![], desc.max_satisfaction_weight().unwrap())],
+                "{}",
+                s
+            );
+        }
 
-        // act
-        descriptor.satisfy(&mut txin, &satisfier).unwrap();
+        let desc = StdDescriptor::from_str(&format!(
+            "elwsh(or_d(pk({}),and_v(v:pk({}),older(144))))",
+            pks[0], pks[1]
+        ))
+        .unwrap();
+        let weights = desc.path_weights().unwrap();
+        assert_eq!(weights.len(), 2);
+        assert_eq!(weights[0].0, vec![0]);
+        assert_eq!(weights[1].0, vec![1]);
+        // The timelocked path also dissatisfies the first key
+        assert!(weights[0].1 < weights[1].1);
+        assert_eq!(weights[1].1, desc.max_satisfaction_weight().unwrap());
+
+        // Nested disjunctions, with a key only known by its hash
+        let desc = StdDescriptor::from_str(&format!(
+            "elsh(wsh(andor(pk({}),older(10),or_d(pk({}),pkh({})))))",
+            pks[0], pks[1], "1111111111111111111111111111111111111111"
+        ))
+        .unwrap();
+        let weights = desc.path_weights().unwrap();
+        let paths: Vec<_> = weights.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(paths, vec![vec![0], vec![1, 0], vec![1, 1]]);
+        let max = weights.iter().map(|&(_, w)| w).max().unwrap();
+        assert_eq!(max, desc.max_satisfaction_weight().unwrap());
+
+        // Unsatisfiable branches are skipped
+        let desc = StdDescriptor::from_str(&format!("elwsh(l:pk({}))", pks[0])).unwrap();
+        let weights = desc.path_weights().unwrap();
+        assert_eq!(weights.len(), 1);
+        assert_eq!(weights[0].0, vec![1]);
 
-        // assert
-        let witness0 = &txin.witness.script_witness[0];
-        let witness1 = &txin.witness.script_witness[1];
+        let desc =
+            StdDescriptor::from_str(&format!("elcovwsh({},pk({}))", pks[0], pks[1])).unwrap();
+        assert!(desc.path_weights().is_err());
+    }
 
-        let sig0 = secp256k1_zkp::Signature::from_der(&witness0[..witness0.len() - 1]).unwrap();
-        let sig1 = secp256k1_zkp::Signature::from_der(&witness1[..witness1.len() - 1]).unwrap();
+    #[test]
+    fn scriptpubkey_count() {
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let desc =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/0)", xpub)).unwrap();
+        assert_eq!(desc.scriptpubkey_count(0..100), 1);
+        let desc =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/0/*)", xpub)).unwrap();
+        assert_eq!(desc.scriptpubkey_count(0..100), 100);
+        assert_eq!(desc.scriptpubkey_count(Range { start: 20, end: 10 }), 0);
+        assert_eq!(desc.scriptpubkey_count((1 << 31) - 5..u32::max_value()), 5);
 
-        // why are we asserting this way?
-        // The witness stack is evaluated from top to bottom. Given an `and` instruction, the left arm of the and is going to evaluate first,
-        // meaning the next witness element (on a three element stack, that is the middle one) needs to be the signature for the left side of the `and`.
-        // The left side of the `and` performs a CHECKSIG against public key `a` so `sig1` needs to be `sig_a` and `sig0` needs to be `sig_b`.
-        assert_eq!(sig1, sig_a);
-        assert_eq!(sig0, sig_b);
+        // One scriptPubKey per derivation path at every index
+        let desc =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/<0;1>/*)", xpub))
+                .unwrap();
+        assert_eq!(desc.scriptpubkey_count(0..100), 200);
+        assert_eq!(desc.scriptpubkey_count(Range { start: 20, end: 10 }), 0);
+        let desc =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/<0;1;2>)", xpub))
+                .unwrap();
+        assert_eq!(desc.scriptpubkey_count(0..100), 3);
     }
 
     #[test]
-    fn test_scriptcode() {
-        // P2WPKH (from bip143 test vectors)
-        let descriptor = Descriptor::<PublicKey>::from_str(
-            "elwpkh(025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357)",
-        )
+    fn network_consistency() {
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let tpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed";
+        let single = "020000000000000000000000000000000000000000000000000000000000000002";
+
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(2,{}/0/*,{},{}/1/*))",
+            xpub, single, xpub
+        ))
         .unwrap();
         assert_eq!(
-            *descriptor.script_code().as_bytes(),
-            Vec::<u8>::from_hex("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap()[..]
+            desc.network_consistency().unwrap(),
+            Some(bitcoin::Network::Bitcoin)
         );
-
-        // P2SH-P2WPKH (from bip143 test vectors)
-        let descriptor = Descriptor::<PublicKey>::from_str(
-            "elsh(wpkh(03ad1d8e89212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a26873))",
-        )
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(1,{},{}/<0;1>/*))",
+            single, tpub
+        ))
         .unwrap();
         assert_eq!(
-            *descriptor.script_code().as_bytes(),
-            Vec::<u8>::from_hex("76a91479091972186c449eb1ded22b78e40d009bdf008988ac").unwrap()[..]
+            desc.network_consistency().unwrap(),
+            Some(bitcoin::Network::Testnet)
         );
+        let desc =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({})", single)).unwrap();
+        assert_eq!(desc.network_consistency().unwrap(), None);
 
-        // P2WSH (from bitcoind's `createmultisig`)
-        let descriptor = Descriptor::<PublicKey>::from_str(
-            "elwsh(multi(2,03789ed0bb717d88f7d321a368d905e7430207ebbd82bd342cf11ae157a7ace5fd,03dbc6764b8884a92e871274b87583e6d5c2a58819473e17e107ef3f6aa5a61626))",
-        )
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(2,{}/0/*,{},{}/0/*))",
+            xpub, single, tpub
+        ))
         .unwrap();
+        match desc.network_consistency() {
+            Err(Error::BadDescriptor(e)) => {
+                assert!(e.contains(&format!("{}/0/* (bitcoin)", xpub)));
+                assert!(e.contains(&format!("{}/0/* (testnet)", tpub)));
+                assert!(!e.contains(single));
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn hardened_marker_display() {
+        let s = "elwsh(multi(1,[d34db33f/48h/1h/0h/2h]tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/0/*))";
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(s).unwrap();
+        let h = desc.to_string_with_hardened_marker(HardenedMarker::H);
+        assert_eq!(h, format!("{}#{}", s, desc_checksum(s).unwrap()));
+        // The checksum is valid, and the descriptor round-trips
         assert_eq!(
-            *descriptor
-                .script_code()
-                .as_bytes(),
-            Vec::<u8>::from_hex("522103789ed0bb717d88f7d321a368d905e7430207ebbd82bd342cf11ae157a7ace5fd2103dbc6764b8884a92e871274b87583e6d5c2a58819473e17e107ef3f6aa5a6162652ae").unwrap()[..]
+            Descriptor::<DescriptorPublicKey>::from_str(&h).unwrap(),
+            desc
         );
-
-        // P2SH-P2WSH (from bitcoind's `createmultisig`)
-        let descriptor = Descriptor::<PublicKey>::from_str("elsh(wsh(multi(2,03789ed0bb717d88f7d321a368d905e7430207ebbd82bd342cf11ae157a7ace5fd,03dbc6764b8884a92e871274b87583e6d5c2a58819473e17e107ef3f6aa5a61626)))").unwrap();
         assert_eq!(
-            *descriptor
-                .script_code()
-                .as_bytes(),
-            Vec::<u8>::from_hex("522103789ed0bb717d88f7d321a368d905e7430207ebbd82bd342cf11ae157a7ace5fd2103dbc6764b8884a92e871274b87583e6d5c2a58819473e17e107ef3f6aa5a6162652ae")
-                .unwrap()[..]
+            desc.to_string_with_hardened_marker(HardenedMarker::Apostrophe),
+            desc.to_string()
         );
+        assert!(desc.to_string().contains("/48'/1'/0'/2'"));
+
+        // The hardened wildcard follows the marker as well
+        let desc =
+            Descriptor::<DescriptorPublicKey>::from_str(&s.replace("/0/*", "/0/*h")).unwrap();
+        let apostrophe = desc.to_string_with_hardened_marker(HardenedMarker::Apostrophe);
+        assert!(apostrophe.contains("/48'/1'/0'/2'") && apostrophe.contains("/0/*'"));
+        let h = desc.to_string_with_hardened_marker(HardenedMarker::H);
+        assert!(h.contains("/48h/1h/0h/2h") && h.contains("/0/*h"));
     }
 
     #[test]
-    fn parse_descriptor_key() {
-        // With a wildcard
-        let key = "[78412e3a/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*";
-        let expected = DescriptorPublicKey::XPub(DescriptorXKey {
-            origin: Some((
-                bip32::Fingerprint::from(&[0x78, 0x41, 0x2e, 0x3a][..]),
-                (&[
-                    bip32::ChildNumber::from_hardened_idx(44).unwrap(),
-                    bip32::ChildNumber::from_hardened_idx(0).unwrap(),
-                    bip32::ChildNumber::from_hardened_idx(0).unwrap(),
-                ][..])
-                .into(),
-            )),
-            xkey: bip32::ExtendedPubKey::from_str("xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL").unwrap(),
-            derivation_path: (&[bip32::ChildNumber::from_normal_idx(1).unwrap()][..]).into(),
-            wildcard: Wildcard::Unhardened,
-        });
-        assert_eq!(expected, key.parse().unwrap());
-        assert_eq!(format!("{}", expected), key);
+    fn satisfying_key_sets() {
+        let keys: Vec<DescriptorPublicKey> = [
+            "020000000000000000000000000000000000000000000000000000000000000002",
+            "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        ]
+        .iter()
+        .map(|k| DescriptorPublicKey::from_str(k).unwrap())
+        .collect();
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(2,{},{},{}))",
+            keys[0], keys[1], keys[2]
+        ))
+        .unwrap();
+        let pair = |a: usize, b: usize| -> HashSet<DescriptorPublicKey> {
+            vec![keys[a].clone(), keys[b].clone()].into_iter().collect()
+        };
+        assert_eq!(
+            desc.satisfying_key_sets().unwrap(),
+            vec![pair(0, 1), pair(0, 2), pair(1, 2)]
+        );
 
-        // Without origin
-        let key = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1";
-        let expected = DescriptorPublicKey::XPub(DescriptorXKey {
-            origin: None,
-            xkey: bip32::ExtendedPubKey::from_str("xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL").unwrap(),
-            derivation_path: (&[bip32::ChildNumber::from_normal_idx(1).unwrap()][..]).into(),
-            wildcard: Wildcard::None,
-        });
-        assert_eq!(expected, key.parse().unwrap());
-        assert_eq!(format!("{}", expected), key);
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(or_d(pk({}),and_v(v:pk({}),older(144))))",
+            keys[0], keys[1]
+        ))
+        .unwrap();
+        let single: HashSet<_> = vec![keys[0].clone()].into_iter().collect();
+        let other: HashSet<_> = vec![keys[1].clone()].into_iter().collect();
+        assert_eq!(desc.satisfying_key_sets().unwrap(), vec![single, other]);
+    }
 
-        // Testnet tpub
-        let key = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/1";
-        let expected = DescriptorPublicKey::XPub(DescriptorXKey {
-            origin: None,
-            xkey: bip32::ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed").unwrap(),
-            derivation_path: (&[bip32::ChildNumber::from_normal_idx(1).unwrap()][..]).into(),
-            wildcard: Wildcard::None,
-        });
-        assert_eq!(expected, key.parse().unwrap());
-        assert_eq!(format!("{}", expected), key);
+    #[test]
+    fn checksum_for_nested_sh() {
+        let descriptor_str = "elsh(wpkh(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL))";
+        let descriptor: Descriptor<DescriptorPublicKey> = descriptor_str.parse().unwrap();
+        assert_eq!(descriptor.to_string(), "elsh(wpkh(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL))#2040pn7l");
 
-        // Without derivation path
-        let key = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
-        let expected = DescriptorPublicKey::XPub(DescriptorXKey {
-            origin: None,
-            xkey: bip32::ExtendedPubKey::from_str("xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL").unwrap(),
-            derivation_path: bip32::DerivationPath::from(&[][..]),
-            wildcard: Wildcard::None,
-        });
-        assert_eq!(expected, key.parse().unwrap());
-        assert_eq!(format!("{}", expected), key);
+        let descriptor_str = "elsh(wsh(pk(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL)))";
+        let descriptor: Descriptor<DescriptorPublicKey> = descriptor_str.parse().unwrap();
+        assert_eq!(descriptor.to_string(), "elsh(wsh(pk(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL)))#pqs0de7e");
+    }
 
-        // Raw (compressed) pubkey
-        let key = "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8";
-        let expected = DescriptorPublicKey::SinglePub(DescriptorSinglePub {
-            key: bitcoin::PublicKey::from_str(
-                "03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8",
-            )
-            .unwrap(),
-            origin: None,
-        });
-        assert_eq!(expected, key.parse().unwrap());
-        assert_eq!(format!("{}", expected), key);
+    #[test]
+    fn timelocked_recovery() {
+        let primary = bitcoin::PublicKey::from_str(
+            "020000000000000000000000000000000000000000000000000000000000000002",
+        )
+        .unwrap();
+        let recovery = bitcoin::PublicKey::from_str(
+            "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        )
+        .unwrap();
+        let desc = Descriptor::timelocked_recovery(primary, recovery, 4032).unwrap();
+        assert_eq!(
+            desc,
+            Descriptor::from_str(&format!(
+                "elwsh(or_d(pk({}),and_v(v:older(4032),pk({}))))",
+                primary, recovery
+            ))
+            .unwrap()
+        );
+
+        assert!(Descriptor::timelocked_recovery(primary, recovery, 0).is_err());
+        assert!(Descriptor::timelocked_recovery(primary, recovery, 0x10000).is_err());
+        // Time-based relative locktimes are not block counts
+        assert!(Descriptor::timelocked_recovery(primary, recovery, (1 << 22) | 1).is_err());
+    }
 
-        // Raw (uncompressed) pubkey
-        let key = "04f5eeb2b10c944c6b9fbcfff94c35bdeecd93df977882babc7f3a2cf7f5c81d3b09a68db7f0e04f21de5d4230e75e6dbe7ad16eefe0d4325a62067dc6f369446a";
-        let expected = DescriptorPublicKey::SinglePub(DescriptorSinglePub {
-            key: bitcoin::PublicKey::from_str(
-                "04f5eeb2b10c944c6b9fbcfff94c35bdeecd93df977882babc7f3a2cf7f5c81d3b09a68db7f0e04f21de5d4230e75e6dbe7ad16eefe0d4325a62067dc6f369446a",
-            )
-            .unwrap(),
-            origin: None,
+    #[test]
+    fn pset_sighash_message() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[3; 32]).unwrap();
+        let pk = bitcoin::PublicKey {
+            key: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+            compressed: true,
+        };
+        let desc = Descriptor::new_wpkh(pk).unwrap();
+
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![elements::TxIn {
+                previous_output: elements::OutPoint::default(),
+                is_pegin: false,
+                has_issuance: false,
+                script_sig: Script::new(),
+                sequence: 0xfffffffe,
+                asset_issuance: Default::default(),
+                witness: Default::default(),
+            }],
+            output: vec![elements::TxOut {
+                script_pubkey: desc.script_pubkey(),
+                value: elements::confidential::Value::Explicit(90_000),
+                ..Default::default()
+            }],
+        };
+        let mut pset = Pset::from_tx(tx);
+        assert!(desc.sighash_message(&pset, 0).is_err());
+        pset.inputs[0].witness_utxo = Some(elements::TxOut {
+            script_pubkey: desc.script_pubkey(),
+            value: elements::confidential::Value::Explicit(100_000),
+            ..Default::default()
         });
-        assert_eq!(expected, key.parse().unwrap());
-        assert_eq!(format!("{}", expected), key);
+        pset.inputs[0].sighash_type = Some(elements::SigHashType::AllPlusAnyoneCanPay);
 
-        // Raw pubkey with origin
-        let desc =
-            "[78412e3a/0'/42/0']0231c7d3fc85c148717848033ce276ae2b464a4e2c367ed33886cc428b8af48ff8";
-        let expected = DescriptorPublicKey::SinglePub(DescriptorSinglePub {
-            key: bitcoin::PublicKey::from_str(
-                "0231c7d3fc85c148717848033ce276ae2b464a4e2c367ed33886cc428b8af48ff8",
-            )
-            .unwrap(),
-            origin: Some((
-                bip32::Fingerprint::from(&[0x78, 0x41, 0x2e, 0x3a][..]),
-                (&[
-                    bip32::ChildNumber::from_hardened_idx(0).unwrap(),
-                    bip32::ChildNumber::from_normal_idx(42).unwrap(),
-                    bip32::ChildNumber::from_hardened_idx(0).unwrap(),
-                ][..])
-                    .into(),
-            )),
-        });
-        assert_eq!(expected, desc.parse().expect("Parsing desc"));
-        assert_eq!(format!("{}", expected), desc);
+        let other = Descriptor::new_pkh(pk);
+        match other.sighash_message(&pset, 0) {
+            Err(pset::Error::InputError(pset::InputError::InvalidScriptPubkey { .. }, 0)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // A signature over the message finalizes the input
+        let (msg, sighash_type) = desc.sighash_message(&pset, 0).unwrap();
+        assert_eq!(sighash_type, elements::SigHashType::AllPlusAnyoneCanPay);
+        let mut sig = secp.sign(&msg, &sk).serialize_der().to_vec();
+        sig.push(sighash_type as u8);
+        pset.inputs[0].partial_sigs.insert(pk, sig);
+        pset::finalize(&mut pset, &secp).unwrap();
     }
 
     #[test]
-    fn test_sortedmulti() {
-        fn _test_sortedmulti(raw_desc_one: &str, raw_desc_two: &str, raw_addr_expected: &str) {
-            let secp_ctx = secp256k1_zkp::Secp256k1::verification_only();
-            let index = 5;
+    fn matches_spk() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let desc = Descriptor::<DescriptorPublicKey>::from_str("elwpkh(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*)").unwrap();
+        let spk = |index| {
+            desc.derive(index)
+                .translate_pk2(|xpk| xpk.derive_public_key(&secp))
+                .unwrap()
+                .script_pubkey()
+        };
 
-            // Parse descriptor
-            let mut desc_one = Descriptor::<DescriptorPublicKey>::from_str(raw_desc_one).unwrap();
-            let mut desc_two = Descriptor::<DescriptorPublicKey>::from_str(raw_desc_two).unwrap();
+        assert!(desc.matches_spk(&spk(7), 7, &secp));
+        assert!(!desc.matches_spk(&spk(7), 6, &secp));
+        assert_eq!(desc.matches_spk_first_two(&spk(0), &secp), Some(0));
+        assert_eq!(desc.matches_spk_first_two(&spk(1), &secp), Some(1));
+        assert_eq!(desc.matches_spk_first_two(&spk(2), &secp), None);
 
-            // Same string formatting
-            assert_eq!(desc_one.to_string(), raw_desc_one);
-            assert_eq!(desc_two.to_string(), raw_desc_two);
+        // Hardened steps cannot be derived from public keys
+        let hardened = Descriptor::<DescriptorPublicKey>::from_str("elwpkh(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*h)").unwrap();
+        assert!(!hardened.matches_spk(&spk(0), 0, &secp));
+    }
 
-            // Derive a child if the descriptor is ranged
-            if raw_desc_one.contains("*") && raw_desc_two.contains("*") {
-                desc_one = desc_one.derive(index);
-                desc_two = desc_two.derive(index);
-            }
+    #[test]
+    fn script_hash() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        // Electrum protocol documentation example, the genesis coinbase key
+        let desc = Descriptor::<DescriptorPublicKey>::from_str("elpkh(04678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f)").unwrap();
+        assert_eq!(
+            desc.script_hash(0, &secp).unwrap().to_string(),
+            "8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161"
+        );
+        assert_eq!(desc.witness_script_hash(0, &secp).unwrap(), None);
 
-            // Same address
-            let addr_one = desc_one
-                .translate_pk2(|xpk| xpk.derive_public_key(&secp_ctx))
-                .unwrap()
-                .address(&elements::AddressParams::ELEMENTS)
-                .unwrap();
-            let addr_two = desc_two
-                .translate_pk2(|xpk| xpk.derive_public_key(&secp_ctx))
-                .unwrap()
-                .address(&elements::AddressParams::ELEMENTS)
-                .unwrap();
-            let addr_expected = elements::Address::from_str(raw_addr_expected).unwrap();
-            assert_eq!(addr_one, addr_expected);
-            assert_eq!(addr_two, addr_expected);
-        }
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(1,{}/0/*,{}/1/*))",
+            xpub, xpub
+        ))
+        .unwrap();
+        let derived = desc
+            .derive(3)
+            .translate_pk2(|xpk| xpk.derive_public_key(&secp))
+            .unwrap();
+        let hash = desc.witness_script_hash(3, &secp).unwrap().unwrap();
+        assert_eq!(&derived.script_pubkey()[2..], &hash[..]);
+        assert_ne!(desc.script_hash(3, &secp), desc.script_hash(4, &secp));
+
+        // Blinding does not change the scriptPubKey
+        let blinded = Blinded::new(
+            DescriptorPublicKey::from_str(&format!("{}/2", xpub)).unwrap(),
+            desc.clone(),
+        );
+        assert_eq!(blinded.script_hash(3, &secp), desc.script_hash(3, &secp));
+    }
 
-        // P2SH and pubkeys
-        _test_sortedmulti(
-            "elsh(sortedmulti(1,03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556,0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352))#tse3qz98",
-            "elsh(sortedmulti(1,0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352,03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556))#ptnf05qc",
-            "XUDXJZnP2GXsKRKdxSLKzJM1iZ4gbbyrGh",
+    #[test]
+    fn to_definite_string() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let desc = Descriptor::<DescriptorPublicKey>::from_str("elsh(wsh(multi(1,[d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*,020000000000000000000000000000000000000000000000000000000000000002)))").unwrap();
+
+        let definite = desc.to_definite_string(5, &secp).unwrap();
+        assert!(!definite.contains("xpub") && !definite.contains('['));
+        let parsed = Descriptor::<PublicKey>::from_str(&definite).unwrap();
+        assert!(desc.matches_spk(&parsed.script_pubkey(), 5, &secp));
+        assert_eq!(parsed.to_string(), definite);
+
+        let hardened = Descriptor::<DescriptorPublicKey>::from_str("elwpkh(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*h)").unwrap();
+        assert_eq!(
+            hardened.to_definite_string(0, &secp),
+            Err(ConversionError::HardenedChild)
         );
+    }
 
-        // P2WSH and single-xpub descriptor
-        _test_sortedmulti(
-            "elwsh(sortedmulti(1,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH))#a8h2v83d",
-            "elwsh(sortedmulti(1,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB))#qfcn7ujk",
-            "ert1qpq2cfgz5lktxzr5zqv7nrzz46hsvq3492ump9pz8rzcl8wqtwqcs2yqnuv",
+    #[test]
+    fn check_key_collisions() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(2,{}/0/*,{}/1/*))",
+            xpub, xpub
+        ))
+        .unwrap();
+        assert!(desc.check_key_collisions(0..100, &secp).is_empty());
+
+        // The fixed key is the wildcard key at index 5
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(2,{}/1/*,{}/1/5))",
+            xpub, xpub
+        ))
+        .unwrap();
+        let key = |s: String| DescriptorPublicKey::from_str(&s).unwrap();
+        let collision = KeyCollision {
+            index: 5,
+            keys: (key(format!("{}/1/*", xpub)), key(format!("{}/1/5", xpub))),
+            public_key: key(format!("{}/1/5", xpub))
+                .derive_public_key(&secp)
+                .unwrap(),
+        };
+        assert_eq!(desc.check_key_collisions(0..100, &secp), vec![collision]);
+        assert!(desc.check_key_collisions(6..100, &secp).is_empty());
+
+        // Each derivation path has a wildcard key colliding at index 5, but
+        // the paths of different multipath keys never derive together
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwsh(multi(2,{}/<0;1>/*,{}/1/5,{}/<1;0>/*))",
+            xpub, xpub, xpub
+        ))
+        .unwrap();
+        let collision = KeyCollision {
+            index: 5,
+            keys: (key(format!("{}/1/*", xpub)), key(format!("{}/1/5", xpub))),
+            public_key: key(format!("{}/1/5", xpub))
+                .derive_public_key(&secp)
+                .unwrap(),
+        };
+        let other = KeyCollision {
+            index: 5,
+            keys: (key(format!("{}/1/5", xpub)), key(format!("{}/1/*", xpub))),
+            public_key: collision.public_key,
+        };
+        assert_eq!(
+            desc.check_key_collisions(0..100, &secp),
+            vec![other, collision]
         );
+    }
 
-        // P2WSH-P2SH and ranged descriptor
-        _test_sortedmulti(
-            "elsh(wsh(sortedmulti(1,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB/1/0/*,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH/0/0/*)))#l7qy253t",
-            "elsh(wsh(sortedmulti(1,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH/0/0/*,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB/1/0/*)))#0gpee5cl",
-            "XBkDY63XnRTz6BbwzJi3ifGhBwLTomEzkq",
+    #[test]
+    fn into_single_descriptors() {
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let desc = |s: String| Descriptor::<DescriptorPublicKey>::from_str(&s).unwrap();
+
+        let multi = desc(format!("elwsh(multi(1,{}/<0;1>/*,{}/2/<3;4>))", xpub, xpub));
+        assert_eq!(
+            multi.into_single_descriptors().unwrap(),
+            vec![
+                desc(format!("elwsh(multi(1,{}/0/*,{}/2/3))", xpub, xpub)),
+                desc(format!("elwsh(multi(1,{}/1/*,{}/2/4))", xpub, xpub)),
+            ]
         );
+        let single = desc(format!("elwpkh({}/0/*)", xpub));
+        assert_eq!(single.into_single_descriptors().unwrap(), vec![single]);
+        let mismatch = desc(format!(
+            "elwsh(multi(1,{}/<0;1>/*,{}/<0;1;2>/*))",
+            xpub, xpub
+        ));
+        assert!(mismatch.into_single_descriptors().is_err());
     }
 
     #[test]
-    fn test_parse_descriptor() {
-        let secp = &secp256k1_zkp::Secp256k1::signing_only();
-        let (descriptor, key_map) = Descriptor::parse_descriptor(secp, "elwpkh(tprv8ZgxMBicQKsPcwcD4gSnMti126ZiETsuX7qwrtMypr6FBwAP65puFn4v6c3jrN9VwtMRMph6nyT63NrfUL4C3nBzPcduzVSuHD7zbX2JKVc/44'/0'/0'/0/*)").unwrap();
-        assert_eq!(descriptor.to_string(), "elwpkh([2cbe2a6d/44'/0'/0']tpubDCvNhURocXGZsLNqWcqD3syHTqPXrMSTwi8feKVwAcpi29oYKsDD3Vex7x2TDneKMVN23RbLprfxB69v94iYqdaYHsVz3kPR37NQXeqouVz/0/*)#pznhhta9");
-        assert_eq!(key_map.len(), 1);
+    fn addresses() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let params = &elements::AddressParams::ELEMENTS;
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elsh(wsh(multi(1,[78412e3a/44'/0'/0']{}/0/*,{}/1/7)))",
+            xpub, xpub
+        ))
+        .unwrap();
+        let expected: Vec<_> = (5..9)
+            .map(|i| {
+                desc.derived_public_descriptor(i, &secp)
+                    .unwrap()
+                    .address(params)
+                    .unwrap()
+            })
+            .collect();
+        let addrs: Vec<_> = desc.addresses(params, 5, &secp).unwrap().take(4).collect();
+        assert_eq!(addrs, expected);
 
-        // https://github.com/bitcoin/bitcoin/blob/7ae86b3c6845873ca96650fc69beb4ae5285c801/src/test/descriptor_tests.cpp#L355-L360
-        macro_rules! check_invalid_checksum {
-            ($secp: ident,$($desc: expr),*) => {
-                $(
-                    match Descriptor::parse_descriptor($secp, $desc) {
-                        Err(Error::BadDescriptor(_)) => {},
-                        Err(e) => panic!("Expected bad checksum for {}, got '{}'", $desc, e),
-                        _ => panic!("Invalid checksum treated as valid: {}", $desc),
-                    };
-                )*
-            };
-        }
-        check_invalid_checksum!(secp,
-            "elsh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#",
-            "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#",
-            "elsh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#ggrsrxf",
-            "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#tjg09x5tq",
-            "elsh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#ggrsrxf",
-            "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#tjg09x5",
-            "elsh(multi(3,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#ggrsrxfy",
-            "elsh(multi(3,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#tjg09x5t",
-            "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#tjq09x4t",
-            "elsh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))##ggssrxfy",
-            "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))##tjq09x4t"
+        // The iterator stops at the last unhardened index
+        assert_eq!(
+            desc.addresses(params, (1 << 31) - 2, &secp)
+                .unwrap()
+                .count(),
+            2
         );
 
-        Descriptor::parse_descriptor(&secp, "elsh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))#9s2ngs7u").expect("Valid descriptor with checksum");
-        Descriptor::parse_descriptor(&secp, "elsh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))#uklept69").expect("Valid descriptor with checksum");
+        // Without wildcard there is a single address
+        let desc =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/0/1)", xpub)).unwrap();
+        let addrs: Vec<_> = desc.addresses(params, 5, &secp).unwrap().collect();
+        assert_eq!(
+            addrs,
+            vec![desc
+                .derived_public_descriptor(0, &secp)
+                .unwrap()
+                .address(params)
+                .unwrap()]
+        );
+
+        // Hardened steps cannot be derived, and bare descriptors have no address
+        let desc =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/0/*h)", xpub)).unwrap();
+        assert!(desc.addresses(params, 0, &secp).is_err());
+        let desc =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/0h/*)", xpub)).unwrap();
+        assert!(desc.addresses(params, 0, &secp).is_err());
+        let desc =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elpk({}/*)", xpub)).unwrap();
+        assert!(desc.addresses(params, 0, &secp).is_err());
     }
 
     #[test]
-    #[cfg(feature = "compiler")]
-    fn parse_and_derive() {
-        let descriptor_str = "thresh(2,\
-pk([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*),\
-pk(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1),\
-pk(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8))";
-        let policy: policy::concrete::Policy<DescriptorPublicKey> = descriptor_str.parse().unwrap();
-        let descriptor = Descriptor::new_sh(policy.compile().unwrap()).unwrap();
-        let derived_descriptor = descriptor.derive(42);
+    fn earliest_spend() {
+        let (a, b) = (
+            "020000000000000000000000000000000000000000000000000000000000000002",
+            "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        );
+        let desc = Descriptor::<PublicKey>::from_str(&format!(
+            "elwsh(or_i(and_v(v:pk({}),after(1000)),and_v(v:pk({}),older(10))))",
+            a, b
+        ))
+        .unwrap();
+        let spend = |height, mtp| desc.earliest_spend(height, mtp).unwrap();
+        assert_eq!(
+            spend(500, 1_600_000_000),
+            SpendEstimate {
+                path: vec![1],
+                lock_time: 0,
+                sequence: 10,
+                height: 510,
+                time: 1_600_000_000,
+            }
+        );
+        assert_eq!(
+            spend(995, 1_600_000_000),
+            SpendEstimate {
+                path: vec![0],
+                lock_time: 1000,
+                sequence: 0,
+                height: 1000,
+                time: 1_600_000_000,
+            }
+        );
+        assert_eq!(spend(2000, 1_600_000_000).height, 2000);
+
+        // Time-based locks
+        let desc = Descriptor::<PublicKey>::from_str(&format!(
+            "elwsh(or_i(and_v(v:pk({}),after(1700000000)),and_v(v:pk({}),older({}))))",
+            a,
+            b,
+            SEQUENCE_LOCKTIME_TYPE_FLAG | 2
+        ))
+        .unwrap();
+        let estimate = desc.earliest_spend(100, 1_600_000_000).unwrap();
+        assert_eq!(estimate.path, vec![1]);
+        assert_eq!((estimate.height, estimate.time), (100, 1_600_001_024));
+        let estimate = desc.earliest_spend(100, 1_699_999_999).unwrap();
+        assert_eq!(estimate.path, vec![0]);
+        assert_eq!((estimate.height, estimate.time), (100, 1_700_000_001));
+
+        let desc = Descriptor::new_wpkh(PublicKey::from_str(a).unwrap()).unwrap();
+        assert_eq!(
+            desc.earliest_spend(100, 1_600_000_000).unwrap(),
+            SpendEstimate {
+                path: vec![],
+                lock_time: 0,
+                sequence: 0,
+                height: 100,
+                time: 1_600_000_000,
+            }
+        );
+    }
 
-        let res_descriptor_str = "thresh(2,\
-pk([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/42),\
-pk(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1),\
-pk(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8))";
-        let res_policy: policy::concrete::Policy<DescriptorPublicKey> =
-            res_descriptor_str.parse().unwrap();
-        let res_descriptor = Descriptor::new_sh(res_policy.compile().unwrap()).unwrap();
+    #[test]
+    fn as_miniscript() {
+        let (a, b) = (
+            "020000000000000000000000000000000000000000000000000000000000000002",
+            "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        );
+        let ms = Miniscript::<PublicKey, Segwitv0>::from_str(&format!(
+            "or_d(pk({}),and_v(v:pk({}),older(144)))",
+            a, b
+        ))
+        .unwrap();
+        let wsh = Descriptor::new_wsh(ms.clone()).unwrap();
+        let sh_wsh = Descriptor::new_sh_wsh(ms.clone()).unwrap();
+        assert_eq!(wsh.as_miniscript(), Some(DescriptorMiniscript::Wsh(&ms)));
+        assert_eq!(sh_wsh.as_miniscript(), Some(DescriptorMiniscript::Wsh(&ms)));
+        assert_eq!(
+            wsh.as_miniscript().unwrap().lift().unwrap(),
+            ms.lift().unwrap()
+        );
+        let mut keys = vec![];
+        wsh.as_miniscript().unwrap().for_each_key(|key| {
+            if let ForEach::Key(pk) = key {
+                keys.push(*pk);
+            }
+            true
+        });
+        assert_eq!(
+            keys,
+            vec![
+                PublicKey::from_str(a).unwrap(),
+                PublicKey::from_str(b).unwrap()
+            ]
+        );
 
-        assert_eq!(res_descriptor, derived_descriptor);
+        let bare = Descriptor::<PublicKey>::from_str(&format!("elpk({})", a)).unwrap();
+        match bare.as_miniscript() {
+            Some(DescriptorMiniscript::Bare(..)) => {}
+            res => panic!("unexpected miniscript {:?}", res),
+        }
+        let sh = Descriptor::<PublicKey>::from_str(&format!("elsh(pk({}))", a)).unwrap();
+        match sh.as_miniscript() {
+            Some(DescriptorMiniscript::Sh(..)) => {}
+            res => panic!("unexpected miniscript {:?}", res),
+        }
+
+        let pk = PublicKey::from_str(a).unwrap();
+        assert_eq!(Descriptor::new_pkh(pk).as_miniscript(), None);
+        assert_eq!(Descriptor::new_wpkh(pk).unwrap().as_miniscript(), None);
+        assert_eq!(Descriptor::new_sh_wpkh(pk).unwrap().as_miniscript(), None);
+        assert_eq!(
+            Descriptor::new_wsh_sortedmulti(1, vec![pk])
+                .unwrap()
+                .as_miniscript(),
+            None
+        );
     }
 
     #[test]
-    fn parse_with_secrets() {
-        let secp = &secp256k1_zkp::Secp256k1::signing_only();
-        let descriptor_str = "elwpkh(xprv9s21ZrQH143K4CTb63EaMxja1YiTnSEWKMbn23uoEnAzxjdUJRQkazCAtzxGm4LSoTSVTptoV9RbchnKPW9HxKtZumdyxyikZFDLhogJ5Uj/44'/0'/0'/0/*)#xldrpn5u";
-        let (descriptor, keymap) =
-            Descriptor::<DescriptorPublicKey>::parse_descriptor(&secp, descriptor_str).unwrap();
+    fn sign_with_root_xprv() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let root = bip32::ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &[7; 32]).unwrap();
+        let fingerprint = root.fingerprint(&secp);
+        let account_path = bip32::DerivationPath::from_str("m/84'/1776'/0'").unwrap();
+        let account = root.derive_priv(&secp, &account_path).unwrap();
+        let xpub = bip32::ExtendedPubKey::from_private(&secp, &account);
+        let desc = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwpkh([{}/84'/1776'/0']{}/0/*)",
+            fingerprint, xpub
+        ))
+        .unwrap();
 
-        let expected = "elwpkh([a12b02f4/44'/0'/0']xpub6BzhLAQUDcBUfHRQHZxDF2AbcJqp4Kaeq6bzJpXrjrWuK26ymTFwkEFbxPra2bJ7yeZKbDjfDeFwxe93JMqpo5SsPJH6dZdvV9kMzJkAZ69/0/*)#20ufqv7z";
-        assert_eq!(expected, descriptor.to_string());
-        assert_eq!(keymap.len(), 1);
+        let path = account_path.extend([
+            bip32::ChildNumber::Normal { index: 0 },
+            bip32::ChildNumber::Normal { index: 5 },
+        ]);
+        let sk = root.derive_priv(&secp, &path).unwrap().private_key;
+        let pk = sk.public_key(&secp);
+        let spk = desc
+            .derive(5)
+            .translate_pk2(|xpk| xpk.derive_public_key(&secp))
+            .unwrap()
+            .script_pubkey();
+
+        let txin = |vout| elements::TxIn {
+            previous_output: elements::OutPoint {
+                txid: elements::Txid::default(),
+                vout,
+            },
+            is_pegin: false,
+            has_issuance: false,
+            script_sig: Script::new(),
+            sequence: 0xfffffffe,
+            asset_issuance: Default::default(),
+            witness: Default::default(),
+        };
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![txin(0), txin(1)],
+            output: vec![elements::TxOut {
+                script_pubkey: spk.clone(),
+                value: elements::confidential::Value::Explicit(90_000),
+                ..Default::default()
+            }],
+        };
+        let mut pset = Pset::from_tx(tx);
+        pset.inputs[0].witness_utxo = Some(elements::TxOut {
+            script_pubkey: spk,
+            value: elements::confidential::Value::Explicit(100_000),
+            ..Default::default()
+        });
+        pset.inputs[0]
+            .bip32_derivation
+            .insert(pk, (fingerprint, path));
+        // An input of another wallet
+        pset.inputs[1].witness_utxo = Some(elements::TxOut {
+            script_pubkey: Descriptor::new_pkh(pk).script_pubkey(),
+            value: elements::confidential::Value::Explicit(100_000),
+            ..Default::default()
+        });
 
-        // try to turn it back into a string with the secrets
-        assert_eq!(descriptor_str, descriptor.to_string_with_secret(&keymap));
+        assert_eq!(
+            desc.sign_with_root_xprv(&mut pset, &root, &secp).unwrap(),
+            1
+        );
+        assert!(pset.inputs[0].partial_sigs.contains_key(&pk));
+        assert!(pset.inputs[1].partial_sigs.is_empty());
+
+        // The input is found on the second path of a multipath descriptor
+        let multipath = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "elwpkh([{}/84'/1776'/0']{}/<1;0>/*)",
+            fingerprint, xpub
+        ))
+        .unwrap();
+        pset.inputs[0].partial_sigs.clear();
+        assert_eq!(
+            multipath
+                .sign_with_root_xprv(&mut pset, &root, &secp)
+                .unwrap(),
+            1
+        );
+        assert!(pset.inputs[0].partial_sigs.contains_key(&pk));
+        pset::finalize_input(&mut pset, &secp, 0).unwrap();
+
+        // Another master key cannot sign
+        let other =
+            bip32::ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &[8; 32]).unwrap();
+        pset.inputs[0].partial_sigs.clear();
+        assert_eq!(
+            desc.sign_with_root_xprv(&mut pset, &other, &secp).unwrap(),
+            0
+        );
     }
 
     #[test]
-    fn checksum_for_nested_sh() {
-        let descriptor_str = "elsh(wpkh(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL))";
-        let descriptor: Descriptor<DescriptorPublicKey> = descriptor_str.parse().unwrap();
-        assert_eq!(descriptor.to_string(), "elsh(wpkh(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL))#2040pn7l");
+    fn is_standard_on_liquid() {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let keys: Vec<PublicKey> = (1..=101u8)
+            .map(|i| PublicKey {
+                key: secp256k1_zkp::PublicKey::from_secret_key(
+                    &secp,
+                    &secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap(),
+                ),
+                compressed: true,
+            })
+            .collect();
+        let standard = |s: String| StdDescriptor::from_str(&s).unwrap().is_standard_on_liquid();
 
-        let descriptor_str = "elsh(wsh(pk(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL)))";
-        let descriptor: Descriptor<DescriptorPublicKey> = descriptor_str.parse().unwrap();
-        assert_eq!(descriptor.to_string(), "elsh(wsh(pk(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL)))#pqs0de7e");
+        assert_eq!(standard(format!("elwpkh({})", keys[0])), Ok(()));
+        assert_eq!(standard(format!("elpk({})", keys[0])), Ok(()));
+        assert_eq!(
+            standard(format!("elmulti(2,{},{},{})", keys[0], keys[1], keys[2])),
+            Ok(())
+        );
+        assert_eq!(
+            standard(format!("elwsh(and_v(v:pk({}),pk({})))", keys[0], keys[1])),
+            Ok(())
+        );
+
+        // 101 signatures in the witness
+        let mut ms = format!("pk({})", keys[100]);
+        for key in keys[..100].iter().rev() {
+            ms = format!("and_v(v:pk({}),{})", key, ms);
+        }
+        assert_eq!(
+            standard(format!("elwsh({})", ms)),
+            Err(NonStandardReason::WitnessItems(101))
+        );
     }
 }
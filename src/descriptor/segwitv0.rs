@@ -23,6 +23,7 @@ use elements::{self, Script};
 
 use expression::{self, FromTree};
 use miniscript::context::{ScriptContext, ScriptContextError};
+use miniscript::satisfy::check_path_consumed;
 use policy::{semantic, Liftable};
 use util::varint_len;
 use {
@@ -70,6 +71,30 @@ impl<Pk: MiniscriptKey> Wsh<Pk> {
         })
     }
 
+    /// Returns a satisfying witness and scriptSig taking the spending
+    /// path chosen by `path`. See [Miniscript::satisfy_path] for the
+    /// format of `path`.
+    pub fn get_satisfaction_path<S>(
+        &self,
+        satisfier: S,
+        path: &[usize],
+    ) -> Result<(Vec<Vec<u8>>, Script), Error>
+    where
+        Pk: ToPublicKey,
+        S: Satisfier<Pk>,
+    {
+        let (mut witness, witness_script) = match self.inner {
+            WshInner::SortedMulti(ref smv) => {
+                check_path_consumed(path)?;
+                (smv.satisfy(satisfier)?, smv.encode())
+            }
+            WshInner::Ms(ref ms) => (ms.satisfy_path(satisfier, path)?, ms.encode()),
+        };
+        witness.push(witness_script.into_bytes());
+        let script_sig = Script::new();
+        Ok((witness, script_sig))
+    }
+
     /// Get the descriptor without the checksum, without the el prefix
     pub(crate) fn to_string_no_checksum(&self) -> String {
         match self.inner {
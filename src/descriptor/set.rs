@@ -0,0 +1,255 @@
+// Miniscript
+// Written in 2021 by rust-miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Descriptor Sets
+//!
+//! The receive and change descriptors of a wallet, used to tell which
+//! outputs of a transaction belong to the wallet.
+//!
+
+use std::cmp;
+use std::collections::HashMap;
+
+use elements::{self, secp256k1_zkp, Script};
+
+use super::{Descriptor, DescriptorPublicKey, DescriptorTrait};
+
+/// The owner of a transaction output, as found by
+/// [DescriptorSet::classify_outputs]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum OutputClass {
+    /// Output paying to a receive descriptor of the set
+    Receive {
+        /// Position of the descriptor in [DescriptorSet::receive]
+        descriptor: usize,
+        /// Derivation index of the scriptPubKey
+        index: u32,
+    },
+    /// Output paying to a change descriptor of the set
+    Change {
+        /// Position of the descriptor in [DescriptorSet::change]
+        descriptor: usize,
+        /// Derivation index of the scriptPubKey
+        index: u32,
+    },
+    /// Explicit fee output
+    Fee,
+    /// Output paying to anything else
+    External,
+}
+
+/// The descriptors of a wallet, split between the ones used to receive
+/// payments and the ones used for change
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DescriptorSet {
+    /// Descriptors handed out to receive payments
+    pub receive: Vec<Descriptor<DescriptorPublicKey>>,
+    /// Descriptors receiving the change of the wallet's own transactions
+    pub change: Vec<Descriptor<DescriptorPublicKey>>,
+    /// Number of derivation indices, starting at 0, searched for each
+    /// ranged descriptor
+    pub lookahead: u32,
+}
+
+impl DescriptorSet {
+    /// Create a new descriptor set
+    pub fn new(
+        receive: Vec<Descriptor<DescriptorPublicKey>>,
+        change: Vec<Descriptor<DescriptorPublicKey>>,
+        lookahead: u32,
+    ) -> Self {
+        DescriptorSet {
+            receive,
+            change,
+            lookahead,
+        }
+    }
+
+    /// Classifies each output of `tx`, in order, by matching its
+    /// scriptPubKey against the first [DescriptorSet::lookahead] indices
+    /// of every descriptor. Non-ranged descriptors only have index 0, and
+    /// multipath descriptors match on each of their derivation paths.
+    ///
+    /// The amount sent by the transaction is the value of the outputs
+    /// which are neither receive nor change. Descriptors whose keys cannot
+    /// be derived, because of hardened steps, never match. If a
+    /// scriptPubKey is produced by several descriptors, receive
+    /// descriptors come first, then the earliest descriptor and index.
+    pub fn classify_outputs<C: secp256k1_zkp::Verification>(
+        &self,
+        tx: &elements::Transaction,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Vec<OutputClass> {
+        let spks = self.script_pubkeys(secp);
+        tx.output
+            .iter()
+            .map(|txout| {
+                if txout.is_fee() {
+                    OutputClass::Fee
+                } else {
+                    spks.get(&txout.script_pubkey)
+                        .cloned()
+                        .unwrap_or(OutputClass::External)
+                }
+            })
+            .collect()
+    }
+
+    /// Every scriptPubKey of the set, with its class
+    pub(super) fn script_pubkeys<C: secp256k1_zkp::Verification>(
+        &self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> HashMap<Script, OutputClass> {
+        let mut spks = HashMap::new();
+        let lists: [(&[Descriptor<DescriptorPublicKey>], bool); 2] =
+            [(&self.receive, false), (&self.change, true)];
+        for &(descs, is_change) in lists.iter() {
+            for (descriptor, desc) in descs.iter().enumerate() {
+                let end = if desc.is_deriveable() {
+                    cmp::min(self.lookahead, 1 << 31)
+                } else {
+                    1
+                };
+                for single in desc.into_single_descriptors().unwrap_or_default() {
+                    for index in 0..end {
+                        let derived = match single.derived_public_descriptor(index, secp) {
+                            Ok(derived) => derived,
+                            Err(_) => break,
+                        };
+                        let class = if is_change {
+                            OutputClass::Change { descriptor, index }
+                        } else {
+                            OutputClass::Receive { descriptor, index }
+                        };
+                        spks.entry(derived.script_pubkey()).or_insert(class);
+                    }
+                }
+            }
+        }
+        spks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::confidential;
+    use std::str::FromStr;
+    use TranslatePk2;
+
+    #[test]
+    fn classify_outputs() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let desc = |s: String| Descriptor::<DescriptorPublicKey>::from_str(&s).unwrap();
+        let receive = desc(format!("elwpkh({}/0/*)", xpub));
+        let change = desc(format!("elwpkh({}/1/*)", xpub));
+        let single = desc(format!("elsh(wpkh({}/2))", xpub));
+        let set = DescriptorSet::new(
+            vec![receive.clone(), single.clone()],
+            vec![change.clone()],
+            20,
+        );
+
+        let txout = |script_pubkey: Script| elements::TxOut {
+            script_pubkey,
+            value: confidential::Value::Explicit(1000),
+            ..Default::default()
+        };
+        let spk = |desc: &Descriptor<DescriptorPublicKey>, index| {
+            desc.derive(index)
+                .translate_pk2(|xpk| xpk.derive_public_key(&secp))
+                .unwrap()
+                .script_pubkey()
+        };
+        let external = desc(format!("elwpkh({}/3/*)", xpub));
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![
+                txout(spk(&external, 0)),
+                txout(spk(&change, 7)),
+                txout(spk(&receive, 19)),
+                txout(spk(&receive, 20)),
+                txout(spk(&single, 0)),
+                elements::TxOut {
+                    asset: confidential::Asset::Explicit(elements::AssetId::default()),
+                    ..txout(Script::new())
+                },
+            ],
+        };
+        assert_eq!(
+            set.classify_outputs(&tx, &secp),
+            vec![
+                OutputClass::External,
+                OutputClass::Change {
+                    descriptor: 0,
+                    index: 7
+                },
+                OutputClass::Receive {
+                    descriptor: 0,
+                    index: 19
+                },
+                // Beyond the lookahead
+                OutputClass::External,
+                OutputClass::Receive {
+                    descriptor: 1,
+                    index: 0
+                },
+                OutputClass::Fee,
+            ]
+        );
+    }
+
+    #[test]
+    fn classify_multipath_outputs() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+        let desc = |s: String| Descriptor::<DescriptorPublicKey>::from_str(&s).unwrap();
+        let set = DescriptorSet::new(vec![desc(format!("elwpkh({}/<0;1>/*)", xpub))], vec![], 20);
+
+        let txout = |desc: Descriptor<DescriptorPublicKey>| elements::TxOut {
+            script_pubkey: desc
+                .translate_pk2(|xpk| xpk.derive_public_key(&secp))
+                .unwrap()
+                .script_pubkey(),
+            value: confidential::Value::Explicit(1000),
+            ..Default::default()
+        };
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![
+                txout(desc(format!("elwpkh({}/0/3)", xpub))),
+                txout(desc(format!("elwpkh({}/1/5)", xpub))),
+                txout(desc(format!("elwpkh({}/2/5)", xpub))),
+            ],
+        };
+        assert_eq!(
+            set.classify_outputs(&tx, &secp),
+            vec![
+                OutputClass::Receive {
+                    descriptor: 0,
+                    index: 3
+                },
+                OutputClass::Receive {
+                    descriptor: 0,
+                    index: 5
+                },
+                OutputClass::External,
+            ]
+        );
+    }
+}
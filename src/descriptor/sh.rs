@@ -25,6 +25,7 @@ use elements::{self, script, Script};
 
 use expression::{self, FromTree};
 use miniscript::context::ScriptContext;
+use miniscript::satisfy::check_path_consumed;
 use policy::{semantic, Liftable};
 use push_opcode_size;
 use util::{varint_len, witness_to_scriptsig};
@@ -193,6 +194,40 @@ impl<Pk: MiniscriptKey> Sh<Pk> {
             inner: ShInner::Wpkh(Wpkh::new(pk)?),
         })
     }
+
+    /// Returns a satisfying witness and scriptSig taking the spending
+    /// path chosen by `path`. See [Miniscript::satisfy_path] for the
+    /// format of `path`.
+    pub fn get_satisfaction_path<S>(
+        &self,
+        satisfier: S,
+        path: &[usize],
+    ) -> Result<(Vec<Vec<u8>>, Script), Error>
+    where
+        Pk: ToPublicKey + FromStr,
+        Pk::Hash: FromStr,
+        <Pk as FromStr>::Err: ToString,
+        <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+        S: Satisfier<Pk>,
+    {
+        match self.inner {
+            ShInner::Wsh(ref wsh) => {
+                let (witness, _) = wsh.get_satisfaction_path(satisfier, path)?;
+                Ok((witness, self.unsigned_script_sig()))
+            }
+            ShInner::Ms(ref ms) => {
+                let mut script_witness = ms.satisfy_path(satisfier, path)?;
+                script_witness.push(ms.encode().into_bytes());
+                let script_sig = witness_to_scriptsig(&script_witness);
+                let witness = vec![];
+                Ok((witness, script_sig))
+            }
+            ShInner::Wpkh(..) | ShInner::SortedMulti(..) => {
+                check_path_consumed(path)?;
+                self.get_satisfaction(satisfier)
+            }
+        }
+    }
 }
 
 impl<Pk: MiniscriptKey> ElementsTrait<Pk> for Sh<Pk>
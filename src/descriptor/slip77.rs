@@ -0,0 +1,179 @@
+// Miniscript
+// Written in 2021 by rust-miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # SLIP-77 Blinding Keys
+//!
+//! Deterministic derivation of the blinding keys of confidential outputs
+//! as described in SLIP-77. Every output script is blinded with its own
+//! key, derived from a single master blinding key and the script itself,
+//! so that the blinding keys can be managed separately from the keys
+//! controlling the coins.
+//!
+
+use std::fmt;
+
+use elements::hashes::hex::ToHex;
+use elements::hashes::{sha256, sha512, Hash, HashEngine, Hmac, HmacEngine};
+use elements::secp256k1_zkp::{self, PublicKey, Secp256k1, SecretKey};
+use elements::Script;
+
+/// A SLIP-77 master blinding key
+///
+/// The key is secret, `Debug` only shows a fingerprint of it.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Slip77MasterKey([u8; 32]);
+
+impl fmt::Debug for Slip77MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The first bytes of the hash identify the key without revealing it
+        let hash = sha256::Hash::hash(&self.0);
+        write!(f, "Slip77MasterKey(#{})", hash[..4].to_hex())
+    }
+}
+
+impl Slip77MasterKey {
+    /// Create a master blinding key from its raw bytes
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Slip77MasterKey(bytes)
+    }
+
+    /// Derive the master blinding key of a wallet from its seed, using the
+    /// SLIP-21 symmetric key derivation with the label `SLIP-0077`
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let mut engine = HmacEngine::<sha512::Hash>::new(b"Symmetric key seed");
+        engine.input(seed);
+        let root = Hmac::<sha512::Hash>::from_engine(engine);
+
+        let mut engine = HmacEngine::<sha512::Hash>::new(&root[..32]);
+        engine.input(&[0]);
+        engine.input(b"SLIP-0077");
+        let node = Hmac::<sha512::Hash>::from_engine(engine);
+
+        let mut key = [0; 32];
+        key.copy_from_slice(&node[32..]);
+        Slip77MasterKey(key)
+    }
+
+    /// The raw bytes of the master blinding key
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The blinding private key of the confidential outputs paying to
+    /// `spk`, that is `HMAC-SHA256(master, spk)`
+    pub fn blinding_private_key(&self, spk: &Script) -> SecretKey {
+        let mut engine = HmacEngine::<sha256::Hash>::new(&self.0);
+        engine.input(spk.as_bytes());
+        let hmac = Hmac::<sha256::Hash>::from_engine(engine);
+        SecretKey::from_slice(&hmac[..]).expect("cryptographically unreachable")
+    }
+
+    /// The blinding public key of the confidential outputs paying to `spk`
+    pub fn blinding_public_key<C: secp256k1_zkp::Signing>(
+        &self,
+        spk: &Script,
+        secp: &Secp256k1<C>,
+    ) -> PublicKey {
+        PublicKey::from_secret_key(secp, &self.blinding_private_key(spk))
+    }
+}
+
+/// Computes the blinding key pair of the confidential outputs paying to
+/// `spk`, the private key being `HMAC-SHA256(master, spk)`
+pub fn blinding_key_for_script<C: secp256k1_zkp::Signing>(
+    master: &Slip77MasterKey,
+    spk: &Script,
+    secp: &Secp256k1<C>,
+) -> (SecretKey, PublicKey) {
+    let sk = master.blinding_private_key(spk);
+    let pk = PublicKey::from_secret_key(secp, &sk);
+    (sk, pk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::hashes::hex::FromHex;
+
+    #[test]
+    fn slip77_blinding_keys() {
+        let secp = Secp256k1::new();
+        let master = Slip77MasterKey::from_seed(&[0x42; 64]);
+        assert_eq!(master, Slip77MasterKey::from_seed(&[0x42; 64]));
+        assert_ne!(master, Slip77MasterKey::from_seed(&[0x43; 64]));
+
+        let spk = Script::from(
+            Vec::<u8>::from_hex("0014d0c4a3ef09e997b6e99e397e518fe3e41a118ca1").unwrap(),
+        );
+        let other = Script::from(
+            Vec::<u8>::from_hex("0014a4b4ca48de0b3fffc15404a1acdc8dbaae226955").unwrap(),
+        );
+        let (sk, pk) = blinding_key_for_script(&master, &spk, &secp);
+        assert_eq!(pk, PublicKey::from_secret_key(&secp, &sk));
+        assert_eq!(blinding_key_for_script(&master, &spk, &secp), (sk, pk));
+        assert_ne!(blinding_key_for_script(&master, &other, &secp).0, sk);
+
+        let raw = Slip77MasterKey::new(*master.as_bytes());
+        assert_eq!(blinding_key_for_script(&raw, &spk, &secp).0, sk);
+    }
+
+    #[test]
+    fn slip77_debug_redacted() {
+        let master = Slip77MasterKey::new([0x42; 32]);
+        let debug = format!("{:?}", master);
+        let hash = sha256::Hash::hash(&[0x42; 32]);
+        assert_eq!(debug, format!("Slip77MasterKey(#{})", hash[..4].to_hex()));
+        assert!(!debug.contains(&master.as_bytes().to_hex()));
+        assert!(!debug.contains("4242"));
+    }
+
+    #[test]
+    fn slip77_test_vector() {
+        // From SLIP-0077, for the mnemonic "all all all all all all all all
+        // all all all all" without passphrase
+        let secp = Secp256k1::new();
+        let seed = Vec::<u8>::from_hex(
+            "c76c4ac4f4e4a00d6b274d5c39c700bb4a7ddc04fbc6f78e85ca75007b5b495f\
+             74a9043eeb77bdd53aa6fc3a0e31462270316fa04b8c19114c8798706cd02ac8",
+        )
+        .unwrap();
+        let master = Slip77MasterKey::from_seed(&seed);
+        assert_eq!(
+            &master.as_bytes()[..],
+            &Vec::<u8>::from_hex(
+                "6c2de18eabeff3f7822bc724ad482bef0557f3e1c1e1c75b7a393a5ced4de616"
+            )
+            .unwrap()[..]
+        );
+
+        let spk = Script::from(
+            Vec::<u8>::from_hex("76a914a579388225827d9f2fe9014add644487808c695d88ac").unwrap(),
+        );
+        let sk = master.blinding_private_key(&spk);
+        assert_eq!(
+            sk,
+            SecretKey::from_slice(
+                &Vec::<u8>::from_hex(
+                    "4e6e94df28448c7bb159271fe546da464ea863b3887d2eec6afd841184b70592"
+                )
+                .unwrap()
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            master.blinding_public_key(&spk, &secp).to_string(),
+            "0223ef5cf5d1185f86204b9386c8541061a24b6f72fa4a29e3a0b60e1c20ffaf5b"
+        );
+        assert_eq!(blinding_key_for_script(&master, &spk, &secp).0, sk);
+    }
+}
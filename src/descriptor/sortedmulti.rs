@@ -18,8 +18,10 @@
 
 use std::{fmt, marker::PhantomData, str::FromStr};
 
-use elements::script;
+use bitcoin;
+use elements::{script, secp256k1_zkp};
 
+use descriptor::{ConversionError, DescriptorPublicKey};
 use expression;
 use miniscript::{self, context::ScriptContext, decode::Terminal};
 use policy;
@@ -28,6 +30,12 @@ use script_num_size;
 use {errstr, Error, ForEach, ForEachKey, Miniscript, MiniscriptKey, Satisfier, ToPublicKey};
 
 /// Contents of a "sortedmulti" descriptor
+///
+/// Unlike `multi`, whose script depends on the order in which the keys
+/// are written, the keys of a `sortedmulti` are always sorted according
+/// to BIP 67 when encoding the script. Hence `sortedmulti(2,A,B)` and
+/// `sortedmulti(2,B,A)` are equivalent, but `multi(2,A,B)` and
+/// `multi(2,B,A)` are not.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SortedMultiVec<Pk: MiniscriptKey, Ctx: ScriptContext> {
     /// signatures required
@@ -130,6 +138,28 @@ impl<Pk: MiniscriptKey, Ctx: ScriptContext> SortedMultiVec<Pk, Ctx> {
     }
 }
 
+impl<Ctx: ScriptContext> SortedMultiVec<DescriptorPublicKey, Ctx> {
+    /// Derives the keys at `index` and sorts them according to BIP 67, in
+    /// the order in which they appear in the script at `index`. The order
+    /// of wildcard keys may change from one index to the other.
+    ///
+    /// Errors if a key cannot be derived because of hardened derivation
+    /// steps. Panics if given an index ≥ 2^31
+    pub fn sorted_at_index<C: secp256k1_zkp::Verification>(
+        &self,
+        index: u32,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<Vec<bitcoin::PublicKey>, ConversionError> {
+        let mut pks = self
+            .pks
+            .iter()
+            .map(|pk| pk.clone().derive(index).derive_public_key(secp))
+            .collect::<Result<Vec<_>, _>>()?;
+        pks.sort_by(|a, b| a.key.serialize().cmp(&b.key.serialize()));
+        Ok(pks)
+    }
+}
+
 impl<Pk: MiniscriptKey, Ctx: ScriptContext> SortedMultiVec<Pk, Ctx> {
     /// Create Terminal::Multi containing sorted pubkeys
     pub fn sorted_node(&self) -> Terminal<Pk, Ctx>
@@ -148,6 +178,16 @@ impl<Pk: MiniscriptKey, Ctx: ScriptContext> SortedMultiVec<Pk, Ctx> {
         Terminal::Multi(self.k, pks)
     }
 
+    /// Whether the given keys are already in the order in which a
+    /// `sortedmulti` would place them
+    pub(crate) fn is_sorted(pks: &[Pk]) -> bool
+    where
+        Pk: ToPublicKey,
+    {
+        pks.windows(2)
+            .all(|w| w[0].to_public_key().key.serialize() <= w[1].to_public_key().key.serialize())
+    }
+
     /// Encode as a Bitcoin script
     pub fn encode(&self) -> script::Script
     where
@@ -0,0 +1,152 @@
+// Miniscript
+// Written in 2021 by rust-miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Tracked Descriptors
+//!
+//! A descriptor together with the set of derivation indices whose
+//! outputs have been used on-chain.
+//!
+
+use std::collections::BTreeSet;
+
+use super::{Descriptor, DescriptorPublicKey};
+
+/// A ranged descriptor which keeps track of the derivation indices
+/// that have been used.
+///
+/// With the `serde` feature, this is (de)serialized as a pair of the
+/// descriptor string and the list of used indices.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TrackedDescriptor {
+    /// The tracked descriptor
+    desc: Descriptor<DescriptorPublicKey>,
+    /// Derivation indices which have been marked as used
+    used: BTreeSet<u32>,
+}
+
+impl TrackedDescriptor {
+    /// Create a new tracked descriptor with no used indices
+    pub fn new(desc: Descriptor<DescriptorPublicKey>) -> Self {
+        TrackedDescriptor {
+            desc,
+            used: BTreeSet::new(),
+        }
+    }
+
+    /// Get a reference to the underlying descriptor
+    pub fn descriptor(&self) -> &Descriptor<DescriptorPublicKey> {
+        &self.desc
+    }
+
+    /// Get the underlying descriptor
+    pub fn into_descriptor(self) -> Descriptor<DescriptorPublicKey> {
+        self.desc
+    }
+
+    /// Mark the given derivation index as used. Returns `false` if the
+    /// index was already marked as used.
+    pub fn mark_used(&mut self, index: u32) -> bool {
+        self.used.insert(index)
+    }
+
+    /// Whether the given derivation index has been marked as used
+    pub fn is_used(&self, index: u32) -> bool {
+        self.used.contains(&index)
+    }
+
+    /// Iterate over all the used derivation indices, in ascending order
+    pub fn used_indices(&self) -> impl Iterator<Item = u32> + '_ {
+        self.used.iter().cloned()
+    }
+
+    /// The lowest derivation index which has not been marked as used
+    pub fn next_unused_index(&self) -> u32 {
+        // The set is ordered, so the first gap is the lowest unused index
+        let mut next = 0;
+        for &index in &self.used {
+            if index != next {
+                break;
+            }
+            next += 1;
+        }
+        next
+    }
+
+    /// The descriptor derived at [TrackedDescriptor::next_unused_index]
+    ///
+    /// Panics if all the unhardened indices have been used
+    pub fn next_unused(&self) -> Descriptor<DescriptorPublicKey> {
+        self.desc.derive(self.next_unused_index())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for TrackedDescriptor {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
+    {
+        ::serde::Serialize::serialize(&(&self.desc, &self.used), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for TrackedDescriptor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        let (desc, used) = ::serde::Deserialize::deserialize(deserializer)?;
+        Ok(TrackedDescriptor { desc, used })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrackedDescriptor;
+    use std::str::FromStr;
+    use Descriptor;
+
+    const DESC: &str = "elwpkh(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*)";
+
+    #[test]
+    fn next_unused_index() {
+        let mut tracked = TrackedDescriptor::new(Descriptor::from_str(DESC).unwrap());
+        assert_eq!(tracked.next_unused_index(), 0);
+        assert!(tracked.mark_used(0));
+        assert!(!tracked.mark_used(0));
+        assert!(tracked.mark_used(1));
+        assert!(tracked.mark_used(3));
+        assert_eq!(tracked.next_unused_index(), 2);
+        assert!(tracked.is_used(3));
+        assert!(!tracked.is_used(2));
+        assert_eq!(tracked.used_indices().collect::<Vec<_>>(), vec![0, 1, 3]);
+        assert_eq!(tracked.next_unused(), tracked.descriptor().derive(2));
+        tracked.mark_used(2);
+        assert_eq!(tracked.next_unused_index(), 4);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_roundtrip() {
+        extern crate serde_json;
+
+        let mut tracked = TrackedDescriptor::new(Descriptor::from_str(DESC).unwrap());
+        tracked.mark_used(0);
+        tracked.mark_used(5);
+        let ser = serde_json::to_string(&tracked).unwrap();
+        assert_eq!(ser, format!("[\"{}\",[0,5]]", tracked.descriptor()));
+        let de: TrackedDescriptor = serde_json::from_str(&ser).unwrap();
+        assert_eq!(de, tracked);
+    }
+}
@@ -0,0 +1,258 @@
+// Miniscript
+// Written in 2021 by rust-miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Descriptor Wallets
+//!
+//! A pair of receive and change descriptors along with their used
+//! derivation indices, providing the address generation, ownership checks
+//! and transaction scanning wallets are built on.
+//!
+
+use std::cmp;
+
+use elements::{self, secp256k1_zkp, Script};
+
+use super::{
+    Descriptor, DescriptorPublicKey, DescriptorSet, DescriptorTrait, OutputClass, TrackedDescriptor,
+};
+use Error;
+
+/// One of the two descriptors of a [Wallet]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Keychain {
+    /// The descriptor handed out to receive payments
+    Receive,
+    /// The descriptor receiving the change of the wallet's own transactions
+    Change,
+}
+
+/// A wallet made of a receive and a change descriptor
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Wallet {
+    /// The receive descriptor and its used indices
+    receive: TrackedDescriptor,
+    /// The change descriptor and its used indices
+    change: TrackedDescriptor,
+    /// Number of indices searched beyond the highest used one
+    gap_limit: u32,
+}
+
+impl Wallet {
+    /// Create a new wallet with no used indices. Scripts are searched up to
+    /// `gap_limit` indices beyond the highest used index of the descriptors.
+    pub fn new(
+        receive: Descriptor<DescriptorPublicKey>,
+        change: Descriptor<DescriptorPublicKey>,
+        gap_limit: u32,
+    ) -> Self {
+        Wallet {
+            receive: TrackedDescriptor::new(receive),
+            change: TrackedDescriptor::new(change),
+            gap_limit,
+        }
+    }
+
+    /// The descriptor of `keychain`, with its used indices
+    pub fn keychain(&self, keychain: Keychain) -> &TrackedDescriptor {
+        match keychain {
+            Keychain::Receive => &self.receive,
+            Keychain::Change => &self.change,
+        }
+    }
+
+    /// The address of the receive descriptor at `index`
+    pub fn receive_address<C: secp256k1_zkp::Verification>(
+        &self,
+        index: u32,
+        params: &'static elements::AddressParams,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<elements::Address, Error> {
+        self.address(Keychain::Receive, index, params, secp)
+    }
+
+    /// The address of the change descriptor at `index`
+    pub fn change_address<C: secp256k1_zkp::Verification>(
+        &self,
+        index: u32,
+        params: &'static elements::AddressParams,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<elements::Address, Error> {
+        self.address(Keychain::Change, index, params, secp)
+    }
+
+    /// The address of the descriptor of `keychain` at `index`
+    ///
+    /// Errors if the keys cannot be derived, and for descriptors without
+    /// address. Panics if given an index ≥ 2^31
+    pub fn address<C: secp256k1_zkp::Verification>(
+        &self,
+        keychain: Keychain,
+        index: u32,
+        params: &'static elements::AddressParams,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<elements::Address, Error> {
+        self.keychain(keychain)
+            .descriptor()
+            .derived_public_descriptor(index, secp)
+            .map_err(|e| Error::BadDescriptor(e.to_string()))?
+            .address(params)
+    }
+
+    /// The lowest index of `keychain` which has not been used
+    pub fn next_unused(&self, keychain: Keychain) -> u32 {
+        self.keychain(keychain).next_unused_index()
+    }
+
+    /// Mark `index` of `keychain` as used. Returns `false` if the index was
+    /// already marked as used.
+    pub fn mark_used(&mut self, keychain: Keychain, index: u32) -> bool {
+        match keychain {
+            Keychain::Receive => self.receive.mark_used(index),
+            Keychain::Change => self.change.mark_used(index),
+        }
+    }
+
+    /// The keychain and index producing `spk`, if any, searched up to the
+    /// gap limit
+    ///
+    /// Every scriptPubKey up to the gap limit is derived again on each
+    /// call. To check many scripts at once, use
+    /// [Wallet::scan_transaction] or the [Wallet::descriptor_set].
+    pub fn owns<C: secp256k1_zkp::Verification>(
+        &self,
+        spk: &Script,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Option<(Keychain, u32)> {
+        match self.descriptor_set().script_pubkeys(secp).get(spk) {
+            Some(&OutputClass::Receive { index, .. }) => Some((Keychain::Receive, index)),
+            Some(&OutputClass::Change { index, .. }) => Some((Keychain::Change, index)),
+            _ => None,
+        }
+    }
+
+    /// Classifies the outputs of `tx` like [DescriptorSet::classify_outputs],
+    /// marking the indices of the outputs paying to the wallet as used
+    pub fn scan_transaction<C: secp256k1_zkp::Verification>(
+        &mut self,
+        tx: &elements::Transaction,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Vec<OutputClass> {
+        let classes = self.descriptor_set().classify_outputs(tx, secp);
+        for class in &classes {
+            match *class {
+                OutputClass::Receive { index, .. } => {
+                    self.receive.mark_used(index);
+                }
+                OutputClass::Change { index, .. } => {
+                    self.change.mark_used(index);
+                }
+                OutputClass::Fee | OutputClass::External => {}
+            }
+        }
+        classes
+    }
+
+    /// The descriptors of the wallet, searched up to the gap limit beyond
+    /// the highest used index of either descriptor
+    pub fn descriptor_set(&self) -> DescriptorSet {
+        let used_end = |tracked: &TrackedDescriptor| {
+            tracked
+                .used_indices()
+                .last()
+                .map_or(0, |index| index.saturating_add(1))
+        };
+        let lookahead = cmp::max(used_end(&self.receive), used_end(&self.change))
+            .saturating_add(self.gap_limit);
+        DescriptorSet::new(
+            vec![self.receive.descriptor().clone()],
+            vec![self.change.descriptor().clone()],
+            lookahead,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::confidential;
+    use std::str::FromStr;
+
+    #[test]
+    fn wallet() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed";
+        let receive =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("elwpkh({}/0/*)", xpub)).unwrap();
+        let change = receive.to_change_descriptor().unwrap();
+        let mut wallet = Wallet::new(receive.clone(), change, 5);
+        let params = &elements::AddressParams::ELEMENTS;
+
+        let address = wallet.receive_address(3, params, &secp).unwrap();
+        let expected = receive.derived_public_descriptor(3, &secp).unwrap();
+        assert_eq!(address, expected.address(params).unwrap());
+        let change_address = wallet.change_address(3, params, &secp).unwrap();
+        assert_ne!(change_address, address);
+
+        let spk = address.script_pubkey();
+        assert_eq!(wallet.owns(&spk, &secp), Some((Keychain::Receive, 3)));
+        let change_spk = change_address.script_pubkey();
+        assert_eq!(wallet.owns(&change_spk, &secp), Some((Keychain::Change, 3)));
+        // Beyond the gap limit
+        let far = wallet.receive_address(7, params, &secp).unwrap();
+        assert_eq!(wallet.owns(&far.script_pubkey(), &secp), None);
+
+        // Scanning marks the outputs as used, which moves the gap limit
+        let txout = |script_pubkey: Script| elements::TxOut {
+            script_pubkey,
+            value: confidential::Value::Explicit(1000),
+            ..Default::default()
+        };
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![txout(spk), txout(Script::new())],
+        };
+        assert_eq!(wallet.next_unused(Keychain::Receive), 0);
+        assert!(wallet.mark_used(Keychain::Receive, 0));
+        assert!(wallet.mark_used(Keychain::Receive, 1));
+        assert!(wallet.mark_used(Keychain::Receive, 2));
+        let classes = wallet.scan_transaction(&tx, &secp);
+        assert_eq!(
+            classes[0],
+            OutputClass::Receive {
+                descriptor: 0,
+                index: 3
+            }
+        );
+        assert_eq!(classes[1], OutputClass::External);
+        assert_eq!(wallet.next_unused(Keychain::Receive), 4);
+        assert_eq!(wallet.next_unused(Keychain::Change), 0);
+        assert_eq!(
+            wallet.owns(&far.script_pubkey(), &secp),
+            Some((Keychain::Receive, 7))
+        );
+        assert!(wallet.keychain(Keychain::Receive).is_used(3));
+
+        // The gap limit starts after the highest used index, even when
+        // lower indices are unused
+        let far = wallet.receive_address(14, params, &secp).unwrap();
+        assert_eq!(wallet.owns(&far.script_pubkey(), &secp), None);
+        assert!(wallet.mark_used(Keychain::Change, 9));
+        assert_eq!(wallet.next_unused(Keychain::Change), 0);
+        assert_eq!(
+            wallet.owns(&far.script_pubkey(), &secp),
+            Some((Keychain::Receive, 14))
+        );
+    }
+}
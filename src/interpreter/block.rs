@@ -0,0 +1,199 @@
+// Miniscript
+// Written in 2021 by rust-miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Block Verification
+//!
+//! Batch interpretation of every input of an Elements block, intended for
+//! indexers which need to validate many transactions at once. A single
+//! secp context is used for the whole block and the sighash midstates of
+//! each transaction are computed only once, regardless of its number of
+//! inputs.
+//!
+
+use elements::{self, secp256k1_zkp, sighash};
+
+use super::{Error, Interpreter};
+
+/// A transaction input which failed to interpret correctly
+#[derive(Debug)]
+pub struct InputFailure {
+    /// Id of the spending transaction
+    pub txid: elements::Txid,
+    /// Index of the failing input in the spending transaction
+    pub vin: usize,
+    /// Reason of the failure
+    pub error: Error,
+}
+
+/// Interprets every input of `block`, returning the inputs which failed
+///
+/// `prevout` is called with the outpoint spent by each input and should
+/// return the corresponding output, or `None` if it is unknown, in which
+/// case the input is reported with an `Error::MissingPrevout`. Outputs
+/// created earlier in the same block must be returned by `prevout` as well.
+///
+/// Coinbase inputs are skipped, and so are pegin inputs since the coins
+/// they spend live on the parent chain. Like `Interpreter`, this assumes all
+/// spent coins are descriptor controlled: anything else is reported as a
+/// failure. Absolute timelocks are checked against the transaction locktime
+/// and relative ones against the input sequence number.
+pub fn verify_block<C, F>(
+    secp: &secp256k1_zkp::Secp256k1<C>,
+    block: &elements::Block,
+    mut prevout: F,
+) -> Vec<InputFailure>
+where
+    C: secp256k1_zkp::Verification,
+    F: FnMut(&elements::OutPoint) -> Option<elements::TxOut>,
+{
+    let mut failures = vec![];
+    for tx in &block.txdata {
+        let mut sighash_cache = sighash::SigHashCache::new(tx);
+        for (vin, input) in tx.input.iter().enumerate() {
+            if input.is_coinbase() || input.is_pegin() {
+                continue;
+            }
+            let result = prevout(&input.previous_output)
+                .ok_or(Error::MissingPrevout(input.previous_output))
+                .and_then(|txout| {
+                    let mut interpreter = Interpreter::from_txdata(
+                        &txout.script_pubkey,
+                        &input.script_sig,
+                        &input.witness.script_witness,
+                        tx.lock_time,
+                        input.sequence,
+                    )?;
+                    let vfyfn = interpreter.cached_sighash_verify(
+                        secp,
+                        &mut sighash_cache,
+                        vin,
+                        txout.value,
+                    );
+                    match interpreter.iter(vfyfn).filter_map(Result::err).next() {
+                        Some(error) => Err(error),
+                        None => Ok(()),
+                    }
+                });
+            if let Err(error) = result {
+                failures.push(InputFailure {
+                    txid: tx.txid(),
+                    vin,
+                    error,
+                });
+            }
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin;
+    use elements::hashes::Hash;
+    use elements::{confidential, BlockHash, TxMerkleNode};
+    use std::collections::HashMap;
+    use {Descriptor, DescriptorTrait};
+
+    #[test]
+    fn verify_block_inputs() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[3; 32]).unwrap();
+        let pk = bitcoin::PublicKey {
+            key: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+            compressed: true,
+        };
+        let desc = Descriptor::new_wpkh(pk).unwrap();
+        let funding = elements::TxOut {
+            script_pubkey: desc.script_pubkey(),
+            value: confidential::Value::Explicit(100_000),
+            ..Default::default()
+        };
+
+        let outpoint = |vout| elements::OutPoint {
+            txid: elements::Txid::hash(&[1]),
+            vout,
+        };
+        let txin = |vout| elements::TxIn {
+            previous_output: outpoint(vout),
+            is_pegin: false,
+            has_issuance: false,
+            script_sig: elements::Script::new(),
+            sequence: 0xffffffff,
+            asset_issuance: Default::default(),
+            witness: Default::default(),
+        };
+        let mut coinbase = txin(0);
+        coinbase.previous_output = elements::OutPoint::default();
+        let mut tx = elements::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![txin(0), txin(1), txin(2)],
+            output: vec![],
+        };
+
+        // Sign the first input and reuse its signature for the second one
+        let dummy_witness = vec![vec![0x30; 72], pk.to_bytes()];
+        let interpreter = Interpreter::from_txdata(
+            &funding.script_pubkey,
+            &tx.input[0].script_sig,
+            &dummy_witness,
+            0,
+            0,
+        )
+        .unwrap();
+        let msg = interpreter.sighash_message(&tx, 0, funding.value, elements::SigHashType::All);
+        let mut sig = secp.sign(&msg, &sk).serialize_der().to_vec();
+        sig.push(elements::SigHashType::All as u8);
+        for input in tx.input.iter_mut() {
+            input.witness.script_witness = vec![sig.clone(), pk.to_bytes()];
+        }
+
+        let block = elements::Block {
+            header: elements::BlockHeader {
+                version: 0x20000000,
+                prev_blockhash: BlockHash::default(),
+                merkle_root: TxMerkleNode::default(),
+                time: 0,
+                height: 1,
+                ext: Default::default(),
+            },
+            txdata: vec![
+                elements::Transaction {
+                    version: 2,
+                    lock_time: 0,
+                    input: vec![coinbase],
+                    output: vec![],
+                },
+                tx.clone(),
+            ],
+        };
+        let mut utxos = HashMap::new();
+        utxos.insert(outpoint(0), funding.clone());
+        utxos.insert(outpoint(1), funding.clone());
+
+        let failures = verify_block(&secp, &block, |outpoint| utxos.get(outpoint).cloned());
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].txid, tx.txid());
+        assert_eq!(failures[0].vin, 1);
+        match failures[0].error {
+            Error::PkEvaluationError(key) => assert_eq!(key, pk),
+            ref e => panic!("unexpected error {}", e),
+        }
+        assert_eq!(failures[1].vin, 2);
+        match failures[1].error {
+            Error::MissingPrevout(missing) => assert_eq!(missing, outpoint(2)),
+            ref e => panic!("unexpected error {}", e),
+        }
+    }
+}
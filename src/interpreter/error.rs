@@ -42,6 +42,8 @@ pub enum Error {
     InvalidSignature(bitcoin::PublicKey),
     /// Miniscript error
     Miniscript(::Error),
+    /// The output spent by a transaction input could not be found
+    MissingPrevout(elements::OutPoint),
     /// MultiSig requires 1 extra zero element apart from the `k` signatures
     MissingExtraZeroMultiSig,
     /// Script abortion because of incorrect dissatisfaction for multisig.
@@ -87,6 +89,9 @@ pub enum Error {
     VerifyFailed,
     /// Incorrect Covenant Witness
     IncorrectCovenantWitness,
+    /// The signature for the given pubkey did not verify against the
+    /// message supplied on the stack in `OP_CHECKSIGFROMSTACK`
+    CheckSigFromStackFailed(bitcoin::PublicKey),
     /// Covenant witness size mismatch
     /// eg: supplied a witness at
     /// nVersion with 5 bytes instead of 4
@@ -152,6 +157,7 @@ impl fmt::Display for Error {
             Error::NonEmptyWitness => f.write_str("legacy spend had nonempty witness"),
             Error::NonEmptyScriptSig => f.write_str("segwit spend had nonempty scriptsig"),
             Error::Miniscript(ref e) => write!(f, "parse error: {}", e),
+            Error::MissingPrevout(ref outpoint) => write!(f, "missing prevout {}", outpoint),
             Error::MissingExtraZeroMultiSig => f.write_str("CMS missing extra zero"),
             Error::MultiSigEvaluationError => {
                 f.write_str("CMS script aborted, incorrect satisfaction/dissatisfaction")
@@ -179,6 +185,11 @@ impl fmt::Display for Error {
                 "Covenant witness incorrect, the initial stack supplied for \
                 covenant global context is incorrect",
             ),
+            Error::CheckSigFromStackFailed(ref pk) => write!(
+                f,
+                "OP_CHECKSIGFROMSTACK signature check failed for pk {}",
+                pk
+            ),
             Error::CovWitnessSizeErr {
                 pos,
                 expected,
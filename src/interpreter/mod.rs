@@ -20,25 +20,29 @@
 //!
 
 use bitcoin::PublicKey;
-use elements::{self, secp256k1_zkp, SigHash};
+use elements::{self, secp256k1_zkp};
 use elements::{confidential, sighash};
 use elements::{
-    hashes::{hash160, ripemd160, sha256, sha256d, Hash, HashEngine},
+    hashes::{hash160, ripemd160, sha256, sha256d, Hash},
     SigHashType,
 };
 use miniscript::context::NoChecks;
 use miniscript::ScriptContext;
+use std::cmp;
+use std::ops::Deref;
 use util;
 use Miniscript;
 use Terminal;
-use {Descriptor, ElementsSig, ToPublicKey};
+use {Descriptor, DescriptorPublicKey, DescriptorTrait, ElementsSig, ToPublicKey, TranslatePk2};
 
+mod block;
 mod error;
 mod inner;
 mod stack;
 
 use {CovenantExt, Extension};
 
+pub use self::block::{verify_block, InputFailure};
 pub use self::error::Error;
 pub use self::stack::{Element, Stack};
 
@@ -68,6 +72,29 @@ impl<'txin> Interpreter<'txin, CovenantExt> {
     ) -> Result<Self, Error> {
         Interpreter::from_txdata_ext(spk, script_sig, witness, age, height)
     }
+
+    /// Constructs an interpreter from the data of a transaction spending
+    /// the output of `descriptor` at derivation `index`
+    ///
+    /// The keys of the descriptor are derived at `index`, so that the keys
+    /// revealed by the spend, for `pkh` fragments and for `pkh` and `wpkh`
+    /// descriptors, are checked against the hashes of the derived keys.
+    /// Errors if the keys cannot be derived. Panics if given an index ≥ 2^31
+    pub fn from_descriptor<C: secp256k1_zkp::Verification>(
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        index: u32,
+        script_sig: &'txin elements::Script,
+        witness: &'txin [Vec<u8>],
+        age: u32,
+        height: u32,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+    ) -> Result<Self, Error> {
+        let derived = descriptor
+            .derive(index)
+            .translate_pk2(|xpk| xpk.derive_public_key(secp))
+            .map_err(|e| Error::Miniscript(::Error::BadDescriptor(e.to_string())))?;
+        Interpreter::from_txdata(&derived.script_pubkey(), script_sig, witness, age, height)
+    }
 }
 
 impl<'txin, Ext> Interpreter<'txin, Ext>
@@ -110,7 +137,7 @@ where
     ///
     /// Running the iterator through will consume the internal stack of the
     /// `Iterpreter`, and it should not be used again after this.
-    pub fn iter<'iter, F>(&'iter mut self, verify_sig: F) -> Iter<'txin, 'iter, Ext, F>
+    pub fn iter<'iter, F>(&'iter mut self, verify_sig: F) -> Iter<'iter, 'txin, Ext, F>
     where
         Ext: Extension<PublicKey>,
         F: FnMut(&PublicKey, ElementsSig) -> bool,
@@ -147,6 +174,33 @@ where
         }
     }
 
+    /// Runs the interpreter through, like [Interpreter::iter], and reports
+    /// the highest absolute and relative timelocks proven by the spend, that
+    /// is the minimum `nLockTime` and `nSequence` the spending transaction
+    /// had to set. Like the constraints returned by [Interpreter::iter],
+    /// this includes timelocks of fragments which did not contribute to the
+    /// satisfaction.
+    ///
+    /// Errors if the script is not satisfied.
+    pub fn required_timelocks<F>(&mut self, verify_sig: F) -> Result<RequiredTimelocks, Error>
+    where
+        F: FnMut(&PublicKey, ElementsSig) -> bool,
+    {
+        let mut ret = RequiredTimelocks::default();
+        for constraint in self.iter(verify_sig) {
+            match constraint? {
+                SatisfiedConstraint::AbsoluteTimeLock { time } => {
+                    ret.lock_time = cmp::max(ret.lock_time, Some(*time))
+                }
+                SatisfiedConstraint::RelativeTimeLock { time } => {
+                    ret.sequence = cmp::max(ret.sequence, Some(*time))
+                }
+                _ => {}
+            }
+        }
+        Ok(ret)
+    }
+
     /// Outputs a "descriptor" string which reproduces the spent coins
     ///
     /// This may not represent the original descriptor used to produce the transaction,
@@ -216,6 +270,18 @@ where
         sighash_type: elements::SigHashType,
     ) -> secp256k1_zkp::Message {
         let mut sighash_cache = sighash::SigHashCache::new(unsigned_tx);
+        self.cached_sighash_message(&mut sighash_cache, input_idx, amount, sighash_type)
+    }
+
+    /// Same as `sighash_message`, but reuses the transaction-wide midstates
+    /// stored in `sighash_cache` across calls
+    fn cached_sighash_message<R: Deref<Target = elements::Transaction>>(
+        &self,
+        sighash_cache: &mut sighash::SigHashCache<R>,
+        input_idx: usize,
+        amount: confidential::Value,
+        sighash_type: elements::SigHashType,
+    ) -> secp256k1_zkp::Message {
         let hash = if self.is_legacy() {
             sighash_cache.legacy_sighash(input_idx, &self.script_code, sighash_type)
         } else {
@@ -234,35 +300,35 @@ where
         input_idx: usize,
         amount: confidential::Value,
     ) -> impl Fn(&PublicKey, ElementsSig) -> bool + 'a {
+        let mut sighash_cache = sighash::SigHashCache::new(unsigned_tx);
+        self.cached_sighash_verify(secp, &mut sighash_cache, input_idx, amount)
+    }
+
+    /// Same as `sighash_verify`, but reuses the transaction-wide midstates
+    /// stored in `sighash_cache` across calls
+    fn cached_sighash_verify<'a, C, R>(
+        &self,
+        secp: &'a secp256k1_zkp::Secp256k1<C>,
+        sighash_cache: &mut sighash::SigHashCache<R>,
+        input_idx: usize,
+        amount: confidential::Value,
+    ) -> impl Fn(&PublicKey, ElementsSig) -> bool + 'a
+    where
+        C: secp256k1_zkp::Verification,
+        R: Deref<Target = elements::Transaction>,
+    {
         // Precompute all sighash types because the borrowck doesn't like us
         // pulling self into the closure
+        let mut sighash = |sighash_type| {
+            self.cached_sighash_message(sighash_cache, input_idx, amount, sighash_type)
+        };
         let sighashes = [
-            self.sighash_message(unsigned_tx, input_idx, amount, elements::SigHashType::All),
-            self.sighash_message(unsigned_tx, input_idx, amount, elements::SigHashType::None),
-            self.sighash_message(
-                unsigned_tx,
-                input_idx,
-                amount,
-                elements::SigHashType::Single,
-            ),
-            self.sighash_message(
-                unsigned_tx,
-                input_idx,
-                amount,
-                elements::SigHashType::AllPlusAnyoneCanPay,
-            ),
-            self.sighash_message(
-                unsigned_tx,
-                input_idx,
-                amount,
-                elements::SigHashType::NonePlusAnyoneCanPay,
-            ),
-            self.sighash_message(
-                unsigned_tx,
-                input_idx,
-                amount,
-                elements::SigHashType::SinglePlusAnyoneCanPay,
-            ),
+            sighash(elements::SigHashType::All),
+            sighash(elements::SigHashType::None),
+            sighash(elements::SigHashType::Single),
+            sighash(elements::SigHashType::AllPlusAnyoneCanPay),
+            sighash(elements::SigHashType::NonePlusAnyoneCanPay),
+            sighash(elements::SigHashType::SinglePlusAnyoneCanPay),
         ];
 
         move |pk: &PublicKey, (sig, sighash_type)| {
@@ -294,6 +360,16 @@ pub enum HashLockType<'intp> {
     Ripemd160(&'intp ripemd160::Hash),
 }
 
+/// The timelocks proven by a spend, as returned by
+/// [Interpreter::required_timelocks]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct RequiredTimelocks {
+    /// The highest `after` value, which `nLockTime` must reach
+    pub lock_time: Option<u32>,
+    /// The highest `older` value, which `nSequence` must reach
+    pub sequence: Option<u32>,
+}
+
 /// A satisfied Miniscript condition (Signature, Hashlock, Timelock)
 /// 'intp represents the lifetime of descriptor and `stack represents
 /// the lifetime of witness
@@ -303,8 +379,8 @@ pub enum SatisfiedConstraint<'intp, 'txin, Ext: 'intp + Extension<PublicKey>> {
     PublicKey {
         /// The bitcoin key
         key: &'intp PublicKey,
-        /// corresponding signature
-        sig: secp256k1_zkp::Signature,
+        /// corresponding signature and its sighash type
+        sig: ElementsSig,
     },
     ///PublicKeyHash, corresponding pubkey and signature
     PublicKeyHash {
@@ -312,8 +388,8 @@ pub enum SatisfiedConstraint<'intp, 'txin, Ext: 'intp + Extension<PublicKey>> {
         keyhash: &'intp hash160::Hash,
         /// Corresponding public key
         key: PublicKey,
-        /// Corresponding signature for the hash
-        sig: secp256k1_zkp::Signature,
+        /// Corresponding signature for the hash and its sighash type
+        sig: ElementsSig,
     },
     ///Hashlock and preimage for SHA256
     HashLock {
@@ -840,23 +916,22 @@ where
                 //terminate the next() function in the subsequent call
                 self.cov = None;
                 // Do the checkSigFromStackCheck
+                // The covenant script hashes the sighash message with OP_SHA256
+                // before passing it to OP_CHECKSIGFROMSTACK
                 let sighash_msg: Vec<u8> = self.stack.0[1..]
                     .into_iter()
                     .rev()
                     .map(|x| Vec::from(x.as_push()))
                     .flatten()
                     .collect();
-                let mut eng = SigHash::engine();
-                eng.input(&sighash_msg);
-                let sighash_u256 = SigHash::from_engine(eng);
-                let msg = elements::secp256k1_zkp::Message::from_slice(&sighash_u256[..]).unwrap();
+                let sha2_msg = sha256::Hash::hash(&sighash_msg);
 
                 // TODO: THIS SHOULD BE A SEPARATE PARAMETER TO THE FUNCTION, BUT SINCE
                 // IT MIGHT ELSEWHERE, CONSIDER MAKING IT A SEPARATE METHOD. RIGHT NOW,
                 // THIS IS CREATING A NEW CONTEXT WHICH IS EXPENSIVE
                 let secp = secp256k1_zkp::Secp256k1::verification_only();
-                if secp.verify(&msg, &sig, &pk.key).is_err() {
-                    return Some(Err(Error::PkEvaluationError(pk.clone().to_public_key())));
+                if !verify_sig_from_stack(&secp, pk, &sig.0, &sha2_msg[..]) {
+                    return Some(Err(Error::CheckSigFromStackFailed(*pk)));
                 }
                 self.stack.0.clear();
                 self.stack.push(stack::Element::Satisfied);
@@ -896,7 +971,7 @@ fn verify_sersig<'txin, F>(
     verify_sig: F,
     pk: &PublicKey,
     sigser: &[u8],
-) -> Result<secp256k1_zkp::Signature, Error>
+) -> Result<ElementsSig, Error>
 where
     F: FnOnce(&PublicKey, ElementsSig) -> bool,
 {
@@ -904,7 +979,7 @@ where
         let sighashtype = elements::SigHashType::from_u32(*sighash_byte as u32);
         let sig = secp256k1_zkp::Signature::from_der(sig)?;
         if verify_sig(pk, (sig, sighashtype)) {
-            Ok(sig)
+            Ok((sig, sighashtype))
         } else {
             Err(Error::InvalidSignature(*pk))
         }
@@ -913,6 +988,19 @@ where
     }
 }
 
+/// Emulates `OP_CHECKSIGFROMSTACK`: checks that `sig` is a valid signature
+/// by `pk` over the SHA256 hash of the raw message `msg` taken from the stack
+fn verify_sig_from_stack<C: secp256k1_zkp::Verification>(
+    secp: &secp256k1_zkp::Secp256k1<C>,
+    pk: &bitcoin::PublicKey,
+    sig: &secp256k1_zkp::Signature,
+    msg: &[u8],
+) -> bool {
+    let msg_hash = sha256::Hash::hash(msg);
+    let msg = secp256k1_zkp::Message::from_slice(&msg_hash[..]).expect("32 byte hash");
+    secp.verify(&msg, sig, &pk.key).is_ok()
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -923,17 +1011,47 @@ mod tests {
     use bitcoin::hashes::{hash160, ripemd160, sha256, sha256d, Hash};
     use elements::secp256k1_zkp::{self, Secp256k1, VerifyOnly};
     use miniscript::context::NoChecks;
+    use std::collections::HashMap;
     use ElementsSig;
     use Miniscript;
     use MiniscriptKey;
     use ToPublicKey;
+    use {DescriptorTrait, Satisfier};
+
+    // Signs with the key and any timelock, the signatures are not checked
+    struct KeySat(bitcoin::PublicKey);
+    impl Satisfier<bitcoin::PublicKey> for KeySat {
+        fn lookup_sig(&self, _: &bitcoin::PublicKey) -> Option<ElementsSig> {
+            let sig = secp256k1_zkp::Signature::from_compact(&[1; 64]).unwrap();
+            Some((sig, SigHashType::All))
+        }
+
+        fn lookup_pkh_sig(
+            &self,
+            hash: &hash160::Hash,
+        ) -> Option<(bitcoin::PublicKey, ElementsSig)> {
+            if self.0.to_pubkeyhash() == *hash {
+                Some((self.0, self.lookup_sig(&self.0)?))
+            } else {
+                None
+            }
+        }
+
+        fn check_older(&self, _: u32) -> bool {
+            true
+        }
+
+        fn check_after(&self, _: u32) -> bool {
+            true
+        }
+    }
 
     fn setup_keys_sigs(
         n: usize,
     ) -> (
         Vec<bitcoin::PublicKey>,
         Vec<Vec<u8>>,
-        Vec<secp256k1_zkp::Signature>,
+        Vec<ElementsSig>,
         secp256k1_zkp::Message,
         Secp256k1<VerifyOnly>,
     ) {
@@ -956,7 +1074,7 @@ mod tests {
                 compressed: true,
             };
             let sig = secp_sign.sign(&msg, &sk);
-            secp_sigs.push(sig);
+            secp_sigs.push((sig, SigHashType::All));
             let mut sigser = sig.serialize_der().to_vec();
             sigser.push(0x01); // sighash_all
             pks.push(pk);
@@ -965,6 +1083,26 @@ mod tests {
         (pks, der_sigs, secp_sigs, msg, secp_verify)
     }
 
+    #[test]
+    fn check_sig_from_stack() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = bitcoin::PublicKey {
+            key: secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk),
+            compressed: true,
+        };
+        let raw_msg = b"some message pushed on the stack";
+        let msg_hash = sha256::Hash::hash(&raw_msg[..]);
+        let sig = secp.sign(
+            &secp256k1_zkp::Message::from_slice(&msg_hash[..]).unwrap(),
+            &sk,
+        );
+        assert!(verify_sig_from_stack(&secp, &pk, &sig, &raw_msg[..]));
+        // The message is hashed exactly once
+        assert!(!verify_sig_from_stack(&secp, &pk, &sig, &msg_hash[..]));
+        assert!(!verify_sig_from_stack(&secp, &pk, &sig, b"another message"));
+    }
+
     #[test]
     fn sat_constraints() {
         let (pks, der_sigs, secp_sigs, sighash, secp) = setup_keys_sigs(10);
@@ -1027,6 +1165,22 @@ mod tests {
             }]
         );
 
+        //Check that the sighash type of the signature is reported
+        let mut sig_none = der_sigs[0].clone();
+        *sig_none.last_mut().unwrap() = SigHashType::None as u8;
+        let mut stack = Stack::from(vec![stack::Element::Push(&sig_none)]);
+        let mut vfyfn = vfyfn_.clone(); // sigh rust 1.29...
+        let constraints = from_stack(&mut vfyfn, &mut stack, &pk);
+        let pk_satisfied: Result<Vec<SatisfiedConstraint<CovenantExt>>, Error> =
+            constraints.collect();
+        assert_eq!(
+            pk_satisfied.unwrap(),
+            vec![SatisfiedConstraint::PublicKey {
+                key: &pks[0],
+                sig: (secp_sigs[0].0, SigHashType::None),
+            }]
+        );
+
         //Check Pk failure with wrong signature
         let mut stack = Stack::from(vec![stack::Element::Dissatisfied]);
         let mut vfyfn = vfyfn_.clone(); // sigh rust 1.29...
@@ -1410,5 +1564,148 @@ mod tests {
         let multi_error: Result<Vec<SatisfiedConstraint<CovenantExt>>, Error> =
             constraints.collect();
         assert!(multi_error.is_err());
+
+        //Check that satisfactions of or_i and the l: and u: wrappers use the
+        //right branch selector and are accepted by the interpreter
+        let or_i = ms_str!("or_i(c:pk_k({}),c:pk_k({}))", pks[0], pks[1]);
+        //The maximum witness size is that of the largest branch: a 73 byte
+        //signature with its length prefix and the selector with its own
+        let cases = vec![
+            (or_i.clone(), 0, vec![1], 73 + 2),
+            (or_i, 1, vec![], 73 + 2),
+            (ms_str!("lc:pk_k({})", pks[1]), 1, vec![], 73 + 1),
+            (ms_str!("uc:pk_k({})", pks[0]), 0, vec![1], 73 + 2),
+        ];
+        for (ms, i, selector, max_sat_size) in cases {
+            let mut sigs = HashMap::new();
+            sigs.insert(pks[i], secp_sigs[i]);
+            let witness = ms.satisfy(&sigs).unwrap();
+            assert_eq!(witness.last(), Some(&selector));
+            assert_eq!(ms.ext.max_sat_size.unwrap().0, max_sat_size);
+            // With a signature of the maximum 72 bytes, the witness of the
+            // largest branch is exactly as large as estimated
+            let mut max_witness = witness.clone();
+            max_witness[0] = vec![0; 72];
+            let max_size: usize = max_witness.iter().map(|elem| elem.len() + 1).sum();
+            assert_eq!(max_size, 73 + selector.len() + 1);
+            assert!(max_size <= max_sat_size);
+
+            let mut stack: Stack = witness
+                .iter()
+                .map(stack::Element::from)
+                .collect::<Vec<_>>()
+                .into();
+            let mut vfyfn = vfyfn_.clone(); // sigh rust 1.29...
+            let constraints = from_stack(&mut vfyfn, &mut stack, &ms);
+            let or_i_satisfied: Result<Vec<SatisfiedConstraint<CovenantExt>>, Error> =
+                constraints.collect();
+            assert_eq!(
+                or_i_satisfied.unwrap(),
+                vec![SatisfiedConstraint::PublicKey {
+                    key: &pks[i],
+                    sig: secp_sigs[i],
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn from_descriptor() {
+        use std::collections::HashSet;
+        use std::str::FromStr;
+
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "tpubD6NzVbkrYhZ4YqYr3amYH15zjxHvBkUUeadieW8AxTZC7aY2L8aPSk3tpW6yW1QnWzXAB7zoiaNMfwXPPz9S68ZCV4yWvkVXjdeksLskCed/0/*";
+        for s in &["elpkh({})", "elwpkh({})", "elwsh(pkh({}))"] {
+            let desc = Descriptor::<DescriptorPublicKey>::from_str(&s.replace("{}", xpub)).unwrap();
+            let mut hashes = HashSet::new();
+            for index in 0..3 {
+                let derived = desc
+                    .derive(index)
+                    .translate_pk2(|xpk| xpk.derive_public_key(&secp))
+                    .unwrap();
+                let key = DescriptorPublicKey::from_str(xpub)
+                    .unwrap()
+                    .derive(index)
+                    .derive_public_key(&secp)
+                    .unwrap();
+                let (witness, script_sig) = derived.get_satisfaction(KeySat(key)).unwrap();
+
+                let mut interpreter =
+                    Interpreter::from_descriptor(&desc, index, &script_sig, &witness, 0, 0, &secp)
+                        .unwrap();
+                for constraint in interpreter.iter(|_, _| true) {
+                    match constraint.unwrap() {
+                        SatisfiedConstraint::PublicKey { key, .. } => {
+                            hashes.insert(key.to_pubkeyhash());
+                        }
+                        SatisfiedConstraint::PublicKeyHash { keyhash, key, .. } => {
+                            assert_eq!(key.to_pubkeyhash(), *keyhash);
+                            hashes.insert(*keyhash);
+                        }
+                        _ => {}
+                    }
+                }
+
+                // The key does not match the one derived at another index
+                let other = Interpreter::from_descriptor(
+                    &desc,
+                    index + 1,
+                    &script_sig,
+                    &witness,
+                    0,
+                    0,
+                    &secp,
+                );
+                assert!(other.is_err(), "{} {}", s, index);
+            }
+            assert_eq!(hashes.len(), 3, "{}", s);
+        }
+    }
+
+    #[test]
+    fn required_timelocks() {
+        use std::str::FromStr;
+
+        let pk = bitcoin::PublicKey::from_str(
+            "020000000000000000000000000000000000000000000000000000000000000002",
+        )
+        .unwrap();
+        let run = |s: &str, lock_time: u32, sequence: u32| {
+            let desc =
+                Descriptor::<bitcoin::PublicKey>::from_str(&s.replace("{}", &pk.to_string()))
+                    .unwrap();
+            let (witness, script_sig) = desc.get_satisfaction(KeySat(pk)).unwrap();
+            let mut interpreter = Interpreter::from_txdata(
+                &desc.script_pubkey(),
+                &script_sig,
+                &witness,
+                lock_time,
+                sequence,
+            )
+            .unwrap();
+            interpreter.required_timelocks(|_, _| true)
+        };
+
+        let desc = "elwsh(and_v(v:pk({}),and_v(v:after(100),older(10))))";
+        let expected = RequiredTimelocks {
+            lock_time: Some(100),
+            sequence: Some(10),
+        };
+        assert_eq!(run(desc, 100, 10).unwrap(), expected);
+        assert_eq!(run(desc, 500, 20).unwrap(), expected);
+        assert!(run(desc, 99, 10).is_err());
+        assert!(run(desc, 100, 9).is_err());
+
+        // The highest timelock is required
+        let desc = "elwsh(and_v(v:pk({}),and_v(v:older(10),older(20))))";
+        let required = run(desc, 0, 20).unwrap();
+        assert_eq!(required.lock_time, None);
+        assert_eq!(required.sequence, Some(20));
+
+        assert_eq!(
+            run("elwpkh({})", 0, 0).unwrap(),
+            RequiredTimelocks::default()
+        );
     }
 }
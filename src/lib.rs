@@ -163,10 +163,11 @@ use elements::{opcodes, script, secp256k1_zkp, secp256k1_zkp::Secp256k1};
 pub use descriptor::{Descriptor, DescriptorPublicKey, DescriptorTrait};
 pub use extensions::{CovenantExt, Extension, NoExt};
 pub use interpreter::Interpreter;
+pub use miniscript::cache::MiniscriptCache;
 pub use miniscript::context::{BareCtx, Legacy, ScriptContext, Segwitv0};
 pub use miniscript::decode::Terminal;
 pub use miniscript::satisfy::{elementssig_from_rawsig, elementssig_to_rawsig};
-pub use miniscript::satisfy::{ElementsSig, Preimage32, Satisfier};
+pub use miniscript::satisfy::{ElementsSig, LoggingSatisfier, Preimage32, Satisfier};
 pub use miniscript::Miniscript;
 
 /// Same as upstream [`TranslatePk`] but with support for extensions
@@ -22,8 +22,10 @@ use miniscript::iter::PkPkh;
 use std::collections::HashSet;
 use std::fmt;
 
+use policy::semantic::Policy;
+use policy::Liftable;
 use Extension;
-use {Miniscript, MiniscriptKey, ScriptContext};
+use {Miniscript, MiniscriptKey, ScriptContext, Terminal};
 /// Possible reasons Miniscript guarantees can fail
 /// We currently mark Miniscript as Non-Analyzable if
 /// 1. It is unsafe(does not require a digital signature to spend it)
@@ -69,6 +71,17 @@ impl fmt::Display for AnalysisError {
 
 impl error::Error for AnalysisError {}
 
+/// Why a fragment is trivial, as reported by
+/// [Miniscript::find_trivial_fragments]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Triviality {
+    /// The fragment is satisfied without any signature, preimage or
+    /// timelock
+    AlwaysTrue,
+    /// The fragment can never be satisfied, making its branch dead
+    AlwaysFalse,
+}
+
 impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension<Pk>> Miniscript<Pk, Ctx, Ext> {
     /// Whether all spend paths of miniscript require a signature
     pub fn requires_sig(&self) -> bool {
@@ -113,6 +126,61 @@ impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension<Pk>> Miniscript<Pk, C
         unique_pkhs_len != all_pkhs_len
     }
 
+    /// Finds the fragments whose semantics are trivially true or trivially
+    /// false, which usually indicate a mistake in a hand-written Miniscript
+    /// or in the policy it was compiled from.
+    ///
+    /// Each fragment is given by its position, the indices of the children
+    /// as returned by [Miniscript::branches] from the root down to it, and
+    /// only the outermost trivial fragments are reported. The constants of
+    /// the `t:`, `l:` and `u:` wrappers and of `and_n` are not reported.
+    /// Fragments which cannot be lifted, such as covenant extensions or
+    /// fragments mixing timelocks, are never trivial.
+    pub fn find_trivial_fragments(&self) -> Vec<(Vec<usize>, Triviality)> {
+        let mut ret = vec![];
+        self.find_trivial_fragments_at(&mut vec![], &mut ret);
+        ret
+    }
+
+    fn find_trivial_fragments_at(
+        &self,
+        position: &mut Vec<usize>,
+        ret: &mut Vec<(Vec<usize>, Triviality)>,
+    ) {
+        // Lifting the whole miniscript would reject unsatisfiable fragments
+        // for exceeding the resource limits
+        let lifted = if self.has_mixed_timelocks() {
+            None
+        } else {
+            self.as_inner().lift().ok()
+        };
+        match lifted {
+            Some(Policy::Trivial) => {
+                ret.push((position.clone(), Triviality::AlwaysTrue));
+                return;
+            }
+            Some(Policy::Unsatisfiable) => {
+                ret.push((position.clone(), Triviality::AlwaysFalse));
+                return;
+            }
+            _ => {}
+        }
+        for (i, child) in self.branches().into_iter().enumerate() {
+            let is_builtin = match (i, &self.node) {
+                (1, &Terminal::AndV(..)) => child.node == Terminal::True,
+                (0, &Terminal::OrI(..)) | (1, &Terminal::OrI(..)) | (2, &Terminal::AndOr(..)) => {
+                    child.node == Terminal::False
+                }
+                _ => false,
+            };
+            if !is_builtin {
+                position.push(i);
+                child.find_trivial_fragments_at(position, ret);
+                position.pop();
+            }
+        }
+    }
+
     /// Check whether the underlying Miniscript is safe under the current context
     /// Lifting these polices would create a semantic representation that does
     /// not represent the underlying semantics when miniscript is spent.
@@ -0,0 +1,157 @@
+// Miniscript
+// Written in 2021 by rust-miniscript developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Parsing Cache
+//!
+//! Memoization of parsed and type-checked Miniscripts, for services which
+//! repeatedly parse the same strings. Type inference and the sanity checks
+//! run once per distinct string, later parses returning a shared copy of
+//! the first result. The cache holds at most a fixed number of entries,
+//! evicting the least recently used one when full.
+//!
+
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use miniscript::context::ScriptContext;
+use miniscript::Miniscript;
+use {Error, Extension, MiniscriptKey, NoExt};
+
+/// A bounded least recently used cache of parsed Miniscripts, keyed by
+/// their string representation
+///
+/// Only successful parses are cached.
+#[derive(Debug)]
+pub struct MiniscriptCache<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension<Pk> = NoExt> {
+    /// Maximum number of cached Miniscripts
+    capacity: usize,
+    /// Cached Miniscripts
+    entries: HashMap<String, Entry<Pk, Ctx, Ext>>,
+    /// Keys of the cached Miniscripts, by the tick of their last use
+    recency: BTreeMap<u64, String>,
+    /// Incremented on every lookup
+    tick: u64,
+}
+
+/// A cached Miniscript along with the tick of its last use
+#[derive(Debug)]
+struct Entry<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension<Pk>> {
+    ms: Arc<Miniscript<Pk, Ctx, Ext>>,
+    last_use: u64,
+}
+
+impl<Pk, Ctx, Ext> MiniscriptCache<Pk, Ctx, Ext>
+where
+    Pk: MiniscriptKey + FromStr,
+    Pk::Hash: FromStr,
+    Ctx: ScriptContext,
+    Ext: Extension<Pk>,
+    <Pk as FromStr>::Err: ToString,
+    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
+{
+    /// Create an empty cache holding at most `capacity` Miniscripts. A
+    /// capacity of 0 disables caching.
+    pub fn new(capacity: usize) -> Self {
+        MiniscriptCache {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Parse a Miniscript like [Miniscript::from_str], returning the cached
+    /// result if `s` was parsed before
+    pub fn parse(&mut self, s: &str) -> Result<Arc<Miniscript<Pk, Ctx, Ext>>, Error> {
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(s) {
+            self.recency.remove(&entry.last_use);
+            self.recency.insert(self.tick, s.to_owned());
+            entry.last_use = self.tick;
+            return Ok(Arc::clone(&entry.ms));
+        }
+
+        let ms = Arc::new(Miniscript::from_str(s)?);
+        if self.capacity == 0 {
+            return Ok(ms);
+        }
+        if self.entries.len() >= self.capacity {
+            let oldest = *self.recency.keys().next().expect("cache is not empty");
+            let key = self.recency.remove(&oldest).expect("oldest key exists");
+            self.entries.remove(&key);
+        }
+        let entry = Entry {
+            ms: Arc::clone(&ms),
+            last_use: self.tick,
+        };
+        self.entries.insert(s.to_owned(), entry);
+        self.recency.insert(self.tick, s.to_owned());
+        Ok(ms)
+    }
+
+    /// Number of cached Miniscripts
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove all cached Miniscripts
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::PublicKey;
+    use miniscript::Segwitv0;
+
+    #[test]
+    fn lru_eviction() {
+        let mut cache = MiniscriptCache::<PublicKey, Segwitv0>::new(2);
+        let a = "pk(020000000000000000000000000000000000000000000000000000000000000002)";
+        let b = "pk(03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd)";
+        let c = "and_v(v:pk(020000000000000000000000000000000000000000000000000000000000000002),older(144))";
+
+        let first = cache.parse(a).unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.parse(a).unwrap()));
+        assert_eq!(*first, Miniscript::from_str(a).unwrap());
+        cache.parse(b).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        // `a` was used last, so `b` is evicted
+        cache.parse(a).unwrap();
+        cache.parse(c).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(Arc::ptr_eq(&first, &cache.parse(a).unwrap()));
+        assert!(cache.entries.contains_key(c));
+        assert!(!cache.entries.contains_key(b));
+
+        // Failed parses are not cached
+        assert!(cache.parse("pk(nope)").is_err());
+        assert_eq!(cache.len(), 2);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        let mut disabled = MiniscriptCache::<PublicKey, Segwitv0>::new(0);
+        disabled.parse(a).unwrap();
+        assert!(disabled.is_empty());
+    }
+}
@@ -338,11 +338,11 @@ pub fn lex(script: &script::Script) -> Result<Vec<Token>, Error> {
                             x.copy_from_slice(bytes);
                             ret.push(Token::Hash32(x));
                         }
-                        33 | 65 => {
-                            ret.push(Token::Pubkey(
-                                PublicKey::from_slice(bytes).map_err(Error::BadPubkey)?,
-                            ));
-                        }
+                        33 | 65 => match PublicKey::from_slice(bytes) {
+                            Ok(pk) => ret.push(Token::Pubkey(pk)),
+                            // May be concatenated, checked once lexed
+                            Err(_) => ret.push(Token::Push(bytes.to_owned())),
+                        },
                         _ => {
                             match script::read_scriptint(bytes) {
                                 Ok(v) if v >= 0 => {
@@ -414,5 +414,19 @@ pub fn lex(script: &script::Script) -> Result<Vec<Token>, Error> {
             script::Instruction::Op(op) => return Err(Error::InvalidOpcode(op)),
         };
     }
+    // Pubkey sized pushes which are not pubkeys are only allowed as the
+    // prefix of a concatenation
+    for (i, token) in ret.iter().enumerate() {
+        if let Token::Push(ref bytes) = *token {
+            if bytes.len() != 33 && bytes.len() != 65 {
+                continue;
+            }
+            if let Err(e) = PublicKey::from_slice(bytes) {
+                if ret.get(i + 1) != Some(&Token::Swap) || ret.get(i + 2) != Some(&Token::Cat) {
+                    return Err(Error::BadPubkey(e));
+                }
+            }
+        }
+    }
     Ok(ret)
 }
@@ -27,6 +27,12 @@ pub const HEIGHT_TIME_THRESHOLD: u32 = 500_000_000;
 // https://github.com/bitcoin/bips/blob/master/bip-0112.mediawiki
 pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
 
+/// Mask extracting the relative lock-time from a sequence number
+/* If nSequence encodes a relative lock-time, this mask is
+ * applied to extract that lock-time from the sequence field. */
+// https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000ffff;
+
 /// Disable flag for sequence locktime
 /* Below flags apply in the context of BIP 68*/
 /* If this flag set, nSequence is NOT interpreted as a
@@ -34,6 +34,7 @@ pub use self::context::{BareCtx, Legacy, Segwitv0};
 
 pub mod analyzable;
 pub mod astelem;
+pub mod cache;
 pub(crate) mod context;
 pub mod decode;
 pub mod iter;
@@ -368,6 +369,110 @@ impl<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension<Pk>> Miniscript<Pk, C
         }
     }
 
+    /// Attempt to produce a satisfying witness for the witness script
+    /// represented by the parse tree, taking the spending path chosen
+    /// by `path` instead of the cheapest one.
+    ///
+    /// Each `andor`, `or_b`, `or_c`, `or_d` and `or_i` fragment on the
+    /// chosen path consumes the next entry of `path`, in script order:
+    /// `0` selects the first branch (`X` and `Y` in `andor(X,Y,Z)`) and `1` the
+    /// second one (`Z`). Errors if `path` has no entry left for such a
+    /// fragment, or has entries left once the chosen path is satisfied.
+    /// Forcing a path bypasses the non-malleability preferences of
+    /// [Miniscript::satisfy].
+    pub fn satisfy_path<S: satisfy::Satisfier<Pk>>(
+        &self,
+        satisfier: S,
+        path: &[usize],
+    ) -> Result<Vec<Vec<u8>>, Error>
+    where
+        Pk: ToPublicKey,
+    {
+        let mut path = path.iter();
+        let sat = satisfy::Satisfaction::satisfy_path(
+            &self.node,
+            &satisfier,
+            self.ty.mall.safe,
+            &mut path,
+        )?;
+        satisfy::check_path_consumed(path.as_slice())?;
+        match sat.stack {
+            satisfy::Witness::Stack(stack) => {
+                Ctx::check_witness::<Pk, Ctx, Ext>(&stack)?;
+                Ok(stack)
+            }
+            satisfy::Witness::Unavailable | satisfy::Witness::Impossible => {
+                Err(Error::CouldNotSatisfy)
+            }
+        }
+    }
+
+    /// Enumerate all the spending paths of the Miniscript, as path
+    /// selectors which can be passed to [Miniscript::satisfy_path].
+    ///
+    /// Every `andor`, `or_b`, `or_c`, `or_d` and `or_i` fragment on a path
+    /// contributes one entry.
+    pub fn spending_paths(&self) -> Vec<Vec<usize>> {
+        fn paths<Pk: MiniscriptKey, Ctx: ScriptContext, Ext: Extension<Pk>>(
+            term: &Terminal<Pk, Ctx, Ext>,
+        ) -> Vec<Vec<usize>> {
+            // All the concatenations of a path of `l` followed by a path of `r`
+            let concat =
+                |prefix: Vec<usize>, l: &Terminal<Pk, Ctx, Ext>, r: &Terminal<Pk, Ctx, Ext>| {
+                    let mut ret = vec![];
+                    let r_paths = paths(r);
+                    for l_path in paths(l) {
+                        for r_path in &r_paths {
+                            let mut path = prefix.clone();
+                            path.extend(&l_path);
+                            path.extend(r_path);
+                            ret.push(path);
+                        }
+                    }
+                    ret
+                };
+            // The first branch selected with `0`, the second one with `1`
+            let branches = |l: &Terminal<Pk, Ctx, Ext>, r: &Terminal<Pk, Ctx, Ext>| {
+                let mut ret = vec![];
+                for (selector, sub) in [(0, l), (1, r)].iter() {
+                    for path in paths(sub) {
+                        let mut p = vec![*selector];
+                        p.extend(path);
+                        ret.push(p);
+                    }
+                }
+                ret
+            };
+            match *term {
+                Terminal::Alt(ref sub)
+                | Terminal::Swap(ref sub)
+                | Terminal::Check(ref sub)
+                | Terminal::DupIf(ref sub)
+                | Terminal::Verify(ref sub)
+                | Terminal::NonZero(ref sub)
+                | Terminal::ZeroNotEqual(ref sub) => paths(&sub.node),
+                Terminal::AndV(ref l, ref r) | Terminal::AndB(ref l, ref r) => {
+                    concat(vec![], &l.node, &r.node)
+                }
+                Terminal::AndOr(ref a, ref b, ref c) => {
+                    let mut ret = concat(vec![0], &a.node, &b.node);
+                    for path in paths(&c.node) {
+                        let mut p = vec![1];
+                        p.extend(path);
+                        ret.push(p);
+                    }
+                    ret
+                }
+                Terminal::OrB(ref l, ref r)
+                | Terminal::OrC(ref l, ref r)
+                | Terminal::OrD(ref l, ref r)
+                | Terminal::OrI(ref l, ref r) => branches(&l.node, &r.node),
+                _ => vec![vec![]],
+            }
+        }
+        paths(&self.node)
+    }
+
     /// Attempt to produce a malleable satisfying witness for the
     /// witness script represented by the parse tree
     pub fn satisfy_malleable<S: satisfy::Satisfier<Pk>>(
@@ -461,11 +566,12 @@ mod tests {
 
     use bitcoin;
     use elements::hashes::{hash160, sha256, Hash};
-    use elements::secp256k1_zkp;
+    use elements::{opcodes, script, secp256k1_zkp};
     use std::str;
     use std::str::FromStr;
     use std::sync::Arc;
     use CovenantExt;
+    use {Descriptor, ForEachKey};
 
     type Segwitv0Script = Miniscript<bitcoin::PublicKey, Segwitv0, CovenantExt>;
 
@@ -630,6 +736,29 @@ mod tests {
         assert!(MsStr::from_str("musig").is_err());
         assert!(MsStr::from_str("slip77").is_err());
     }
+
+    #[test]
+    fn musig_key_origins() {
+        type MsStr = Miniscript<String, Segwitv0, CovenantExt>;
+        // Each participant key keeps its own origin
+        let key = "musig([d34db33f/44'/0'/0']a,[12345678/1h/2]b/0/*,musig([aabbccdd]c,d))";
+        let ms = MsStr::from_str(&format!("pk({})", key)).unwrap();
+        assert_eq!(ms.to_string(), format!("pk({})", key));
+        let mut keys = vec![];
+        ms.for_each_key(|k| {
+            keys.push(k.as_key().clone());
+            true
+        });
+        assert_eq!(keys, vec![key.to_owned()]);
+
+        let desc = Descriptor::<String>::from_str(&format!("elwsh(pk({}))", key)).unwrap();
+        assert_eq!(
+            Descriptor::<String>::from_str(&desc.to_string()).unwrap(),
+            desc
+        );
+        assert!(MsStr::from_str("pk(musig([d34db33f/44'/0'/0']a,b)").is_err());
+    }
+
     #[test]
     fn basic() {
         let pk = bitcoin::PublicKey::from_str(
@@ -739,6 +868,56 @@ mod tests {
         assert_eq!(ms, Miniscript::parse_insane(&ms.encode()).unwrap());
     }
 
+    #[test]
+    fn timelock_units() {
+        let pk = pubkeys(1)[0];
+        // Elements, like Bitcoin, reads absolute timelocks from 500000000
+        // on as unix timestamps
+        for &(t, is_height) in [(499_999_999, true), (500_000_000, false)].iter() {
+            let ms =
+                Segwitv0Script::from_str(&format!("and_v(v:pk({}),after({}))", pk, t)).unwrap();
+            let script = script::Builder::new()
+                .push_key(&pk)
+                .push_opcode(opcodes::all::OP_CHECKSIGVERIFY)
+                .push_int(t as i64)
+                .push_opcode(opcodes::all::OP_CLTV)
+                .into_script();
+            assert_eq!(ms.encode(), script);
+            assert_eq!(Segwitv0Script::parse(&script).unwrap(), ms);
+            assert_eq!(ms.ext.timelock_info.cltv_with_height, is_height);
+            assert_eq!(ms.ext.timelock_info.cltv_with_time, !is_height);
+        }
+
+        // No transaction satisfies both a height and a time based lock of
+        // the same kind, but absolute and relative locks are independent
+        let mixed = [
+            "and_v(v:after(499999999),after(500000000))",
+            "and_v(v:older(10),older(4194314))",
+        ];
+        for sub in mixed.iter() {
+            let s = format!("and_v(v:pk({}),{})", pk, sub);
+            assert!(Segwitv0Script::from_str(&s).is_err());
+            assert!(Segwitv0Script::from_str_insane(&s)
+                .unwrap()
+                .has_mixed_timelocks());
+        }
+        let keys = pubkeys(2);
+        let independent = [
+            format!(
+                "or_i(and_v(v:pk({}),after(499999999)),and_v(v:pk({}),after(500000000)))",
+                keys[0], keys[1]
+            ),
+            format!("and_v(v:pk({}),and_v(v:after(500000000),older(10)))", pk),
+            format!(
+                "and_v(v:pk({}),and_v(v:after(499999999),older(4194314)))",
+                pk
+            ),
+        ];
+        for s in independent.iter() {
+            assert!(Segwitv0Script::from_str(s).is_ok());
+        }
+    }
+
     #[test]
     fn pk_alias() {
         let pubkey = pubkeys(1)[0];
@@ -946,6 +1125,11 @@ mod tests {
         assert!(Segwitv0Script::parse_insane(&hex_script("4c0169b2")).is_err()); // non-minimal push
         assert!(Segwitv0Script::parse_insane(&hex_script("0000af0000ae85")).is_err()); // OR not BOOLOR
 
+        // invalid key, only allowed as the prefix of a concatenation
+        assert!(
+            Segwitv0Script::parse_insane(&hex_script(&format!("21{}ac", "01".repeat(33)))).is_err()
+        );
+
         // misc fuzzer problems
         assert!(Segwitv0Script::parse_insane(&hex_script("0000000000af")).is_err());
         assert!(Segwitv0Script::parse_insane(&hex_script("04009a2970af00")).is_err()); // giant CMS key num
@@ -955,6 +1139,40 @@ mod tests {
         .is_err());
     }
 
+    #[test]
+    fn trivial_fragments() {
+        use miniscript::analyzable::Triviality::{AlwaysFalse, AlwaysTrue};
+
+        let trivial = |s: &str| {
+            Miniscript::<String, Segwitv0>::from_str_insane(s)
+                .unwrap()
+                .find_trivial_fragments()
+        };
+        assert_eq!(trivial("and_v(v:pk(A),pk(B))"), vec![]);
+        assert_eq!(trivial("1"), vec![(vec![], AlwaysTrue)]);
+        assert_eq!(trivial("0"), vec![(vec![], AlwaysFalse)]);
+        // The outermost trivial fragment is reported
+        assert_eq!(
+            trivial("and_v(v:pk(A),or_i(pk(B),1))"),
+            vec![(vec![1], AlwaysTrue)]
+        );
+        assert_eq!(
+            trivial("or_d(pk(A),and_v(v:pk(B),0))"),
+            vec![(vec![1], AlwaysFalse)]
+        );
+        assert_eq!(
+            trivial("thresh(2,pk(A),s:pk(B),a:0)"),
+            vec![(vec![2], AlwaysFalse)]
+        );
+        // Constants of wrappers and of and_n are part of their fragment
+        assert_eq!(trivial("tv:pk(A)"), vec![]);
+        assert_eq!(trivial("or_d(pk(A),l:pk(B))"), vec![]);
+        assert_eq!(trivial("or_d(pk(A),u:pk(B))"), vec![]);
+        assert_eq!(trivial("and_n(pk(A),pk(B))"), vec![]);
+        // Unless the whole fragment is trivial
+        assert_eq!(trivial("and_v(v:pk(A),l:1)"), vec![(vec![1], AlwaysTrue)]);
+    }
+
     #[test]
     fn cov_script_rtt() {
         roundtrip(
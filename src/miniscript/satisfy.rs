@@ -18,9 +18,10 @@
 //! scriptpubkeys.
 //!
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::{cmp, i64, mem};
+use std::{cmp, i64, mem, slice};
 
 use bitcoin;
 use elements::hashes::{hash160, ripemd160, sha256, sha256d};
@@ -29,7 +30,8 @@ use elements::{confidential, OutPoint, Script};
 use {MiniscriptKey, ToPublicKey};
 
 use miniscript::limits::{
-    HEIGHT_TIME_THRESHOLD, SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_TYPE_FLAG,
+    HEIGHT_TIME_THRESHOLD, SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_MASK,
+    SEQUENCE_LOCKTIME_TYPE_FLAG,
 };
 use util::witness_size;
 use Error;
@@ -174,28 +176,45 @@ pub trait Satisfier<Pk: MiniscriptKey + ToPublicKey> {
 // Allow use of `()` as a "no conditions available" satisfier
 impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for () {}
 
+/// Whether an input with sequence number `sequence` satisfies the relative
+/// timelock `n` of an `older`. Like in consensus, block based timelocks
+/// are only satisfied by block based sequences and time based ones by
+/// time based sequences.
+pub(crate) fn sequence_satisfies_older(sequence: u32, n: u32) -> bool {
+    if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+        return true;
+    }
+
+    let mask = SEQUENCE_LOCKTIME_MASK | SEQUENCE_LOCKTIME_TYPE_FLAG;
+    let masked_n = n & mask;
+    let masked_seq = sequence & mask;
+    if masked_n < SEQUENCE_LOCKTIME_TYPE_FLAG && masked_seq >= SEQUENCE_LOCKTIME_TYPE_FLAG {
+        false
+    } else {
+        masked_n <= masked_seq
+    }
+}
+
+/// Whether a transaction with locktime `lock_time` satisfies the absolute
+/// timelock `n` of an `after`. Like in consensus, height based timelocks
+/// are only satisfied by height based locktimes and time based ones by
+/// time based locktimes.
+pub(crate) fn lock_time_satisfies_after(lock_time: u32, n: u32) -> bool {
+    // if n > lock_time; we will be returning false anyways
+    if n < HEIGHT_TIME_THRESHOLD && lock_time >= HEIGHT_TIME_THRESHOLD {
+        false
+    } else {
+        n <= lock_time
+    }
+}
+
 /// Newtype around `u32` which implements `Satisfier` using `n` as an
 /// relative locktime
 pub struct Older(pub u32);
 
 impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for Older {
     fn check_older(&self, n: u32) -> bool {
-        if self.0 & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
-            return true;
-        }
-
-        /* If nSequence encodes a relative lock-time, this mask is
-         * applied to extract that lock-time from the sequence field. */
-        const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000ffff;
-
-        let mask = SEQUENCE_LOCKTIME_MASK | SEQUENCE_LOCKTIME_TYPE_FLAG;
-        let masked_n = n & mask;
-        let masked_seq = self.0 & mask;
-        if masked_n < SEQUENCE_LOCKTIME_TYPE_FLAG && masked_seq >= SEQUENCE_LOCKTIME_TYPE_FLAG {
-            false
-        } else {
-            masked_n <= masked_seq
-        }
+        sequence_satisfies_older(self.0, n)
     }
 }
 
@@ -205,12 +224,7 @@ pub struct After(pub u32);
 
 impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for After {
     fn check_after(&self, n: u32) -> bool {
-        // if n > self.0; we will be returning false anyways
-        if n < HEIGHT_TIME_THRESHOLD && self.0 >= HEIGHT_TIME_THRESHOLD {
-            false
-        } else {
-            n <= self.0
-        }
+        lock_time_satisfies_after(self.0, n)
     }
 }
 
@@ -1362,6 +1376,134 @@ impl Satisfaction {
         )
     }
 
+    /// Produce a satisfaction taking the branches chosen by `path`
+    ///
+    /// Every `andor`, `or_b`, `or_c`, `or_d` and `or_i` fragment on the
+    /// satisfied path consumes the next entry of `path`: `0` selects the
+    /// first (left) branch and `1` the second (right) one. Errors if
+    /// `path` has no more entries for such a fragment.
+    pub(super) fn satisfy_path<Pk, Ctx, Sat, Ext>(
+        term: &Terminal<Pk, Ctx, Ext>,
+        stfr: &Sat,
+        root_has_sig: bool,
+        path: &mut slice::Iter<usize>,
+    ) -> Result<Self, Error>
+    where
+        Pk: MiniscriptKey + ToPublicKey,
+        Ctx: ScriptContext,
+        Sat: Satisfier<Pk>,
+        Ext: Extension<Pk>,
+    {
+        let dissatisfy = |term: &Terminal<Pk, Ctx, Ext>| {
+            Self::dissatisfy_helper(
+                term,
+                stfr,
+                root_has_sig,
+                &mut Satisfaction::minimum,
+                &mut Satisfaction::thresh,
+            )
+        };
+        let branch = |path: &mut slice::Iter<usize>| match path.next() {
+            Some(&b) if b > 1 => Err(Error::Unexpected(format!(
+                "branch {} in path selector, expected 0 or 1",
+                b
+            ))),
+            Some(&b) => Ok(b),
+            None => Err(Error::Unexpected(
+                "path selector too short for the chosen path".to_string(),
+            )),
+        };
+        match *term {
+            Terminal::Alt(ref sub)
+            | Terminal::Swap(ref sub)
+            | Terminal::Check(ref sub)
+            | Terminal::Verify(ref sub)
+            | Terminal::NonZero(ref sub)
+            | Terminal::ZeroNotEqual(ref sub) => {
+                Self::satisfy_path(&sub.node, stfr, root_has_sig, path)
+            }
+            Terminal::DupIf(ref sub) => {
+                let sat = Self::satisfy_path(&sub.node, stfr, root_has_sig, path)?;
+                Ok(Satisfaction {
+                    stack: Witness::combine(sat.stack, Witness::push_1()),
+                    has_sig: sat.has_sig,
+                })
+            }
+            Terminal::AndV(ref l, ref r) | Terminal::AndB(ref l, ref r) => {
+                let l_sat = Self::satisfy_path(&l.node, stfr, root_has_sig, path)?;
+                let r_sat = Self::satisfy_path(&r.node, stfr, root_has_sig, path)?;
+                Ok(Satisfaction {
+                    stack: Witness::combine(r_sat.stack, l_sat.stack),
+                    has_sig: l_sat.has_sig || r_sat.has_sig,
+                })
+            }
+            Terminal::AndOr(ref a, ref b, ref c) => match branch(path)? {
+                0 => {
+                    let a_sat = Self::satisfy_path(&a.node, stfr, root_has_sig, path)?;
+                    let b_sat = Self::satisfy_path(&b.node, stfr, root_has_sig, path)?;
+                    Ok(Satisfaction {
+                        stack: Witness::combine(b_sat.stack, a_sat.stack),
+                        has_sig: a_sat.has_sig || b_sat.has_sig,
+                    })
+                }
+                _ => {
+                    let a_nsat = dissatisfy(&a.node);
+                    let c_sat = Self::satisfy_path(&c.node, stfr, root_has_sig, path)?;
+                    Ok(Satisfaction {
+                        stack: Witness::combine(c_sat.stack, a_nsat.stack),
+                        has_sig: a_nsat.has_sig || c_sat.has_sig,
+                    })
+                }
+            },
+            Terminal::OrB(ref l, ref r) => match branch(path)? {
+                0 => {
+                    let l_sat = Self::satisfy_path(&l.node, stfr, root_has_sig, path)?;
+                    let r_nsat = dissatisfy(&r.node);
+                    Ok(Satisfaction {
+                        stack: Witness::combine(r_nsat.stack, l_sat.stack),
+                        has_sig: l_sat.has_sig,
+                    })
+                }
+                _ => {
+                    let l_nsat = dissatisfy(&l.node);
+                    let r_sat = Self::satisfy_path(&r.node, stfr, root_has_sig, path)?;
+                    Ok(Satisfaction {
+                        stack: Witness::combine(r_sat.stack, l_nsat.stack),
+                        has_sig: r_sat.has_sig,
+                    })
+                }
+            },
+            Terminal::OrD(ref l, ref r) | Terminal::OrC(ref l, ref r) => match branch(path)? {
+                0 => Self::satisfy_path(&l.node, stfr, root_has_sig, path),
+                _ => {
+                    let l_nsat = dissatisfy(&l.node);
+                    let r_sat = Self::satisfy_path(&r.node, stfr, root_has_sig, path)?;
+                    Ok(Satisfaction {
+                        stack: Witness::combine(r_sat.stack, l_nsat.stack),
+                        has_sig: r_sat.has_sig,
+                    })
+                }
+            },
+            Terminal::OrI(ref l, ref r) => match branch(path)? {
+                0 => {
+                    let l_sat = Self::satisfy_path(&l.node, stfr, root_has_sig, path)?;
+                    Ok(Satisfaction {
+                        stack: Witness::combine(l_sat.stack, Witness::push_1()),
+                        has_sig: l_sat.has_sig,
+                    })
+                }
+                _ => {
+                    let r_sat = Self::satisfy_path(&r.node, stfr, root_has_sig, path)?;
+                    Ok(Satisfaction {
+                        stack: Witness::combine(r_sat.stack, Witness::push_0()),
+                        has_sig: r_sat.has_sig,
+                    })
+                }
+            },
+            _ => Ok(Self::satisfy(term, stfr, root_has_sig)),
+        }
+    }
+
     /// Produce a satisfaction(possibly malleable)
     pub(super) fn satisfy_mall<
         Pk: MiniscriptKey + ToPublicKey,
@@ -1382,3 +1524,189 @@ impl Satisfaction {
         )
     }
 }
+
+/// Checks that a path selector has been fully consumed by
+/// [Satisfaction::satisfy_path], or is empty for descriptors without
+/// branches to choose from
+pub(crate) fn check_path_consumed(path: &[usize]) -> Result<(), Error> {
+    if path.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Unexpected(format!(
+            "{} unused entries in path selector",
+            path.len()
+        )))
+    }
+}
+
+/// A lookup attempted on a [`LoggingSatisfier`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Lookup<Pk: MiniscriptKey> {
+    /// Signature for the given key
+    Sig(Pk),
+    /// Public key for the given key hash
+    PkhPk(Pk::Hash),
+    /// Public key and signature for the given key hash
+    PkhSig(Pk::Hash),
+    /// SHA256 preimage
+    Sha256(sha256::Hash),
+    /// HASH256 preimage
+    Hash256(sha256d::Hash),
+    /// RIPEMD160 preimage
+    Ripemd160(ripemd160::Hash),
+    /// HASH160 preimage
+    Hash160(hash160::Hash),
+    /// Relative timelock check
+    Older(u32),
+    /// Absolute timelock check
+    After(u32),
+    /// Transaction data required by covenants, identified by the name of
+    /// the `Satisfier` method without its `lookup_` prefix
+    TxData(&'static str),
+}
+
+/// A `Satisfier` wrapper which forwards every call to the wrapped satisfier
+/// and records each lookup along with whether it succeeded
+///
+/// Useful to find out which signatures, preimages or timelocks are missing
+/// when a satisfaction unexpectedly fails.
+pub struct LoggingSatisfier<Pk: MiniscriptKey, S> {
+    inner: S,
+    log: RefCell<Vec<(Lookup<Pk>, bool)>>,
+}
+
+impl<Pk: MiniscriptKey, S> LoggingSatisfier<Pk, S> {
+    /// Wrap a satisfier
+    pub fn new(inner: S) -> Self {
+        LoggingSatisfier {
+            inner,
+            log: RefCell::new(vec![]),
+        }
+    }
+
+    /// All lookups performed so far, in order, along with whether they succeeded
+    pub fn lookups(&self) -> Vec<(Lookup<Pk>, bool)> {
+        self.log.borrow().clone()
+    }
+
+    /// All lookups performed so far which failed, without duplicates
+    pub fn missing(&self) -> Vec<Lookup<Pk>> {
+        let mut missing = vec![];
+        for &(ref lookup, found) in self.log.borrow().iter() {
+            if !found && !missing.contains(lookup) {
+                missing.push(lookup.clone());
+            }
+        }
+        missing
+    }
+
+    /// Clear the recorded lookups
+    pub fn clear(&self) {
+        self.log.borrow_mut().clear();
+    }
+
+    /// Unwrap the inner satisfier
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn record<T>(&self, lookup: Lookup<Pk>, result: Option<T>) -> Option<T> {
+        self.log.borrow_mut().push((lookup, result.is_some()));
+        result
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for LoggingSatisfier<Pk, S> {
+    fn lookup_sig(&self, p: &Pk) -> Option<ElementsSig> {
+        self.record(Lookup::Sig(p.clone()), self.inner.lookup_sig(p))
+    }
+
+    fn lookup_pkh_pk(&self, pkh: &Pk::Hash) -> Option<Pk> {
+        self.record(Lookup::PkhPk(pkh.clone()), self.inner.lookup_pkh_pk(pkh))
+    }
+
+    fn lookup_pkh_sig(&self, pkh: &Pk::Hash) -> Option<(bitcoin::PublicKey, ElementsSig)> {
+        self.record(Lookup::PkhSig(pkh.clone()), self.inner.lookup_pkh_sig(pkh))
+    }
+
+    fn lookup_sha256(&self, h: sha256::Hash) -> Option<Preimage32> {
+        self.record(Lookup::Sha256(h), self.inner.lookup_sha256(h))
+    }
+
+    fn lookup_hash256(&self, h: sha256d::Hash) -> Option<Preimage32> {
+        self.record(Lookup::Hash256(h), self.inner.lookup_hash256(h))
+    }
+
+    fn lookup_ripemd160(&self, h: ripemd160::Hash) -> Option<Preimage32> {
+        self.record(Lookup::Ripemd160(h), self.inner.lookup_ripemd160(h))
+    }
+
+    fn lookup_hash160(&self, h: hash160::Hash) -> Option<Preimage32> {
+        self.record(Lookup::Hash160(h), self.inner.lookup_hash160(h))
+    }
+
+    fn check_older(&self, t: u32) -> bool {
+        let res = self.inner.check_older(t);
+        self.log.borrow_mut().push((Lookup::Older(t), res));
+        res
+    }
+
+    fn check_after(&self, t: u32) -> bool {
+        let res = self.inner.check_after(t);
+        self.log.borrow_mut().push((Lookup::After(t), res));
+        res
+    }
+
+    fn lookup_nversion(&self) -> Option<u32> {
+        self.record(Lookup::TxData("nversion"), self.inner.lookup_nversion())
+    }
+
+    fn lookup_hashprevouts(&self) -> Option<sha256d::Hash> {
+        self.record(
+            Lookup::TxData("hashprevouts"),
+            self.inner.lookup_hashprevouts(),
+        )
+    }
+
+    fn lookup_hashsequence(&self) -> Option<sha256d::Hash> {
+        self.record(
+            Lookup::TxData("hashsequence"),
+            self.inner.lookup_hashsequence(),
+        )
+    }
+
+    fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
+        self.record(
+            Lookup::TxData("hashissuances"),
+            self.inner.lookup_hashissuances(),
+        )
+    }
+
+    fn lookup_outpoint(&self) -> Option<OutPoint> {
+        self.record(Lookup::TxData("outpoint"), self.inner.lookup_outpoint())
+    }
+
+    fn lookup_scriptcode(&self) -> Option<&Script> {
+        self.record(Lookup::TxData("scriptcode"), self.inner.lookup_scriptcode())
+    }
+
+    fn lookup_value(&self) -> Option<confidential::Value> {
+        self.record(Lookup::TxData("value"), self.inner.lookup_value())
+    }
+
+    fn lookup_nsequence(&self) -> Option<u32> {
+        self.record(Lookup::TxData("nsequence"), self.inner.lookup_nsequence())
+    }
+
+    fn lookup_outputs(&self) -> Option<&[elements::TxOut]> {
+        self.record(Lookup::TxData("outputs"), self.inner.lookup_outputs())
+    }
+
+    fn lookup_nlocktime(&self) -> Option<u32> {
+        self.record(Lookup::TxData("nlocktime"), self.inner.lookup_nlocktime())
+    }
+
+    fn lookup_sighashu32(&self) -> Option<u32> {
+        self.record(Lookup::TxData("sighashu32"), self.inner.lookup_sighashu32())
+    }
+}
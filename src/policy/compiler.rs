@@ -1195,10 +1195,11 @@ mod tests {
     use std::str::FromStr;
     use std::string::String;
 
+    use descriptor::DescriptorType;
     use miniscript::{satisfy, Legacy, Segwitv0};
     use policy::Liftable;
     use script_num_size;
-    use ElementsSig;
+    use {Descriptor, ElementsSig};
 
     type SPolicy = Concrete<String>;
     type BPolicy = Concrete<bitcoin::PublicKey>;
@@ -1582,6 +1583,27 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn compile_legacy() {
+        let (keys, _) = pubkeys_and_a_sig(20);
+        let pol = BPolicy::from_str(&format!(
+            "or(pk({}),and(pk({}),older(144)))",
+            keys[0], keys[1]
+        ))
+        .unwrap();
+        let desc = pol.compile_legacy().unwrap();
+        assert_eq!(desc.desc_type(), DescriptorType::Sh);
+        let ms: Miniscript<bitcoin::PublicKey, Legacy> = pol.compile().unwrap();
+        assert_eq!(desc, Descriptor::new_sh(ms).unwrap());
+
+        // A 1-of-20 threshold of single keys does not fit in 520 bytes
+        let pks: Vec<BPolicy> = keys.iter().map(|pk| Concrete::Key(*pk)).collect();
+        let pol = Concrete::Threshold(1, pks);
+        assert!(pol.compile_legacy().is_err());
+        // .. while it does under segwit
+        assert!(pol.compile::<Segwitv0>().is_ok());
+    }
 }
 
 #[cfg(all(test, feature = "unstable"))]
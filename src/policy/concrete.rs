@@ -26,7 +26,12 @@ use expression::{self, FromTree};
 use miniscript::limits::{HEIGHT_TIME_THRESHOLD, SEQUENCE_LOCKTIME_TYPE_FLAG};
 use miniscript::types::extra_props::TimeLockInfo;
 #[cfg(feature = "compiler")]
-use {miniscript::ScriptContext, policy::compiler, policy::compiler::CompilerError, Miniscript};
+use {
+    miniscript::{Legacy, ScriptContext},
+    policy::compiler,
+    policy::compiler::CompilerError,
+    Descriptor, Miniscript,
+};
 use {Error, ForEach, ForEachKey, MiniscriptKey};
 /// Concrete policy which corresponds directly to a Miniscript structure,
 /// and whose disjunctions are annotated with satisfaction probabilities
@@ -132,6 +137,17 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
             _ => compiler::best_compilation(self),
         }
     }
+
+    /// Compile the policy into a legacy (non-segwit) `sh` descriptor
+    ///
+    /// The compilation respects the legacy limits of `MAX_SCRIPT_ELEMENT_SIZE`
+    /// (520) bytes for the redeem script and `MAX_OPS_PER_SCRIPT` (201) opcodes.
+    /// Errors if the policy cannot be compiled within these limits.
+    #[cfg(feature = "compiler")]
+    pub fn compile_legacy(&self) -> Result<Descriptor<Pk>, Error> {
+        let ms = self.compile::<Legacy>()?;
+        Descriptor::new_sh(ms)
+    }
 }
 
 impl<Pk: MiniscriptKey> ForEachKey<Pk> for Policy<Pk> {
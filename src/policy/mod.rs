@@ -264,9 +264,9 @@ impl<Pk: MiniscriptKey> Liftable<Pk> for BtcPolicy<Pk> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Concrete, Semantic};
+    use super::{Concrete, Liftable, Semantic};
     use std::str::FromStr;
-    use DummyKey;
+    use {CovenantExt, Descriptor, DummyKey, Miniscript, Segwitv0};
 
     type ConcretePol = Concrete<DummyKey>;
     type SemanticPol = Semantic<DummyKey>;
@@ -298,6 +298,30 @@ mod tests {
         // thresh with k = 2
         assert!(ConcretePol::from_str("thresh(2,after(1000000000),after(100),pk())").is_err());
     }
+
+    #[test]
+    fn lift_errors() {
+        // Fragments without a semantic representation are reported as
+        // errors rather than panicking
+        let desc = Descriptor::<String>::from_str("elcovwsh(A,pk(B))").unwrap();
+        assert!(desc.lift().is_err());
+        let ms = Miniscript::<String, Segwitv0, CovenantExt>::from_str_insane(
+            "and_v(v:pk(A),ver_eq(2))",
+        )
+        .unwrap();
+        assert!(ms.lift().is_err());
+        // So are spending paths mixing heights and times
+        let ms = Miniscript::<String, Segwitv0>::from_str_insane(
+            "and_v(v:after(100),after(1000000000))",
+        )
+        .unwrap();
+        assert!(ms.lift().is_err());
+        assert!(ConcretePol::from_str("and(pk(),pk())")
+            .unwrap()
+            .lift()
+            .is_ok());
+    }
+
     #[test]
     fn policy_rtt_tests() {
         concrete_policy_rtt("pk()");
@@ -14,14 +14,16 @@
 
 //! Abstract Policies
 
+use std::collections::HashSet;
 use std::str::FromStr;
-use std::{fmt, str};
+use std::{fmt, hash, str};
 
 use elements::hashes::hex::FromHex;
 use elements::hashes::{hash160, ripemd160, sha256, sha256d};
 
 use super::concrete::PolicyError;
 use errstr;
+use miniscript::satisfy::{lock_time_satisfies_after, sequence_satisfies_older};
 use Error;
 use {expression, ForEach, ForEachKey, MiniscriptKey};
 
@@ -472,20 +474,77 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
         ret
     }
 
+    /// Helper function to do the recursion in `absolute_timelocks`.
+    fn real_absolute_timelocks(&self) -> Vec<u32> {
+        match *self {
+            Policy::Unsatisfiable
+            | Policy::Trivial
+            | Policy::KeyHash(..)
+            | Policy::Sha256(..)
+            | Policy::Hash256(..)
+            | Policy::Ripemd160(..)
+            | Policy::Hash160(..) => vec![],
+            Policy::Older(..) => vec![],
+            Policy::After(t) => vec![t],
+            Policy::Threshold(_, ref subs) => subs.iter().fold(vec![], |mut acc, x| {
+                acc.extend(x.real_absolute_timelocks());
+                acc
+            }),
+        }
+    }
+
+    /// Returns a list of all absolute timelocks, not including 0,
+    /// which appear in the policy
+    pub fn absolute_timelocks(&self) -> Vec<u32> {
+        let mut ret = self.real_absolute_timelocks();
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+
     /// Filter a policy by eliminating relative timelock constraints
     /// that are not satisfied at the given age.
-    pub fn at_age(mut self, time: u32) -> Policy<Pk> {
+    ///
+    /// The age is the sequence number of the spending input: like in
+    /// consensus, block based timelocks are only satisfied by block based
+    /// ages and time based ones by time based ages.
+    pub fn at_age(mut self, age: u32) -> Policy<Pk> {
         self = match self {
             Policy::Older(t) => {
-                if t > time {
-                    Policy::Unsatisfiable
-                } else {
+                if sequence_satisfies_older(age, t) {
                     Policy::Older(t)
+                } else {
+                    Policy::Unsatisfiable
                 }
             }
             Policy::Threshold(k, subs) => {
-                Policy::Threshold(k, subs.into_iter().map(|sub| sub.at_age(time)).collect())
+                Policy::Threshold(k, subs.into_iter().map(|sub| sub.at_age(age)).collect())
+            }
+            x => x,
+        };
+        self.normalized()
+    }
+
+    /// Filter a policy by eliminating absolute timelock constraints
+    /// that are not satisfied by the given transaction locktime.
+    ///
+    /// Like in consensus, height based timelocks are only satisfied by
+    /// height based locktimes and time based ones by time based locktimes.
+    pub fn at_lock_time(mut self, lock_time: u32) -> Policy<Pk> {
+        self = match self {
+            Policy::After(t) => {
+                if lock_time_satisfies_after(lock_time, t) {
+                    Policy::After(t)
+                } else {
+                    Policy::Unsatisfiable
+                }
             }
+            Policy::Threshold(k, subs) => Policy::Threshold(
+                k,
+                subs.into_iter()
+                    .map(|sub| sub.at_lock_time(lock_time))
+                    .collect(),
+            ),
             x => x,
         };
         self.normalized()
@@ -526,6 +585,63 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
             }
         }
     }
+
+    /// Enumerate the minimal sets of keys whose signatures can satisfy the
+    /// policy. Timelocks and hash preimages are assumed to be available and
+    /// do not appear in the returned sets. An unsatisfiable policy has no
+    /// key sets, while a policy without keys returns a single empty set.
+    pub fn satisfying_key_sets(&self) -> Vec<HashSet<Pk::Hash>> {
+        match *self {
+            Policy::Unsatisfiable => vec![],
+            Policy::KeyHash(ref pkh) => {
+                let mut set = HashSet::new();
+                set.insert(pkh.clone());
+                vec![set]
+            }
+            Policy::Trivial
+            | Policy::After(..)
+            | Policy::Older(..)
+            | Policy::Sha256(..)
+            | Policy::Hash256(..)
+            | Policy::Ripemd160(..)
+            | Policy::Hash160(..) => vec![HashSet::new()],
+            Policy::Threshold(k, ref subs) => {
+                let sub_sets: Vec<_> = subs.iter().map(Policy::satisfying_key_sets).collect();
+                let mut ret = vec![];
+                threshold_key_sets(k, &sub_sets, &HashSet::new(), &mut ret);
+                ret
+            }
+        }
+    }
+}
+
+/// Collect the minimal unions of key sets picking one set from each of
+/// `k` of the `subs`, extending the accumulated set `acc`
+fn threshold_key_sets<H: Clone + Eq + hash::Hash>(
+    k: usize,
+    subs: &[Vec<HashSet<H>>],
+    acc: &HashSet<H>,
+    ret: &mut Vec<HashSet<H>>,
+) {
+    if k == 0 {
+        // Only keep sets which are not a superset of some other set
+        if ret.iter().any(|set| set.is_subset(acc)) {
+            return;
+        }
+        ret.retain(|set| !acc.is_subset(set));
+        ret.push(acc.clone());
+        return;
+    }
+    if subs.len() < k {
+        return;
+    }
+    // Either the first sub-policy is satisfied...
+    for set in &subs[0] {
+        let union = acc.union(set).cloned().collect();
+        threshold_key_sets(k - 1, &subs[1..], &union, ret);
+    }
+    // ...or it is not
+    threshold_key_sets(k, &subs[1..], acc, ret);
 }
 
 impl<Pk: MiniscriptKey> Policy<Pk> {
@@ -589,9 +705,39 @@ mod tests {
         assert_eq!(policy.clone().at_age(999), Policy::Unsatisfiable);
         assert_eq!(policy.clone().at_age(1000), policy.clone());
         assert_eq!(policy.clone().at_age(10000), policy.clone());
+        // A time based age does not satisfy a block based timelock
+        assert_eq!(
+            policy.clone().at_age((1 << 22) | 10000),
+            Policy::Unsatisfiable
+        );
         assert_eq!(policy.n_keys(), 0);
         assert_eq!(policy.minimum_n_keys(), 0);
 
+        let policy = StringPolicy::from_str("older(4194314)").unwrap();
+        assert_eq!(policy.clone().at_age(10000), Policy::Unsatisfiable);
+        assert_eq!(policy.clone().at_age((1 << 22) | 9), Policy::Unsatisfiable);
+        assert_eq!(policy.clone().at_age((1 << 22) | 10), policy);
+
+        let policy = StringPolicy::from_str("or(pkh(),after(1000))").unwrap();
+        assert_eq!(
+            policy.clone().at_lock_time(999),
+            Policy::KeyHash("".to_owned())
+        );
+        assert_eq!(
+            policy.clone().at_lock_time(1000),
+            policy.clone().normalized()
+        );
+        assert_eq!(
+            policy.at_lock_time(500_000_000),
+            Policy::KeyHash("".to_owned())
+        );
+        let policy = StringPolicy::from_str("after(500000010)").unwrap();
+        assert_eq!(
+            policy.clone().at_lock_time(499_999_999),
+            Policy::Unsatisfiable
+        );
+        assert_eq!(policy.clone().at_lock_time(500_000_010), policy);
+
         let policy = StringPolicy::from_str("or(pkh(),older(1000))").unwrap();
         assert_eq!(
             policy,
@@ -631,6 +777,10 @@ mod tests {
             policy.relative_timelocks(),
             vec![1000, 2000, 10000] //sorted and dedup'd
         );
+
+        let policy = StringPolicy::from_str("or(and(older(1000),after(500)),after(100))").unwrap();
+        assert_eq!(policy.absolute_timelocks(), vec![100, 500]);
+        assert_eq!(policy.relative_timelocks(), vec![1000]);
     }
 
     #[test]
@@ -710,4 +860,38 @@ mod tests {
         assert!(auth_alice.entails(htlc_pol.clone()).unwrap());
         assert!(htlc_pol.entails(control_alice).unwrap());
     }
+
+    #[test]
+    fn satisfying_key_sets() {
+        fn key_sets(s: &str) -> Vec<Vec<String>> {
+            let pol = StringPolicy::from_str(s).unwrap();
+            pol.satisfying_key_sets()
+                .into_iter()
+                .map(|set| {
+                    let mut keys: Vec<_> = set.into_iter().collect();
+                    keys.sort();
+                    keys
+                })
+                .collect()
+        }
+        fn set(keys: &[&str]) -> Vec<String> {
+            keys.iter().map(|k| k.to_string()).collect()
+        }
+
+        assert_eq!(key_sets("UNSATISFIABLE"), Vec::<Vec<String>>::new());
+        assert_eq!(key_sets("older(10)"), vec![set(&[])]);
+        assert_eq!(
+            key_sets("thresh(2,pkh(A),pkh(B),pkh(C))"),
+            vec![set(&["A", "B"]), set(&["A", "C"]), set(&["B", "C"])]
+        );
+        // Timelocks are ignored and supersets are not minimal
+        assert_eq!(
+            key_sets("or(pkh(A),and(pkh(A),and(pkh(B),older(100))))"),
+            vec![set(&["A"])]
+        );
+        assert_eq!(
+            key_sets("and(or(pkh(A),pkh(B)),or(pkh(A),and(pkh(C),after(10))))"),
+            vec![set(&["A"]), set(&["B", "C"])]
+        );
+    }
 }
@@ -34,7 +34,7 @@ use Descriptor;
 use Miniscript;
 use {BareCtx, Legacy, MiniscriptKey, Segwitv0};
 // Get the scriptpubkey for the pset input
-fn get_scriptpubkey(pset: &Pset, index: usize) -> Result<&Script, InputError> {
+pub(crate) fn get_scriptpubkey(pset: &Pset, index: usize) -> Result<&Script, InputError> {
     let script_pubkey;
     let inp = &pset.inputs[index];
     if let Some(ref witness_utxo) = inp.witness_utxo {
@@ -49,7 +49,7 @@ fn get_scriptpubkey(pset: &Pset, index: usize) -> Result<&Script, InputError> {
 }
 
 // Get the amount being spent for the pset input
-fn get_amt(pset: &Pset, index: usize) -> Result<confidential::Value, InputError> {
+pub(crate) fn get_amt(pset: &Pset, index: usize) -> Result<confidential::Value, InputError> {
     let amt;
     let inp = &pset.inputs[index];
     if let Some(ref witness_utxo) = inp.witness_utxo {